@@ -2,8 +2,6 @@
 #import bevy_sprite::mesh2d_view_bindings::globals
 #import "shaders/noise.wgsl"::smoothNoise
 
-const NIGHT_INTENSITY: f32 = 0.9;
-
 const CLOUD_SPEED: f32 = 0.2;
 const CLOUD_SCALE: f32 = 200.0;
 const CLOUD_SPARSITY: f32 = 0.6; // higher = fewer clouds, more gaps
@@ -11,6 +9,20 @@ const CLOUD_DENSITY: f32 = 0.4; // lower = denser clouds
 const CLOUD_SHADOW_LENGTH: f32 = 100.0;
 const CLOUD_SHADOW_MIN_OPACITY: f32 = 0.45;
 
+const RAIN_SPEED: f32 = 600.0;
+const RAIN_SLANT: f32 = 0.2;
+const RAIN_SPACING: f32 = 6.0;
+const RAIN_LENGTH: f32 = 24.0;
+const RAIN_OPACITY: f32 = 0.35;
+
+const SNOW_SPEED: f32 = 1.5;
+const SNOW_SCALE: f32 = 12.0;
+const SNOW_OPACITY: f32 = 0.8;
+
+const STORM_DARKNESS: f32 = 0.35;
+const LIGHTNING_FLASH_RATE: f32 = 4.0;
+const HEAT_OPACITY: f32 = 0.12;
+
 const PI: f32 = 3.141592653589793;
 
 struct ChunkWeatherMaterial {
@@ -18,6 +30,10 @@ struct ChunkWeatherMaterial {
     time_of_day: f32,
     wind: vec2<f32>,
     cloud_opacity: f32,
+    rain: f32,
+    snow: f32,
+    storm: f32,
+    heat: f32,
 };
 
 @group(#{MATERIAL_BIND_GROUP}) @binding(0) var<uniform> material: ChunkWeatherMaterial;
@@ -35,13 +51,48 @@ fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
 
     let shadow_blend = smoothstep(0.1, 0.01, clouds);
     let cloud_alpha = clouds + clouds_shadow * shadow_blend * max(material.cloud_opacity, CLOUD_SHADOW_MIN_OPACITY);
-    let clouds_color = vec4(vec3f(clouds), cloud_alpha);
+    var color = vec4(vec3f(clouds), cloud_alpha);
+
+    // Weather events, night is handled by the tiles lighting
+    color = mix(color, vec4(vec3(0.0), 1.0), material.storm * STORM_DARKNESS);
+    color = mix(color, vec4(1.0, 0.5, 0.1, 1.0), material.heat * HEAT_OPACITY);
+    color = mix(color, vec4(0.7, 0.75, 0.85, 1.0), material.rain * rain(p) * RAIN_OPACITY);
+    color = mix(color, vec4(1.0), material.snow * snow(p) * SNOW_OPACITY);
+    color = mix(color, vec4(1.0), material.storm * lightning());
+
+    return color;
+}
 
-    let night_cycle = (cos((material.time_of_day - 0.5) * 2.0 * PI) * 0.5) + 0.5;
-    let night_alpha = (1.0 - night_cycle) * NIGHT_INTENSITY;
-    let night_color = vec4(vec3(0.0), 1.0);
+fn hash(p: vec2<f32>) -> f32 {
+    return fract(sin(dot(p, vec2(12.9898, 78.233))) * 43758.5453);
+}
+
+// Slanted streaks falling down
+fn rain(p: vec2<f32>) -> f32 {
+    let q = vec2(p.x + p.y * RAIN_SLANT, p.y + globals.time * RAIN_SPEED);
+    let cell = floor(q / vec2(RAIN_SPACING, RAIN_LENGTH));
+    let local = fract(q / vec2(RAIN_SPACING, RAIN_LENGTH));
+
+    let visible = step(0.8, hash(cell));
+    let streak = smoothstep(0.2, 0.0, abs(local.x - 0.5));
+    return visible * streak;
+}
+
+// Flakes drifting down and sideways
+fn snow(p: vec2<f32>) -> f32 {
+    let q = p / SNOW_SCALE + vec2(sin(globals.time + p.y * 0.01) * 0.5, globals.time * SNOW_SPEED);
+    let cell = floor(q);
+    let local = fract(q) - 0.5;
+
+    let offset = vec2(hash(cell), hash(cell + 17.0)) - 0.5;
+    let flake = smoothstep(0.15, 0.05, length(local - offset * 0.6));
+    return flake * step(0.5, hash(cell + 3.0));
+}
 
-    return mix(clouds_color, night_color, night_alpha);
+// Short random flashes of the whole sky
+fn lightning() -> f32 {
+    let flash = step(0.97, hash(vec2(floor(globals.time * LIGHTNING_FLASH_RATE), material.seed)));
+    return flash * 0.4;
 }
 
 fn clouds_noise(p: vec2<f32>) -> f32 {
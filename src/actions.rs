@@ -1,13 +1,16 @@
 use bevy::prelude::*;
 
 use crate::{
-    Dweller, DwellersSelected, OpenWorkstationUi, TILE_SIZE, TilemapData,
+    CameraControl, Dweller, DwellersSelected, Layer, OpenWorkstationUi, TILE_SIZE, TilemapData,
+    blueprints::Blueprint,
     data::{MobId, ObjectId, TileId},
     extract_ok, extract_some,
     mobs::{Mob, MobBundle},
+    room_designer::{RoomDesigner, place_room},
+    task_index::TaskIndex,
     tasks::{BuildResult, Task, TaskBundle, TaskKind, TaskNeeds},
     ui::{CoordinatesUi, UiButton},
-    utils::transform_to_pos,
+    utils::{pascal_case_to_title_case, transform_to_pos},
 };
 
 const MAX_ACTIONS: usize = 2048;
@@ -19,6 +22,9 @@ pub enum ActionKind {
     Cancel,
     Task(TaskKind),
     TaskWithNeeds(TaskKind, TaskNeeds),
+    WalledRoom,
+    PlaceRoom(usize),
+    SaveRoom,
     DebugBuild(BuildResult),
     DebugSpawn(MobId),
 }
@@ -49,7 +55,7 @@ pub fn keyboard_current_action(
 #[derive(Resource, Default, Debug)]
 pub struct CurrentAction {
     pub kind: ActionKind,
-    pub pos_start: Option<IVec2>,
+    pub pos_start: Option<IVec3>,
 }
 
 impl CurrentAction {
@@ -67,6 +73,8 @@ pub fn terrain_pointer_down(
     q_camera: Query<(&Camera, &GlobalTransform)>,
     q_ui_buttons: Query<(), With<UiButton>>,
     mut coordinates_ui: Single<&mut Text, With<CoordinatesUi>>,
+    tilemap_data: Res<TilemapData>,
+    camera_control: Res<CameraControl>,
 ) {
     if q_ui_buttons.contains(pointer_press.entity) {
         return;
@@ -80,9 +88,21 @@ pub fn terrain_pointer_down(
                     camera_transform,
                     pointer_press.pointer_location.position
                 ));
-            let pos = (world_position / TILE_SIZE).floor().as_ivec2();
-
-            coordinates_ui.0 = format!("({}, {})", pos.x, pos.y);
+            let pos = (world_position / TILE_SIZE)
+                .floor()
+                .as_ivec2()
+                .extend(camera_control.layer);
+
+            coordinates_ui.0 = match tilemap_data.biome(pos) {
+                Some(biome) => format!(
+                    "({}, {}, {}) {}",
+                    pos.x,
+                    pos.y,
+                    pos.z,
+                    pascal_case_to_title_case(&format!("{biome:?}"))
+                ),
+                None => format!("({}, {}, {})", pos.x, pos.y, pos.z),
+            };
 
             // Start selection
             current_action.pos_start = Some(pos);
@@ -104,9 +124,11 @@ pub fn terrain_pointer_up(
     mut dwellers_selected: ResMut<DwellersSelected>,
     mut tilemap_data: ResMut<TilemapData>,
     q_camera: Query<(&Camera, &GlobalTransform)>,
-    q_tasks: Query<(Entity, &Task)>,
-    q_mobs: Query<(Entity, &Transform), With<Mob>>,
-    mut q_dwellers: Query<(Entity, &mut Dweller, &Transform)>,
+    task_index: Res<TaskIndex>,
+    mut room_designer: ResMut<RoomDesigner>,
+    q_mobs: Query<(Entity, &Mob, &Transform, &Layer)>,
+    mut q_dwellers: Query<(Entity, &mut Dweller, &Transform, &Layer)>,
+    camera_control: Res<CameraControl>,
 ) {
     if matches!(pointer_release.button, PointerButton::Primary) {
         let (camera, camera_transform) = extract_ok!(q_camera.single());
@@ -114,13 +136,16 @@ pub fn terrain_pointer_up(
             camera
                 .viewport_to_world_2d(camera_transform, pointer_release.pointer_location.position)
         );
-        let pos_end = (world_position / TILE_SIZE).floor().as_ivec2();
+        let pos_end = (world_position / TILE_SIZE)
+            .floor()
+            .as_ivec2()
+            .extend(camera_control.layer);
 
-        // Confirm selection
+        // Confirm selection, on the layer where it ends
         let pos_start = extract_some!(current_action.pos_start);
 
-        let pos_min = IVec2::new(pos_start.x.min(pos_end.x), pos_start.y.min(pos_end.y));
-        let pos_max = IVec2::new(pos_start.x.max(pos_end.x), pos_start.y.max(pos_end.y));
+        let pos_min = pos_start.min(pos_end).with_z(pos_end.z);
+        let pos_max = pos_start.max(pos_end).with_z(pos_end.z);
 
         let mut max_tasks = match current_action.kind {
             ActionKind::Task(TaskKind::Walk) => {
@@ -137,9 +162,51 @@ pub fn terrain_pointer_up(
             dwellers_selected.reset();
         }
 
+        if matches!(current_action.kind, ActionKind::WalledRoom) {
+            let blueprint = Blueprint::walled_room(
+                pos_min,
+                pos_max,
+                TileId::WoodWall,
+                TileId::WoodFloor,
+                &tilemap_data,
+            );
+
+            if blueprint.is_empty() {
+                debug!("Walled room too small or nothing to do ({pos_min}, {pos_max})");
+            } else {
+                let count = blueprint.spawn(&mut commands, &task_index);
+                debug!("Walled room: {count} tasks placed");
+            }
+
+            current_action.pos_start = None;
+            return;
+        }
+
+        match current_action.kind {
+            // The prefab bottom left corner is placed where the pointer is released
+            ActionKind::PlaceRoom(index) => {
+                place_room(
+                    &mut commands,
+                    &room_designer,
+                    index,
+                    pos_end,
+                    &tilemap_data,
+                    &task_index,
+                );
+                current_action.pos_start = None;
+                return;
+            }
+            ActionKind::SaveRoom => {
+                room_designer.capture(pos_min, pos_max, &tilemap_data);
+                current_action.pos_start = None;
+                return;
+            }
+            _ => {}
+        }
+
         'positions: for y in (pos_min.y..=pos_max.y).rev() {
             for x in pos_min.x..=pos_max.x {
-                let pos = IVec2::new(x, y);
+                let pos = IVec3::new(x, y, pos_end.z);
 
                 if max_tasks == 0 {
                     break 'positions;
@@ -154,32 +221,31 @@ pub fn terrain_pointer_up(
                 if let ActionKind::Task(task_kind) | ActionKind::TaskWithNeeds(task_kind, _) =
                     current_action.kind
                 {
-                    if !task_kind.is_valid_on_tile(tile) {
+                    // Unexplored tiles can't be worked on
+                    if !tile.discovered || !task_kind.is_valid_on_tile(tile) {
+                        continue;
+                    }
+
+                    // Stairs need a free tile on the loaded layer at the other end
+                    if let TaskKind::Build {
+                        result:
+                            BuildResult::Object(object @ (ObjectId::StairsDown | ObjectId::StairsUp)),
+                    } = task_kind
+                        && tilemap_data
+                            .stairs_free_link(pos, object)
+                            .is_none_or(|link| task_index.has_task_at(link))
+                    {
                         continue;
                     }
 
                     // Abort if an incompatible task already exists at this position
-                    if q_tasks.iter().filter(|(_, t)| t.pos == pos).any(
-                        |(entity_other_task, other_task)| match (task_kind, other_task.kind) {
-                            (TaskKind::Stockpile, TaskKind::Pickup) => {
-                                commands.entity(entity_other_task).despawn();
-                                // Stockpile task will be correctly marked TaskNeeds::Impossible below
-                                false
-                            }
-                            (TaskKind::Pickup, TaskKind::Stockpile)
-                            | (
-                                TaskKind::Smoothen,
-                                TaskKind::Stockpile
-                                | TaskKind::Pickup
-                                | TaskKind::Harvest
-                                | TaskKind::Attack
-                                | TaskKind::Workstation { .. },
-                            )
-                            | (TaskKind::Attack, _) => false,
-                            _ => true,
-                        },
-                    ) {
+                    let Some(replaced_tasks) = task_index.check_placement(pos, task_kind) else {
                         continue;
+                    };
+
+                    // e.g. a Stockpile replaces a Pickup task, it will be correctly marked TaskNeeds::Impossible below
+                    for entity_other_task in replaced_tasks {
+                        commands.entity(entity_other_task).despawn();
                     }
                 }
 
@@ -187,7 +253,7 @@ pub fn terrain_pointer_up(
                     q_dwellers
                         .get(entity)
                         .ok()
-                        .map(|(_, dweller, _)| dweller.uuid)
+                        .map(|(_, dweller, _, _)| dweller.uuid)
                 });
 
                 match &current_action.kind {
@@ -195,7 +261,7 @@ pub fn terrain_pointer_up(
                         TaskKind::Dig => {
                             commands.spawn(TaskBundle::new(
                                 Task::new(pos, *task_kind, dweller_id),
-                                TaskNeeds::Nothing,
+                                TaskNeeds::dig(tile),
                             ));
 
                             max_tasks = max_tasks.saturating_sub(1);
@@ -204,12 +270,10 @@ pub fn terrain_pointer_up(
 
                         TaskKind::Flood => {
                             if matches!(tile.id, TileId::ShallowWater)
-                                || tilemap_data.neighbours(pos).iter().any(|(_, tile)| {
-                                    matches!(
-                                        tile.id,
-                                        TileId::Water | TileId::ShallowWater | TileId::Lava
-                                    )
-                                })
+                                || tilemap_data
+                                    .neighbours(pos)
+                                    .iter()
+                                    .any(|(_, tile)| tile.has_water() || tile.id == TileId::Lava)
                             {
                                 commands.spawn(TaskBundle::new(
                                     Task::new(pos, *task_kind, dweller_id),
@@ -258,12 +322,15 @@ pub fn terrain_pointer_up(
                             debug!("Picking up task at {pos:?}");
                         }
 
-                        TaskKind::Attack => {
-                            if let Some((entity_mob, _)) =
-                                q_mobs.iter().find(|(_, mob_transform)| {
-                                    mob_transform.translation.distance(
-                                        Vec3::new(pos.x as f32, pos.y as f32, 0.) * TILE_SIZE,
-                                    ) < TILE_SIZE
+                        TaskKind::Attack | TaskKind::Recruit => {
+                            if let Some((entity_mob, ..)) =
+                                q_mobs.iter().find(|(_, mob, mob_transform, mob_layer)| {
+                                    // Only wanderers can be recruited
+                                    (*task_kind == TaskKind::Attack || mob.id == MobId::Wanderer)
+                                        && mob_layer.0 == pos.z
+                                        && mob_transform.translation.distance(
+                                            Vec3::new(pos.x as f32, pos.y as f32, 0.) * TILE_SIZE,
+                                        ) < TILE_SIZE
                                 })
                             {
                                 let task_entity = commands
@@ -275,10 +342,11 @@ pub fn terrain_pointer_up(
                                 commands.entity(entity_mob).add_child(task_entity);
 
                                 max_tasks = max_tasks.saturating_sub(1);
-                                debug!("Attacking task at {pos:?}");
+                                debug!("{task_kind:?} task at {pos:?}");
                             }
                         }
 
+                        // A fishing rod only speeds fishing up
                         TaskKind::Fish => {
                             commands.spawn(TaskBundle::new(
                                 Task::new(pos, *task_kind, dweller_id),
@@ -325,6 +393,16 @@ pub fn terrain_pointer_up(
                             debug!("Scooping task at {pos:?}");
                         }
 
+                        TaskKind::Deconstruct => {
+                            commands.spawn(TaskBundle::new(
+                                Task::new(pos, *task_kind, dweller_id),
+                                TaskNeeds::Nothing,
+                            ));
+
+                            max_tasks = max_tasks.saturating_sub(1);
+                            debug!("Deconstructing task at {pos:?}");
+                        }
+
                         _ => {}
                     },
 
@@ -339,20 +417,28 @@ pub fn terrain_pointer_up(
                             debug!("Building task at {pos:?}");
                         }
 
+                        TaskKind::Extinguish => {
+                            commands.spawn(TaskBundle::new(
+                                Task::new(pos, *task_kind, dweller_id),
+                                needs.clone(),
+                            ));
+
+                            max_tasks = max_tasks.saturating_sub(1);
+                            debug!("Extinguishing task at {pos:?}");
+                        }
+
                         _ => {}
                     },
 
                     ActionKind::Cancel => {
-                        if let Some((entity_task, task)) =
-                            q_tasks.iter().find(|(_, task)| task.pos == pos)
-                        {
-                            commands.entity(entity_task).despawn();
+                        if let Some(task) = task_index.at(pos).next() {
+                            commands.entity(task.entity).despawn();
 
                             // stop dweller from moving towards this task
                             if let Some(dweller_id) = task.dweller_id
-                                && let Some((_, mut dweller, _)) = q_dwellers
+                                && let Some((_, mut dweller, _, _)) = q_dwellers
                                     .iter_mut()
-                                    .find(|(_, dweller, _)| dweller.uuid == dweller_id)
+                                    .find(|(_, dweller, _, _)| dweller.uuid == dweller_id)
                             {
                                 dweller.move_queue = Vec::new();
                             }
@@ -362,9 +448,7 @@ pub fn terrain_pointer_up(
                                 task.kind,
                                 TaskKind::Stockpile | TaskKind::Workstation { .. }
                             ) && TaskKind::Pickup.is_valid_on_tile(tile)
-                                && !q_tasks.iter().any(|(_, task)| {
-                                    task.kind == TaskKind::Pickup && task.pos == pos
-                                })
+                                && !task_index.at(pos).any(|task| task.kind == TaskKind::Pickup)
                             {
                                 commands.spawn(TaskBundle::new(
                                     Task::new(pos, TaskKind::Pickup, dweller_id),
@@ -383,17 +467,15 @@ pub fn terrain_pointer_up(
                     ActionKind::Select => {
                         // if single click on workstation, open workstation ui
                         if pos_min == pos_max
-                            && let Some(entity) = q_tasks.iter().find_map(|(entity, task)| {
-                                if task.pos == pos { Some(entity) } else { None }
-                            })
+                            && let Some(entity) = task_index.at(pos).next().map(|task| task.entity)
                         {
                             commands.trigger(OpenWorkstationUi { entity });
                             break;
                         }
 
                         // else select dwellers
-                        for (entity, _, transform) in &q_dwellers {
-                            if pos == transform_to_pos(transform) {
+                        for (entity, _, transform, layer) in &q_dwellers {
+                            if pos == transform_to_pos(transform, layer) {
                                 dwellers_selected.add(entity);
                             }
                         }
@@ -415,6 +497,9 @@ pub fn terrain_pointer_up(
                     ActionKind::DebugSpawn(mob_id) => {
                         commands.spawn(MobBundle::new(*mob_id, pos));
                     }
+
+                    // Handled above, for the whole selection
+                    ActionKind::WalledRoom | ActionKind::PlaceRoom(_) | ActionKind::SaveRoom => {}
                 }
             }
         }
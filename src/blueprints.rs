@@ -0,0 +1,543 @@
+use bevy::{
+    platform::collections::{HashMap, HashSet},
+    prelude::*,
+};
+use uuid::Uuid;
+
+use crate::{
+    StructureData, TilePlaced, TilemapData,
+    data::{BUILD_RECIPES, ObjectId, TileId},
+    task_index::TaskIndex,
+    tasks::{BuildResult, Task, TaskBundle, TaskKind, TaskNeeds},
+};
+
+// Blueprint tasks are done phase by phase on each tile
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub enum BlueprintPhase {
+    Clear, // Dig, Harvest, Pickup
+    Floor,
+    Furniture,
+    Wall,
+}
+
+#[derive(Debug)]
+pub struct BlueprintTask {
+    pub pos: IVec3,
+    pub kind: TaskKind,
+    pub needs: TaskNeeds,
+    pub phase: BlueprintPhase,
+    pub depth: i32, // distance to the blueprint border, walls are built from the inside out
+    pub door_distance: i32, // walls of a same ring are built from the far side towards the doors
+}
+
+impl BlueprintTask {
+    // Walls with a higher order are built first
+    fn wall_order(&self) -> (i32, i32) {
+        (self.depth, self.door_distance)
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct Blueprint {
+    tasks: Vec<BlueprintTask>,
+}
+
+impl Blueprint {
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    fn push(&mut self, pos: IVec3, kind: TaskKind, needs: TaskNeeds, phase: BlueprintPhase) {
+        self.tasks.push(BlueprintTask {
+            pos,
+            kind,
+            needs,
+            phase,
+            depth: 0,
+            door_distance: 0,
+        });
+    }
+
+    // Skipped if the result can't be built
+    fn push_build(&mut self, pos: IVec3, result: BuildResult, phase: BlueprintPhase) {
+        let Some((_, cost)) = BUILD_RECIPES.iter().find(|(r, _)| *r == result) else {
+            return;
+        };
+
+        self.push(
+            pos,
+            TaskKind::Build { result },
+            TaskNeeds::Objects(cost.to_vec()),
+            phase,
+        );
+    }
+
+    // Plan the tasks that turn the tile at pos into the wanted tile
+    pub fn plan_tile(&mut self, pos: IVec3, wanted: TilePlaced, tilemap_data: &TilemapData) {
+        // Nothing is planned on unexplored tiles
+        let Some(tile) = tilemap_data.get(pos).filter(|tile| tile.discovered) else {
+            return;
+        };
+
+        let diggable = TaskKind::Dig.is_valid_on_tile(tile);
+
+        // Water and lava can't be dug nor built upon
+        if tile.id.data().is_wall() && !diggable {
+            return;
+        }
+
+        if wanted.id.data().is_wall() {
+            // Keep existing walls
+            if tile.id.data().is_wall() {
+                return;
+            }
+
+            self.plan_clear_object(pos, tile);
+            self.push_build(pos, BuildResult::Tile(wanted.id), BlueprintPhase::Wall);
+            return;
+        }
+
+        // Floors: dig walls, clear objects that are not wanted
+        if diggable {
+            self.push(
+                pos,
+                TaskKind::Dig,
+                TaskNeeds::dig(tile),
+                BlueprintPhase::Clear,
+            );
+        } else if tile.object != wanted.object {
+            self.plan_clear_object(pos, tile);
+        }
+
+        // Digging leaves a stone floor
+        let floor_after_clear = if diggable {
+            TileId::StoneFloor
+        } else {
+            tile.id
+        };
+
+        if floor_after_clear != wanted.id {
+            self.push_build(pos, BuildResult::Tile(wanted.id), BlueprintPhase::Floor);
+        }
+
+        if let Some(object) = wanted.object
+            && (diggable || tile.object != Some(object))
+        {
+            self.push_build(pos, BuildResult::Object(object), BlueprintPhase::Furniture);
+        }
+    }
+
+    fn plan_clear_object(&mut self, pos: IVec3, tile: TilePlaced) {
+        if tile.object.is_none() {
+            return;
+        }
+
+        if TaskKind::Harvest.is_valid_on_tile(tile) {
+            self.push(
+                pos,
+                TaskKind::Harvest,
+                TaskNeeds::Nothing,
+                BlueprintPhase::Clear,
+            );
+        } else if TaskKind::Pickup.is_valid_on_tile(tile) {
+            self.push(
+                pos,
+                TaskKind::Pickup,
+                TaskNeeds::EmptyHands,
+                BlueprintPhase::Clear,
+            );
+        }
+    }
+
+    // Structure tiles left empty keep the current terrain
+    pub fn from_structure(
+        structure: &StructureData,
+        origin: IVec3,
+        tilemap_data: &TilemapData,
+    ) -> Self {
+        let mut blueprint = Self::default();
+
+        for y in 0..structure.y_size() {
+            for x in 0..structure.x_size() {
+                if let Some(tile) = structure.get_tile(x, y) {
+                    let pos = origin + IVec3::new(x as i32, y as i32, 0);
+                    blueprint.plan_tile(pos, *tile, tilemap_data);
+                }
+            }
+        }
+
+        let pos_max = origin + (structure.size().as_ivec2() - IVec2::ONE).extend(0);
+        blueprint.compute_depths(origin, pos_max);
+        blueprint
+    }
+
+    // Rectangle of walls with floors inside and a door on its border
+    pub fn walled_room(
+        pos_min: IVec3,
+        pos_max: IVec3,
+        wall: TileId,
+        floor: TileId,
+        tilemap_data: &TilemapData,
+    ) -> Self {
+        let mut blueprint = Self::default();
+
+        if pos_max.x - pos_min.x < 2 || pos_max.y - pos_min.y < 2 {
+            return blueprint;
+        }
+
+        let is_border = |pos: IVec3| {
+            pos.x == pos_min.x || pos.x == pos_max.x || pos.y == pos_min.y || pos.y == pos_max.y
+        };
+        let is_corner = |pos: IVec3| {
+            (pos.x == pos_min.x || pos.x == pos_max.x) && (pos.y == pos_min.y || pos.y == pos_max.y)
+        };
+        let outwards = |pos: IVec3| {
+            if pos.y == pos_min.y {
+                IVec3::NEG_Y
+            } else if pos.y == pos_max.y {
+                IVec3::Y
+            } else if pos.x == pos_min.x {
+                IVec3::NEG_X
+            } else {
+                IVec3::X
+            }
+        };
+
+        // The door makes sure the room never seals off a dweller:
+        // prefer a border tile that already opens on a walkable tile
+        let border = (pos_min.x..=pos_max.x)
+            .flat_map(|x| (pos_min.y..=pos_max.y).map(move |y| IVec3::new(x, y, pos_min.z)))
+            .filter(|pos| is_border(*pos) && !is_corner(*pos))
+            .collect::<Vec<_>>();
+
+        let door_pos = border
+            .iter()
+            .copied()
+            .find(|pos| {
+                tilemap_data
+                    .get(*pos + outwards(*pos))
+                    .is_some_and(|tile| !tile.is_blocking())
+            })
+            .unwrap_or(IVec3::new(
+                (pos_min.x + pos_max.x) / 2,
+                pos_min.y,
+                pos_min.z,
+            ));
+
+        // If the door opens on a wall, dig it
+        let door_outside = door_pos + outwards(door_pos);
+        if let Some(tile) = tilemap_data
+            .get(door_outside)
+            .filter(|tile| TaskKind::Dig.is_valid_on_tile(*tile))
+        {
+            blueprint.push(
+                door_outside,
+                TaskKind::Dig,
+                TaskNeeds::dig(tile),
+                BlueprintPhase::Clear,
+            );
+        }
+
+        for x in pos_min.x..=pos_max.x {
+            for y in pos_min.y..=pos_max.y {
+                let pos = IVec3::new(x, y, pos_min.z);
+
+                let wanted = if pos == door_pos {
+                    floor.with(ObjectId::Door)
+                } else if is_border(pos) {
+                    wall.place()
+                } else {
+                    floor.place()
+                };
+
+                blueprint.plan_tile(pos, wanted, tilemap_data);
+            }
+        }
+
+        blueprint.compute_depths(pos_min, pos_max);
+        blueprint
+    }
+
+    pub fn compute_depths(&mut self, pos_min: IVec3, pos_max: IVec3) {
+        let doors = self
+            .tasks
+            .iter()
+            .filter(|task| {
+                task.kind
+                    == TaskKind::Build {
+                        result: BuildResult::Object(ObjectId::Door),
+                    }
+            })
+            .map(|task| task.pos)
+            .collect::<Vec<_>>();
+
+        for task in &mut self.tasks {
+            let pos = task.pos;
+            task.depth = (pos.x - pos_min.x)
+                .min(pos_max.x - pos.x)
+                .min(pos.y - pos_min.y)
+                .min(pos_max.y - pos.y)
+                .max(0);
+            task.door_distance = doors
+                .iter()
+                .map(|door| TilemapData::manhattan_distance(pos, *door))
+                .min()
+                .unwrap_or(0);
+        }
+    }
+
+    // Spawn all tasks, each depending on:
+    // - tasks of earlier phases on the same tile (dig before build, floor before furniture)
+    // - for walls, the walls of the previous step of wall_order (inside out, then from the far
+    //   side towards the doors), the walls of the first step on every non-wall task
+    // A task that can't be placed also drops the later phases of its tile
+    pub fn spawn(self, commands: &mut Commands, task_index: &TaskIndex) -> usize {
+        let mut ids_by_pos: HashMap<IVec3, Vec<(BlueprintPhase, Uuid)>> = HashMap::new();
+        let mut rejected_pos = HashSet::new();
+
+        // Tasks the walls of the current step wait for (every non-wall task for the first step),
+        // and the walls of that step
+        let mut previous_step: Vec<(IVec3, Uuid)> = vec![];
+        let mut current_step: Vec<(IVec3, Uuid)> = vec![];
+        let mut current_order = None;
+
+        let mut tasks = self.tasks.iter().collect::<Vec<_>>();
+        tasks.sort_by_key(|task| (task.phase, std::cmp::Reverse(task.wall_order())));
+
+        let mut count = 0;
+
+        for task in tasks {
+            // Tasks are sorted, walls of a new order start a new step
+            if task.phase == BlueprintPhase::Wall && current_order != Some(task.wall_order()) {
+                current_order = Some(task.wall_order());
+                if !current_step.is_empty() {
+                    previous_step = std::mem::take(&mut current_step);
+                }
+            }
+
+            // Reuse an identical existing task (e.g. a Dig already placed by hand)
+            if let Some(existing) = task_index
+                .at(task.pos)
+                .find(|other| other.kind == task.kind)
+            {
+                ids_by_pos
+                    .entry(task.pos)
+                    .or_default()
+                    .push((task.phase, existing.id));
+                if task.phase == BlueprintPhase::Wall {
+                    current_step.push((task.pos, existing.id));
+                } else {
+                    previous_step.push((task.pos, existing.id));
+                }
+                continue;
+            }
+
+            if rejected_pos.contains(&task.pos)
+                || task_index.check_placement(task.pos, task.kind).is_none()
+            {
+                debug!(
+                    "Blueprint task {:?} can't be placed at {}",
+                    task.kind, task.pos
+                );
+                rejected_pos.insert(task.pos);
+                continue;
+            }
+
+            let mut depends_on = ids_by_pos
+                .get(&task.pos)
+                .into_iter()
+                .flatten()
+                .filter(|(phase, _)| *phase < task.phase)
+                .map(|(_, id)| *id)
+                .collect::<Vec<_>>();
+
+            if task.phase == BlueprintPhase::Wall {
+                depends_on.extend(
+                    previous_step
+                        .iter()
+                        .filter(|(pos, _)| *pos != task.pos)
+                        .map(|(_, id)| *id),
+                );
+            }
+
+            let new_task = Task::new(task.pos, task.kind, None).with_dependencies(depends_on);
+            let id = new_task.id;
+
+            commands.spawn(TaskBundle::new(new_task, task.needs.clone()));
+
+            ids_by_pos
+                .entry(task.pos)
+                .or_default()
+                .push((task.phase, id));
+            if task.phase == BlueprintPhase::Wall {
+                current_step.push((task.pos, id));
+            } else {
+                previous_step.push((task.pos, id));
+            }
+            count += 1;
+        }
+
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CHUNK_SIZE;
+
+    fn discovered_chunk(id: TileId) -> TilemapData {
+        TilemapData::filled_chunk(
+            IVec3::ZERO,
+            TilePlaced {
+                discovered: true,
+                ..id.place()
+            },
+        )
+    }
+
+    #[test]
+    fn plan_tile_digs_then_builds_floor_then_furniture() {
+        let tilemap_data = discovered_chunk(TileId::StoneWall);
+        let mut blueprint = Blueprint::default();
+        blueprint.plan_tile(
+            IVec3::new(3, 3, 0),
+            TileId::WoodFloor.with(ObjectId::Bed),
+            &tilemap_data,
+        );
+
+        let planned = blueprint
+            .tasks
+            .iter()
+            .map(|task| (task.phase, task.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            planned,
+            vec![
+                (BlueprintPhase::Clear, TaskKind::Dig),
+                (
+                    BlueprintPhase::Floor,
+                    TaskKind::Build {
+                        result: BuildResult::Tile(TileId::WoodFloor)
+                    }
+                ),
+                (
+                    BlueprintPhase::Furniture,
+                    TaskKind::Build {
+                        result: BuildResult::Object(ObjectId::Bed)
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn plan_tile_skips_undiscovered_tiles() {
+        let mut tilemap_data = discovered_chunk(TileId::StoneFloor);
+        let pos = IVec3::new(3, 3, 0);
+        tilemap_data.chunks.get_mut(&IVec3::ZERO).unwrap()[3 * CHUNK_SIZE as usize + 3]
+            .discovered = false;
+
+        let mut blueprint = Blueprint::default();
+        blueprint.plan_tile(pos, TileId::WoodWall.place(), &tilemap_data);
+        assert!(blueprint.is_empty());
+    }
+
+    #[test]
+    fn walled_room_walls_are_built_towards_the_door() {
+        let tilemap_data = discovered_chunk(TileId::StoneFloor);
+        let blueprint = Blueprint::walled_room(
+            IVec3::new(2, 2, 0),
+            IVec3::new(6, 6, 0),
+            TileId::WoodWall,
+            TileId::WoodFloor,
+            &tilemap_data,
+        );
+
+        let door = blueprint
+            .tasks
+            .iter()
+            .find(|task| {
+                task.kind
+                    == TaskKind::Build {
+                        result: BuildResult::Object(ObjectId::Door),
+                    }
+            })
+            .expect("the room has a door");
+        let walls = blueprint
+            .tasks
+            .iter()
+            .filter(|task| task.phase == BlueprintPhase::Wall)
+            .collect::<Vec<_>>();
+
+        assert_eq!(walls.len(), 15);
+        assert!(walls.iter().all(|wall| wall.depth == 0));
+        for wall in &walls {
+            assert_eq!(
+                wall.door_distance,
+                TilemapData::manhattan_distance(wall.pos, door.pos)
+            );
+        }
+
+        // The wall opposite the door goes up first, the ones next to it last
+        let first = walls.iter().max_by_key(|wall| wall.wall_order()).unwrap();
+        let last = walls.iter().min_by_key(|wall| wall.wall_order()).unwrap();
+        assert!(first.door_distance >= 4);
+        assert_eq!(last.door_distance, 1);
+    }
+
+    #[test]
+    fn walls_wait_for_the_previous_step_only() {
+        let tilemap_data = discovered_chunk(TileId::StoneFloor);
+        let blueprint = Blueprint::walled_room(
+            IVec3::new(2, 2, 0),
+            IVec3::new(6, 6, 0),
+            TileId::WoodWall,
+            TileId::WoodFloor,
+            &tilemap_data,
+        );
+        let orders = blueprint
+            .tasks
+            .iter()
+            .filter(|task| task.phase == BlueprintPhase::Wall)
+            .map(|task| (task.pos, task.wall_order()))
+            .collect::<HashMap<_, _>>();
+
+        let mut world = World::new();
+        blueprint.spawn(&mut world.commands(), &TaskIndex::default());
+        world.flush();
+
+        let tasks = world
+            .query::<&Task>()
+            .iter(&world)
+            .map(|task| (task.id, task))
+            .collect::<HashMap<_, _>>();
+
+        for wall in tasks.values() {
+            let Some(order) = orders.get(&wall.pos) else {
+                continue;
+            };
+            let dependencies = wall
+                .depends_on
+                .iter()
+                .filter_map(|id| tasks.get(id))
+                .filter(|task| task.pos != wall.pos)
+                .collect::<Vec<_>>();
+            assert!(!dependencies.is_empty());
+
+            match orders.values().filter(|other| *other > order).min() {
+                // The first walls wait for everything else
+                None => assert!(
+                    dependencies
+                        .iter()
+                        .all(|task| !orders.contains_key(&task.pos))
+                ),
+                Some(previous) => assert!(
+                    dependencies
+                        .iter()
+                        .all(|task| orders.get(&task.pos) == Some(previous))
+                ),
+            }
+        }
+    }
+}
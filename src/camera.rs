@@ -3,7 +3,7 @@ use bevy::{
     prelude::*,
 };
 
-use crate::{CHUNK_SIZE, TILE_SIZE, dwellers::Dweller};
+use crate::{CHUNK_SIZE, Layer, MIN_LAYER, TILE_SIZE, dwellers::Dweller};
 
 const CAMERA_KEYBOARD_SPEED: f32 = 3.0;
 const CAMERA_ZOOM_SPEED: f32 = 0.1;
@@ -12,6 +12,7 @@ const CAMERA_ZOOM_SPEED: f32 = 0.1;
 pub struct CameraControl {
     pub target_pos: Vec2,
     pub target_scale: f32,
+    pub layer: i32,
 }
 
 impl Default for CameraControl {
@@ -19,6 +20,7 @@ impl Default for CameraControl {
         Self {
             target_pos: Vec2::splat(CHUNK_SIZE as f32 * 0.5 * TILE_SIZE),
             target_scale: 1.,
+            layer: 0,
         }
     }
 }
@@ -72,6 +74,21 @@ pub fn update_camera(
         control.target_pos += y as f32 * step * Vec2::Y * CAMERA_KEYBOARD_SPEED;
     }
 
+    // Switch layer, the camera stays above the same position
+    let mut layer_change = 0;
+    if input_keyboard.just_pressed(KeyCode::PageUp) {
+        layer_change += 1;
+    }
+    if input_keyboard.just_pressed(KeyCode::PageDown) {
+        layer_change -= 1;
+    }
+
+    let new_layer = (control.layer + layer_change).clamp(MIN_LAYER, 0);
+    if new_layer != control.layer {
+        control.layer = new_layer;
+        info!("Camera on layer {new_layer}");
+    }
+
     let target = control.target_pos.extend(0.);
     if transform.translation.distance_squared(target) > 0.01 {
         transform.translation = transform.translation.lerp(target, 40. * time.delta_secs());
@@ -92,20 +109,39 @@ pub fn update_camera(
 
 pub fn focus_any_dweller(
     keyboard_input: Res<ButtonInput<KeyCode>>,
-    q_dwellers: Query<&Transform, With<Dweller>>,
-    q_new_dweller: Query<&Transform, Added<Dweller>>,
+    q_dwellers: Query<(&Transform, &Layer), With<Dweller>>,
+    q_new_dweller: Query<(&Transform, &Layer), Added<Dweller>>,
     mut control: ResMut<CameraControl>,
 ) {
-    let mut transform = q_new_dweller.iter().next();
-    if keyboard_input.just_pressed(KeyCode::KeyQ) || transform.is_some() {
-        if transform.is_none() {
-            transform = q_dwellers.iter().next();
+    let mut dweller = q_new_dweller.iter().next();
+    if keyboard_input.just_pressed(KeyCode::KeyQ) || dweller.is_some() {
+        if dweller.is_none() {
+            dweller = q_dwellers.iter().next();
         }
 
-        if let Some(transform) = transform {
+        if let Some((transform, layer)) = dweller {
             info!("Focusing on dweller {:?}", transform.translation.truncate());
             control.target_pos = transform.translation.truncate();
             control.target_scale = 0.5;
+            control.layer = layer.0;
         }
     }
 }
+
+// Only the layer under the camera is drawn
+pub fn update_layer_visibility(
+    control: Res<CameraControl>,
+    mut q_layers: Query<(Ref<Layer>, &mut Visibility)>,
+) {
+    for (layer, mut visibility) in &mut q_layers {
+        if !control.is_changed() && !layer.is_changed() {
+            continue;
+        }
+
+        visibility.set_if_neq(if layer.0 == control.layer {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        });
+    }
+}
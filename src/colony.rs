@@ -0,0 +1,129 @@
+use bevy::{platform::collections::HashSet, prelude::*};
+use pathfinding::directed::bfs::bfs_reach;
+use rand::prelude::*;
+
+use crate::{
+    Layer, TilemapData,
+    data::{EAT_VALUES, ObjectId},
+    dwellers::{Dweller, SpawnDwellersOnChunk, spawn_dweller},
+    social::EventLog,
+    task_index::TaskIndex,
+    tasks::TaskKind,
+    utils::transform_to_pos,
+};
+
+// Each stockpiled object makes the colony more attractive to immigrants
+const IMMIGRANT_CHANCE_PER_WEALTH: f64 = 0.002;
+const IMMIGRANT_CHANCE_MAX: f64 = 0.5;
+const IMMIGRANTS_MAX: u32 = 2;
+// Stockpiled food needed per dweller before anyone settles in
+const FOOD_PER_DWELLER: usize = 3;
+
+// Chance for partners to have a child, if there is a bed for them
+const BIRTH_CHANCE: f64 = 0.1;
+
+pub fn update_colony_growth(
+    mut commands: Commands,
+    q_dwellers: Query<(&Dweller, &Transform, &Layer)>,
+    tilemap_data: Res<TilemapData>,
+    task_index: Res<TaskIndex>,
+    mut ev_spawn_dwellers: MessageWriter<SpawnDwellersOnChunk>,
+    mut event_log: ResMut<EventLog>,
+) {
+    let mut rng = rand::rng();
+
+    let stockpiled = task_index
+        .of_kind(TaskKind::Stockpile)
+        .filter_map(|task| tilemap_data.get(task.pos).and_then(|tile| tile.object))
+        .collect::<Vec<_>>();
+    let wealth = stockpiled.len();
+    let food = stockpiled
+        .iter()
+        .filter(|object| EAT_VALUES.contains_key(*object))
+        .count();
+    let dwellers = q_dwellers.iter().count();
+
+    // Immigrants arrive from the edge of the map and walk to a surface dweller
+    let immigrant_chance = (wealth as f64 * IMMIGRANT_CHANCE_PER_WEALTH).min(IMMIGRANT_CHANCE_MAX);
+    if food >= dwellers * FOOD_PER_DWELLER
+        && rng.random_bool(immigrant_chance)
+        && let Some(walk_to) = q_dwellers
+            .iter()
+            .map(|(_, transform, layer)| transform_to_pos(transform, layer))
+            .filter(|pos| pos.z == 0)
+            .choose(&mut rng)
+        && let Some(chunk_pos) = tilemap_data
+            .chunks
+            .keys()
+            .filter(|chunk_pos| {
+                chunk_pos.z == 0
+                    && [IVec3::X, IVec3::NEG_X, IVec3::Y, IVec3::NEG_Y]
+                        .iter()
+                        .any(|dir| !tilemap_data.chunks.contains_key(&(**chunk_pos + *dir)))
+            })
+            .choose(&mut rng)
+    {
+        let count = rng.random_range(1..=IMMIGRANTS_MAX);
+        ev_spawn_dwellers.write(SpawnDwellersOnChunk {
+            chunk_pos: *chunk_pos,
+            count,
+            walk_to: Some(walk_to),
+        });
+
+        event_log.push(if count == 1 {
+            "An immigrant is joining the colony".to_string()
+        } else {
+            format!("{count} immigrants are joining the colony")
+        });
+    }
+
+    // Partners have a child when there is a bed to spare
+    let Some((parent, partner, pos)) = q_dwellers
+        .iter()
+        .filter(|(dweller, _, _)| !dweller.is_downed())
+        .filter_map(|(dweller, transform, layer)| {
+            let partner_uuid = dweller.partner.filter(|partner| dweller.uuid < *partner)?;
+            let (partner, _, _) = q_dwellers
+                .iter()
+                .find(|(other, _, _)| other.uuid == partner_uuid && !other.is_downed())?;
+            Some((dweller, partner, transform_to_pos(transform, layer)))
+        })
+        .find(|_| rng.random_bool(BIRTH_CHANCE))
+    else {
+        return;
+    };
+
+    // Only beds the colony knows of and can walk to count, not those of unexplored ruins
+    let mut reachable = HashSet::new();
+    for (_, transform, layer) in &q_dwellers {
+        let dweller_pos = transform_to_pos(transform, layer);
+        if !reachable.contains(&dweller_pos) {
+            reachable.extend(bfs_reach(dweller_pos, |pos| {
+                tilemap_data.non_blocking_neighbours_pos(*pos, true)
+            }));
+        }
+    }
+    let beds = reachable
+        .iter()
+        .filter(|pos| {
+            tilemap_data
+                .get(**pos)
+                .is_some_and(|tile| tile.discovered && tile.object == Some(ObjectId::Bed))
+        })
+        .count();
+    if beds <= dwellers {
+        return;
+    }
+
+    let mut child = Dweller::new(Dweller::random_name());
+    child.parents = vec![
+        (parent.uuid, parent.name.clone()),
+        (partner.uuid, partner.name.clone()),
+    ];
+
+    event_log.push(format!(
+        "{} and {} had a child, {}",
+        parent.name, partner.name, child.name
+    ));
+    spawn_dweller(&mut commands, child, pos);
+}
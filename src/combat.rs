@@ -0,0 +1,118 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use rand::prelude::*;
+
+use crate::data::{ObjectId, WEAPONS};
+
+const COMBAT_LOG_SIZE: usize = 8;
+
+// Chances are clamped so that every attack can hit or miss
+const HIT_CHANCE_MIN: f64 = 0.05;
+const HIT_CHANCE_MAX: f64 = 0.95;
+// Armor never mitigates everything
+const MITIGATION_MAX: u32 = 90;
+
+pub const MOB_ACCURACY: f64 = 0.7;
+
+// Per level of combat skill
+pub const SKILL_MAX: u32 = 10;
+const SKILL_ACCURACY: f64 = 0.03;
+const SKILL_DODGE: f64 = 0.02;
+const SKILL_GAIN_CHANCE: f64 = 0.1;
+
+pub struct WeaponData {
+    pub range: i32,
+    pub cooldown: u32, // ticks between attacks
+    pub accuracy: f64,
+}
+
+// Fists, and tools used as weapons
+const MELEE: WeaponData = WeaponData {
+    range: 1,
+    cooldown: 2,
+    accuracy: 0.7,
+};
+
+impl WeaponData {
+    pub fn of(tool: Option<ObjectId>) -> &'static WeaponData {
+        tool.and_then(|tool| WEAPONS.get(&tool)).unwrap_or(&MELEE)
+    }
+}
+
+pub fn skill_accuracy(skill: u32) -> f64 {
+    skill as f64 * SKILL_ACCURACY
+}
+
+pub fn skill_dodge(skill: u32) -> f64 {
+    skill as f64 * SKILL_DODGE
+}
+
+// Landing hits trains the combat skill
+pub fn train_skill(rng: &mut impl Rng, skill: &mut u32) {
+    if *skill < SKILL_MAX && rng.random_bool(SKILL_GAIN_CHANCE) {
+        *skill += 1;
+    }
+}
+
+pub fn roll_hit(rng: &mut impl Rng, accuracy: f64, dodge: f64) -> bool {
+    rng.random_bool((accuracy - dodge).clamp(HIT_CHANCE_MIN, HIT_CHANCE_MAX))
+}
+
+// Mitigation in percent, the fraction left is rounded randomly so that weak hits still hurt sometimes
+pub fn mitigate(rng: &mut impl Rng, damage: u32, mitigation: u32) -> u32 {
+    let damage = damage as f64 * (100 - mitigation.min(MITIGATION_MAX)) as f64 / 100.0;
+    damage.floor() as u32 + u32::from(rng.random_bool(damage.fract()))
+}
+
+// Last attacks, shown in the corner of the screen
+#[derive(Resource, Default)]
+pub struct CombatLog(pub VecDeque<String>);
+
+impl CombatLog {
+    pub fn push(&mut self, entry: String) {
+        debug!("Combat: {entry}");
+
+        if self.0.len() == COMBAT_LOG_SIZE {
+            self.0.pop_front();
+        }
+        self.0.push_back(entry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hits_are_never_certain() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let hits = (0..1000).filter(|_| roll_hit(&mut rng, 5.0, 0.0)).count();
+        assert!(hits > 900 && hits < 1000, "{hits} hits");
+
+        let hits = (0..1000).filter(|_| roll_hit(&mut rng, 0.0, 5.0)).count();
+        assert!(hits > 0 && hits < 100, "{hits} hits");
+    }
+
+    #[test]
+    fn mitigation_is_capped() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        assert_eq!(mitigate(&mut rng, 10, 0), 10);
+        assert_eq!(mitigate(&mut rng, 10, 50), 5);
+        assert_eq!(mitigate(&mut rng, 10, 100), 1);
+        assert_eq!(mitigate(&mut rng, 10, 500), 1);
+    }
+
+    #[test]
+    fn weak_hits_sometimes_hurt() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let damages = (0..100)
+            .map(|_| mitigate(&mut rng, 1, 50))
+            .collect::<Vec<_>>();
+        assert!(damages.iter().all(|damage| *damage <= 1));
+        assert!(damages.contains(&0) && damages.contains(&1));
+    }
+}
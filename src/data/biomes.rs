@@ -0,0 +1,84 @@
+use bevy::prelude::*;
+
+use crate::{
+    BiomeData, Climate,
+    data::{MobId, ObjectId, TileId},
+    enum_map,
+};
+
+enum_map! {
+    BiomeId => BiomeData {
+        Plains = BiomeData {
+            temperature: 0.0,
+            humidity: -0.3,
+            climate: Climate::Temperate,
+            ground: TileId::GrassFloor,
+            pool: None,
+            vegetation_threshold: 0.4,
+            trees: &[(ObjectId::Tree, 1)],
+            plants: &[(ObjectId::BerryBush, 6), (ObjectId::Bush, 1), (ObjectId::TallGrass, 3)],
+            ores: &[(ObjectId::CopperOre, 5), (ObjectId::TinOre, 3), (ObjectId::Coal, 2)],
+            mobs: &[(MobId::Sheep, 7), (MobId::Boar, 5)],
+        },
+        Forest = BiomeData {
+            temperature: -0.1,
+            humidity: 0.4,
+            climate: Climate::Temperate,
+            ground: TileId::GrassFloor,
+            pool: None,
+            vegetation_threshold: 0.0,
+            trees: &[(ObjectId::Tree, 1)],
+            plants: &[(ObjectId::BerryBush, 3), (ObjectId::Bush, 2), (ObjectId::TallGrass, 5)],
+            ores: &[(ObjectId::CopperOre, 4), (ObjectId::Coal, 4), (ObjectId::TinOre, 2)],
+            mobs: &[(MobId::Boar, 5), (MobId::Moose, 2)],
+        },
+        Desert = BiomeData {
+            temperature: 0.75,
+            humidity: -0.3,
+            climate: Climate::Desert,
+            ground: TileId::SandFloor,
+            pool: None,
+            vegetation_threshold: 0.4,
+            trees: &[(ObjectId::PalmTree, 1)],
+            plants: &[(ObjectId::Cactus, 1)],
+            ores: &[(ObjectId::CopperOre, 4), (ObjectId::TinOre, 2), (ObjectId::GoldOre, 1)],
+            mobs: &[(MobId::Snake, 3)],
+        },
+        Swamp = BiomeData {
+            temperature: 0.2,
+            humidity: 0.8,
+            climate: Climate::Temperate,
+            ground: TileId::GrassFloor,
+            pool: Some(TileId::ShallowWater),
+            vegetation_threshold: 0.2,
+            trees: &[(ObjectId::Tree, 1)],
+            plants: &[(ObjectId::TallGrass, 4), (ObjectId::Bush, 1)],
+            ores: &[(ObjectId::Coal, 5), (ObjectId::CopperOre, 3), (ObjectId::TinOre, 2)],
+            mobs: &[(MobId::Snake, 4), (MobId::Boar, 3)],
+        },
+        Tundra = BiomeData {
+            temperature: -0.8,
+            humidity: 0.0,
+            climate: Climate::Temperate,
+            ground: TileId::SnowFloor,
+            pool: None,
+            vegetation_threshold: 0.6,
+            trees: &[(ObjectId::Tree, 1)],
+            plants: &[(ObjectId::TallGrass, 1)],
+            ores: &[(ObjectId::CopperOre, 3), (ObjectId::TinOre, 3), (ObjectId::IronOre, 2), (ObjectId::SilverOre, 1)],
+            mobs: &[(MobId::Moose, 3)],
+        },
+        Volcanic = BiomeData {
+            temperature: 0.9,
+            humidity: 0.6,
+            climate: Climate::Desert,
+            ground: TileId::AshFloor,
+            pool: Some(TileId::Lava),
+            vegetation_threshold: 0.8,
+            trees: &[],
+            plants: &[(ObjectId::Cactus, 1)],
+            ores: &[(ObjectId::IronOre, 3), (ObjectId::Coal, 3), (ObjectId::CopperOre, 2), (ObjectId::GoldOre, 1)],
+            mobs: &[(MobId::Undead, 2)],
+        },
+    }
+}
@@ -36,19 +36,7 @@ macro_rules! enum_map {
 
 #[macro_export]
 macro_rules! structure_ascii {
-    ($ascii:literal, $mobs:expr) => {{
-        let mut grid: Vec<Vec<Option<TilePlaced>>> = $ascii
-            .trim_end()
-            .lines()
-            .map(|line| {
-                line.chars()
-                    .map(|ch| ASCII_TILES.get(&ch).copied())
-                    .collect()
-            })
-            .collect();
-
-        grid.reverse();
-
-        StructureData::new(grid, $mobs)
-    }};
+    ($ascii:literal, $mobs:expr) => {
+        StructureData::from_ascii($ascii, $mobs)
+    };
 }
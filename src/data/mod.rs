@@ -2,10 +2,12 @@ use std::sync::LazyLock;
 
 use bevy::{platform::collections::HashMap, prelude::*};
 
-use crate::{BuildResult, MobData, Object, Tile, enum_map};
+use crate::{BuildResult, MobData, Object, Tile, ToolCategory, combat::WeaponData, enum_map};
 
+mod biomes;
 mod macros;
 mod structures;
+pub use biomes::*;
 pub use structures::*;
 
 enum_map! {
@@ -32,8 +34,8 @@ enum_map! {
         Forge = Object::blocking("forge"),
         Anvil = Object::blocking("anvil"),
         Grindstone = Object::blocking("grindstone"),
-        Sword = Object::tool("sword", 2),
-        Armor = Object::armor("armor", 3),
+        Sword = Object::tool("sword", ToolCategory::Weapon, 2, 60),
+        Armor = Object::armor("armor", 20),
         Scarecrow = Object::blocking("scarecrow"),
         Haystack = Object::blocking("haystack"),
         FishingSpot = Object::passable_non_carriable("fishing_spot"),
@@ -47,6 +49,41 @@ enum_map! {
         MeadVat = Object::blocking("mead_vat"),
         Hydromel = Object::passable("hydromel"),
         MobLair = Object::blocking_non_carriable("mob_lair"),
+        Torch = Object::passable("torch"),
+        StairsDown = Object::passable_non_carriable("stairs_down"),
+        StairsUp = Object::passable_non_carriable("stairs_up"),
+        TinOre = Object::passable("tin_ore"),
+        TinIngot = Object::passable("tin_ingot"),
+        BronzeIngot = Object::passable("bronze_ingot"),
+        IronOre = Object::passable("iron_ore"),
+        IronIngot = Object::passable("iron_ingot"),
+        SilverOre = Object::passable("silver_ore"),
+        SilverIngot = Object::passable("silver_ingot"),
+        GoldOre = Object::passable("gold_ore"),
+        GoldIngot = Object::passable("gold_ingot"),
+        Coal = Object::passable("coal"),
+        BronzeSword = Object::tool("bronze_sword", ToolCategory::Weapon, 3, 90),
+        BronzeArmor = Object::armor("bronze_armor", 35),
+        IronSword = Object::tool("iron_sword", ToolCategory::Weapon, 5, 150),
+        IronArmor = Object::armor("iron_armor", 50),
+        Statue = Object::blocking("statue"),
+        Pickaxe = Object::tool("pickaxe", ToolCategory::Pickaxe, 1, 80),
+        BronzePickaxe = Object::tool("bronze_pickaxe", ToolCategory::Pickaxe, 2, 120),
+        IronPickaxe = Object::tool("iron_pickaxe", ToolCategory::Pickaxe, 3, 200),
+        Axe = Object::tool("axe", ToolCategory::Axe, 1, 80),
+        Hoe = Object::tool("hoe", ToolCategory::Hoe, 1, 80),
+        FishingRod = Object::tool("fishing_rod", ToolCategory::FishingRod, 1, 50),
+        Hammer = Object::tool("hammer", ToolCategory::Hammer, 1, 120),
+        Bow = Object::tool("bow", ToolCategory::Weapon, 2, 80),
+        Sling = Object::tool("sling", ToolCategory::Weapon, 1, 60),
+        Corpse = Object::passable_non_carriable("corpse"),
+        Grave = Object::passable_non_carriable("grave"),
+        Apothecary = Object::blocking("apothecary"),
+        Bandage = Object::passable("bandage"),
+        Medicine = Object::passable("medicine"),
+        Stove = Object::blocking("stove"),
+        Stew = Object::passable("stew"),
+        HoneyCake = Object::passable("honey_cake"),
     }
 }
 
@@ -66,16 +103,21 @@ enum_map! {
         StoneWall = Tile::wall("stone"),
         DungeonWall = Tile::wall("dungeon"),
         WoodWall = Tile::wall("wood"),
+
+        SnowFloor = Tile::floor("snow"),
+        AshFloor = Tile::floor("ash"),
     }
 }
 
 enum_map! {
     MobId => MobData {
-        Sheep = MobData::new("sheep", 2, 60.0, 0, ObjectId::Hide),
-        Boar = MobData::new("boar", 3, 50.0, 0, ObjectId::Hide),
-        Undead = MobData::new("undead", 5, 40.0, 2, ObjectId::CopperIngot),
-        Snake = MobData::new("snake", 1, 70.0, 1, ObjectId::Hide),
-        Moose = MobData::new("moose", 6, 30.0, 0, ObjectId::Hide),
+        Sheep = MobData::new("sheep", 2, 60.0, 0, 0, 0.1, ObjectId::Hide),
+        Boar = MobData::new("boar", 3, 50.0, 0, 0, 0.1, ObjectId::Hide),
+        Undead = MobData::new("undead", 5, 40.0, 2, 2, 0.05, ObjectId::CopperIngot),
+        Snake = MobData::new("snake", 1, 70.0, 1, 1, 0.3, ObjectId::Hide),
+        Moose = MobData::new("moose", 6, 30.0, 0, 0, 0.05, ObjectId::Hide),
+        // Recruited into the colony, see TaskKind::Recruit
+        Wanderer = MobData::new("wanderer", 5, 40.0, 0, 0, 0.1, ObjectId::Bread),
     }
 }
 
@@ -85,6 +127,14 @@ pub static SPRITE_ANIMATIONS: LazyLock<HashMap<&'static str, (u32, f32)>> =
 pub static SPRITE_SIZE: LazyLock<HashMap<&'static str, (u32, u32)>> =
     LazyLock::new(|| HashMap::from([("sprites/moose.png", (32, 32))]));
 
+// Ores of the underground veins, by depth
+#[rustfmt::skip]
+pub const LAYER_ORES: &[&[(ObjectId, u32)]] = &[
+    &[(ObjectId::CopperOre, 3), (ObjectId::TinOre, 3), (ObjectId::Coal, 3), (ObjectId::IronOre, 2)],
+    &[(ObjectId::IronOre, 4), (ObjectId::Coal, 2), (ObjectId::SilverOre, 2), (ObjectId::CopperOre, 1), (ObjectId::TinOre, 1)],
+    &[(ObjectId::IronOre, 3), (ObjectId::SilverOre, 3), (ObjectId::GoldOre, 2), (ObjectId::Coal, 1)],
+];
+
 #[rustfmt::skip]
 pub const BUILD_RECIPES: &[(BuildResult, &[ObjectId])] = &[
     (BuildResult::Tile(TileId::WoodWall), &[ObjectId::Wood]),
@@ -95,36 +145,155 @@ pub const BUILD_RECIPES: &[(BuildResult, &[ObjectId])] = &[
     (BuildResult::Object(ObjectId::Stool), &[ObjectId::Wood]),
     (BuildResult::Object(ObjectId::Bed), &[ObjectId::Wood]),
     (BuildResult::Object(ObjectId::Door), &[ObjectId::Wood]),
+    (BuildResult::Object(ObjectId::Torch), &[ObjectId::Wood]),
+    (BuildResult::Object(ObjectId::StairsDown), &[ObjectId::Rock, ObjectId::Wood]),
+    (BuildResult::Object(ObjectId::StairsUp), &[ObjectId::Rock, ObjectId::Wood]),
     (BuildResult::Object(ObjectId::Farm), &[ObjectId::Seeds]),
     (BuildResult::Object(ObjectId::Scarecrow), &[ObjectId::Wood, ObjectId::Wheat, ObjectId::Wheat]),
     (BuildResult::Object(ObjectId::Furnace), &[ObjectId::Rock, ObjectId::Rock, ObjectId::Rock]),
     (BuildResult::Object(ObjectId::Forge), &[ObjectId::Rock, ObjectId::Rock, ObjectId::Rock, ObjectId::CopperOre, ObjectId::CopperOre]),
     (BuildResult::Object(ObjectId::Anvil), &[ObjectId::CopperIngot, ObjectId::CopperIngot, ObjectId::CopperIngot, ObjectId::CopperIngot]),
     (BuildResult::Object(ObjectId::Grindstone), &[ObjectId::Rock, ObjectId::Wood]),
+    (BuildResult::Object(ObjectId::Apothecary), &[ObjectId::Wood, ObjectId::Wood, ObjectId::Rock]),
+    (BuildResult::Object(ObjectId::Stove), &[ObjectId::Rock, ObjectId::Rock, ObjectId::CopperIngot]),
+    (BuildResult::Object(ObjectId::Statue), &[ObjectId::Rock, ObjectId::Rock, ObjectId::SilverIngot, ObjectId::GoldIngot]),
     (BuildResult::Object(ObjectId::Haystack), &[ObjectId::Wheat, ObjectId::Wheat, ObjectId::Wheat]),
     (BuildResult::Object(ObjectId::Bush), &[ObjectId::Berries]),
     (BuildResult::Object(ObjectId::Beehive), &[ObjectId::Wood, ObjectId::Honeycomb, ObjectId::Wood]),
     (BuildResult::Object(ObjectId::MeadVat), &[ObjectId::Wood, ObjectId::Wood, ObjectId::Wood, ObjectId::Honeycomb, ObjectId::Honeycomb]),
 ];
 
+// Output and inputs of each recipe, the first one is selected when the workstation is built
 #[rustfmt::skip]
-pub static WORKSTATIONS: LazyLock<HashMap<ObjectId, (ObjectId, Vec<ObjectId>)>> =
+pub static WORKSTATIONS: LazyLock<HashMap<ObjectId, Vec<(ObjectId, Vec<ObjectId>)>>> =
 LazyLock::new(|| HashMap::from([
-    (ObjectId::Furnace, (ObjectId::Bread, vec![ObjectId::Wheat, ObjectId::Wood])),
-    (ObjectId::Forge, (ObjectId::CopperIngot, vec![ObjectId::CopperOre, ObjectId::CopperOre])),
-    (ObjectId::Grindstone, (ObjectId::Sword, vec![ObjectId::CopperIngot, ObjectId::CopperIngot])),
-    (ObjectId::Anvil, (ObjectId::Armor, vec![ObjectId::CopperIngot, ObjectId::CopperIngot, ObjectId::CopperIngot])),
-    (ObjectId::MeadVat, (ObjectId::Hydromel, vec![ObjectId::Honeycomb, ObjectId::WaterBucket])),
+    (ObjectId::Furnace, vec![(ObjectId::Bread, vec![ObjectId::Wheat, ObjectId::Wood])]),
+    (ObjectId::Forge, vec![
+        (ObjectId::CopperIngot, vec![ObjectId::CopperOre, ObjectId::CopperOre]),
+        (ObjectId::TinIngot, vec![ObjectId::TinOre, ObjectId::TinOre]),
+        (ObjectId::BronzeIngot, vec![ObjectId::CopperIngot, ObjectId::CopperIngot, ObjectId::TinIngot]),
+        (ObjectId::IronIngot, vec![ObjectId::IronOre, ObjectId::IronOre, ObjectId::Coal]),
+        (ObjectId::SilverIngot, vec![ObjectId::SilverOre, ObjectId::SilverOre, ObjectId::Coal]),
+        (ObjectId::GoldIngot, vec![ObjectId::GoldOre, ObjectId::GoldOre, ObjectId::Coal]),
+    ]),
+    (ObjectId::Grindstone, vec![
+        (ObjectId::Sword, vec![ObjectId::CopperIngot, ObjectId::CopperIngot]),
+        (ObjectId::BronzeSword, vec![ObjectId::BronzeIngot, ObjectId::BronzeIngot]),
+        (ObjectId::IronSword, vec![ObjectId::IronIngot, ObjectId::IronIngot, ObjectId::Wood]),
+        (ObjectId::FishingRod, vec![ObjectId::Wood, ObjectId::Wood]),
+        (ObjectId::Bow, vec![ObjectId::Wood, ObjectId::Wood, ObjectId::Hide]),
+        (ObjectId::Sling, vec![ObjectId::Hide]),
+    ]),
+    (ObjectId::Anvil, vec![
+        (ObjectId::Armor, vec![ObjectId::CopperIngot, ObjectId::CopperIngot, ObjectId::CopperIngot]),
+        (ObjectId::BronzeArmor, vec![ObjectId::BronzeIngot, ObjectId::BronzeIngot, ObjectId::BronzeIngot]),
+        (ObjectId::IronArmor, vec![ObjectId::IronIngot, ObjectId::IronIngot, ObjectId::IronIngot, ObjectId::Hide]),
+        (ObjectId::Pickaxe, vec![ObjectId::CopperIngot, ObjectId::Wood]),
+        (ObjectId::BronzePickaxe, vec![ObjectId::BronzeIngot, ObjectId::BronzeIngot, ObjectId::Wood]),
+        (ObjectId::IronPickaxe, vec![ObjectId::IronIngot, ObjectId::IronIngot, ObjectId::Wood]),
+        (ObjectId::Axe, vec![ObjectId::CopperIngot, ObjectId::Wood]),
+        (ObjectId::Hoe, vec![ObjectId::CopperIngot, ObjectId::Wood]),
+        (ObjectId::Hammer, vec![ObjectId::CopperIngot, ObjectId::Rock]),
+    ]),
+    (ObjectId::MeadVat, vec![(ObjectId::Hydromel, vec![ObjectId::Honeycomb, ObjectId::WaterBucket])]),
+    (ObjectId::Apothecary, vec![
+        (ObjectId::Bandage, vec![ObjectId::Hide]),
+        (ObjectId::Medicine, vec![ObjectId::Berries, ObjectId::Honeycomb]),
+    ]),
+    (ObjectId::Stove, vec![
+        (ObjectId::Stew, vec![ObjectId::Fish, ObjectId::Wheat, ObjectId::Berries]),
+        (ObjectId::HoneyCake, vec![ObjectId::Wheat, ObjectId::Wheat, ObjectId::Honeycomb]),
+    ]),
 ]));
 
+pub fn workstation_recipe(
+    workstation: ObjectId,
+    recipe: usize,
+) -> Option<&'static (ObjectId, Vec<ObjectId>)> {
+    WORKSTATIONS.get(&workstation)?.get(recipe)
+}
+
+// Only pickaxe tiers gate tasks (ore veins, see TaskNeeds::dig), other tools have tier 0
+pub static TOOL_TIERS: LazyLock<HashMap<ObjectId, u8>> = LazyLock::new(|| {
+    HashMap::from([
+        (ObjectId::Pickaxe, 1),
+        (ObjectId::BronzePickaxe, 2),
+        (ObjectId::IronPickaxe, 3),
+    ])
+});
+
+// Reach, speed and accuracy of weapons, the damage is the tool power
+#[rustfmt::skip]
+pub static WEAPONS: LazyLock<HashMap<ObjectId, WeaponData>> = LazyLock::new(|| HashMap::from([
+    (ObjectId::Sword, WeaponData { range: 1, cooldown: 2, accuracy: 0.75 }),
+    (ObjectId::BronzeSword, WeaponData { range: 1, cooldown: 2, accuracy: 0.8 }),
+    (ObjectId::IronSword, WeaponData { range: 1, cooldown: 2, accuracy: 0.85 }),
+    (ObjectId::Bow, WeaponData { range: 7, cooldown: 4, accuracy: 0.65 }),
+    (ObjectId::Sling, WeaponData { range: 5, cooldown: 3, accuracy: 0.55 }),
+]));
+
+// Pickaxe tier needed to dig a wall containing this ore
+pub static MINING_TIERS: LazyLock<HashMap<ObjectId, u8>> = LazyLock::new(|| {
+    HashMap::from([
+        (ObjectId::IronOre, 1),
+        (ObjectId::SilverOre, 2),
+        (ObjectId::GoldOre, 3),
+    ])
+});
+
 pub static EAT_VALUES: LazyLock<HashMap<ObjectId, i32>> = LazyLock::new(|| {
     HashMap::from([
         (ObjectId::Bread, 500),
         (ObjectId::Fish, 600),
         (ObjectId::Wheat, 50),
+        (ObjectId::Berries, 150),
+        (ObjectId::Honeycomb, 200),
+        (ObjectId::Stew, 900),
+        (ObjectId::HoneyCake, 800),
     ])
 });
 
+// Spoilage gained every spoilage tick, food rots away at SPOILAGE_MAX
+pub static SPOILAGE_RATES: LazyLock<HashMap<ObjectId, u8>> = LazyLock::new(|| {
+    HashMap::from([
+        (ObjectId::Fish, 12),
+        (ObjectId::Berries, 8),
+        (ObjectId::Stew, 6),
+        (ObjectId::Bread, 4),
+        (ObjectId::HoneyCake, 3),
+        (ObjectId::Wheat, 1),
+    ])
+});
+
+// Drinking straight from water tiles
+pub const WATER_DRINK_VALUE: i32 = 300;
+
+pub static DRINK_VALUES: LazyLock<HashMap<ObjectId, i32>> = LazyLock::new(|| {
+    HashMap::from([
+        (ObjectId::WaterBucket, 500),
+        (ObjectId::Hydromel, 400),
+    ])
+});
+
+// Mood of dwellers around
+pub static BEAUTY_VALUES: LazyLock<HashMap<ObjectId, i32>> = LazyLock::new(|| {
+    HashMap::from([
+        (ObjectId::Statue, 8),
+        (ObjectId::Bush, 2),
+        (ObjectId::BerryBush, 2),
+        (ObjectId::Torch, 1),
+        (ObjectId::Table, 1),
+        (ObjectId::Bed, 1),
+        (ObjectId::Grave, -2),
+        (ObjectId::MobLair, -4),
+        (ObjectId::Corpse, -5),
+    ])
+});
+
+// Health restored when a dweller in bed is tended with it
+pub static MEDICINE_VALUES: LazyLock<HashMap<ObjectId, u32>> =
+    LazyLock::new(|| HashMap::from([(ObjectId::Bandage, 3), (ObjectId::Medicine, 6)]));
+
 pub static SLEEP_VALUES: LazyLock<HashMap<ObjectId, i32>> = LazyLock::new(|| {
     HashMap::from([
         (ObjectId::Bed, 100),
@@ -133,3 +302,58 @@ pub static SLEEP_VALUES: LazyLock<HashMap<ObjectId, i32>> = LazyLock::new(|| {
         (ObjectId::Table, 20),
     ])
 });
+
+// Healing while resting on it, the wounded are only carried to and tended in these
+pub static BED_VALUES: LazyLock<HashMap<ObjectId, i32>> =
+    LazyLock::new(|| HashMap::from([(ObjectId::Bed, 100), (ObjectId::Haystack, 60)]));
+
+// Fuel: number of fire ticks before the object or tile burns out
+pub static FLAMMABLE_OBJECTS: LazyLock<HashMap<ObjectId, u8>> = LazyLock::new(|| {
+    HashMap::from([
+        (ObjectId::Wood, 8),
+        (ObjectId::Tree, 20),
+        (ObjectId::PalmTree, 15),
+        (ObjectId::TallGrass, 3),
+        (ObjectId::WheatPlant, 3),
+        (ObjectId::Wheat, 3),
+        (ObjectId::Bush, 4),
+        (ObjectId::BerryBush, 4),
+        (ObjectId::Haystack, 10),
+        (ObjectId::Scarecrow, 6),
+        (ObjectId::Table, 12),
+        (ObjectId::Stool, 6),
+        (ObjectId::Bed, 10),
+        (ObjectId::Door, 10),
+        (ObjectId::Beehive, 8),
+        (ObjectId::MeadVat, 12),
+        (ObjectId::Coal, 10),
+    ])
+});
+
+pub static FLAMMABLE_TILES: LazyLock<HashMap<TileId, u8>> = LazyLock::new(|| {
+    HashMap::from([
+        (TileId::WoodWall, 25),
+        (TileId::WoodFloor, 15),
+        (TileId::Bridge, 15),
+    ])
+});
+
+// Temperature around workstations while a dweller works on them
+pub static WORKSTATION_HEAT: LazyLock<HashMap<ObjectId, f32>> =
+    LazyLock::new(|| {
+        HashMap::from([
+            (ObjectId::Furnace, 200.0),
+            (ObjectId::Forge, 300.0),
+            (ObjectId::Stove, 150.0),
+        ])
+    });
+
+// Light emitted by objects, from 1 to LIGHT_MAX
+pub static LIGHT_SOURCES: LazyLock<HashMap<ObjectId, u8>> = LazyLock::new(|| {
+    HashMap::from([
+        (ObjectId::Torch, 12),
+        (ObjectId::Furnace, 7),
+        (ObjectId::Forge, 9),
+        (ObjectId::Stove, 5),
+    ])
+});
@@ -3,8 +3,8 @@ use std::sync::LazyLock;
 use bevy::{platform::collections::HashMap, prelude::*};
 
 use crate::{
-    StructureData, TilePlaced,
-    data::{MobId, ObjectId, TileId},
+    StructureData, StructureLocation, StructureSpawn, TilePlaced,
+    data::{BiomeId, MobId, ObjectId, TileId},
     enum_map, structure_ascii,
 };
 
@@ -19,6 +19,21 @@ pub static ASCII_TILES: LazyLock<HashMap<char, TilePlaced>> = LazyLock::new(|| {
         ('=', TileId::WoodWall.place()),
         ('-', TileId::WoodFloor.place()),
         ('~', TileId::Water.place()),
+        ('b', TileId::WoodFloor.with(ObjectId::Bed)),
+        ('t', TileId::WoodFloor.with(ObjectId::Table)),
+        ('s', TileId::WoodFloor.with(ObjectId::Stool)),
+        ('f', TileId::WoodFloor.with(ObjectId::Furnace)),
+        ('F', TileId::WoodFloor.with(ObjectId::Forge)),
+        ('A', TileId::WoodFloor.with(ObjectId::Anvil)),
+        ('G', TileId::WoodFloor.with(ObjectId::Grindstone)),
+    ])
+});
+
+// Floors receiving an object from the loot table of the structure
+pub static ASCII_LOOT_TILES: LazyLock<HashMap<char, TilePlaced>> = LazyLock::new(|| {
+    HashMap::from([
+        ('$', TileId::DungeonFloor.place()),
+        ('%', TileId::WoodFloor.place()),
     ])
 });
 
@@ -29,10 +44,10 @@ enum_map! {
             "
   #####
  #.....#
+#$......#
 #.......#
 #.......#
-#.......#
-#.......#
+#......$#
  #.....#
   ##D##
             ",
@@ -43,12 +58,82 @@ enum_map! {
             "
 ,,,,,,,,,
 ,====,,,,,,,,,,,,
-,=--=,,,,,HH,,,,,
+,=-%=,,,,,HH,,,,,
 ,=--=,,,HHH,,,,,,
 ,=Ď==,,,,,,,,,,,,
 ,,,,,,,,,,,,,
             ",
-            vec![]
+            vec![(2, 3, MobId::Wanderer)]
+        ),
+
+        Crypt = structure_ascii!(
+            "
+###########################
+#$...#.......#.......#...$#
+#....#.......#.......#....#
+#....D.......D.......D....#
+#....#.......#.......#....#
+######.......#.......######
+     #########.#######
+             #.#
+     #########D#######
+     #$..............$#
+     #................#
+     #################
+            ",
+            vec![(9, 9, MobId::Undead), (17, 9, MobId::Undead), (13, 2, MobId::Undead)]
+        ),
+
+        DesertRuins = structure_ascii!(
+            "
+ ## ###    ##
+ #.....   .$#
+ #.       ..
+    ..$.. .
+ #...   ... #
+ ###  # ## ##
+            ",
+            vec![(5, 3, MobId::Snake)]
         ),
     }
 }
+
+#[rustfmt::skip]
+pub const STRUCTURE_SPAWNS: &[StructureSpawn] = &[
+    StructureSpawn {
+        structure: StructureId::DungeonCircleRoom,
+        location: StructureLocation::Mountain,
+        biomes: &[],
+        spacing: 3,
+        min_distance: 1,
+        chance: 0.8,
+        loot: &[(ObjectId::CopperIngot, 3), (ObjectId::Bread, 3), (ObjectId::Sword, 1), (ObjectId::Armor, 1)],
+    },
+    StructureSpawn {
+        structure: StructureId::Crypt,
+        location: StructureLocation::Mountain,
+        biomes: &[],
+        spacing: 8,
+        min_distance: 4,
+        chance: 0.7,
+        loot: &[(ObjectId::CopperIngot, 4), (ObjectId::Sword, 2), (ObjectId::Armor, 2), (ObjectId::Hydromel, 1)],
+    },
+    StructureSpawn {
+        structure: StructureId::Outpost,
+        location: StructureLocation::Surface,
+        biomes: &[BiomeId::Plains, BiomeId::Forest, BiomeId::Tundra],
+        spacing: 4,
+        min_distance: 2,
+        chance: 0.6,
+        loot: &[(ObjectId::Wood, 3), (ObjectId::Wheat, 3), (ObjectId::Seeds, 2), (ObjectId::Bread, 1)],
+    },
+    StructureSpawn {
+        structure: StructureId::DesertRuins,
+        location: StructureLocation::Surface,
+        biomes: &[BiomeId::Desert],
+        spacing: 5,
+        min_distance: 2,
+        chance: 0.6,
+        loot: &[(ObjectId::CopperOre, 3), (ObjectId::WaterBucket, 2), (ObjectId::CopperIngot, 1)],
+    },
+];
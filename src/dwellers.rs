@@ -1,17 +1,30 @@
-use std::collections::BinaryHeap;
-
-use bevy::{platform::collections::HashSet, prelude::*, sprite::Anchor};
+use bevy::{
+    platform::collections::{HashMap, HashSet},
+    prelude::*,
+    sprite::Anchor,
+};
 use rand::prelude::*;
 use uuid::Uuid;
 
 use crate::{
-    BuildResult, CHUNK_SIZE, ObjectSlot, SaveScoped, SpriteLoader, TILE_SIZE, Task,
-    TaskCompletionEvent, TaskKind, TaskNeeds, TilemapData,
-    data::ObjectId,
-    despawn_dweller_hover,
+    BuildResult, CHUNK_SIZE, Layer, ObjectSlot, SPOILAGE_MAX, SaveScoped, SpriteLoader, TILE_SIZE,
+    Task, TaskCompletionEvent, TaskKind, TaskNeeds, TilemapData, ToolCategory, Weather,
+    combat::{WeaponData, skill_accuracy, skill_dodge},
+    data::{DRINK_VALUES, EAT_VALUES, ObjectId, TOOL_TIERS},
+    despawn_dweller_hover, has_line_of_sight,
     mobs::Mob,
+    mood::{
+        MOOD_BASE, MOOD_HUNGRY, MOOD_LOW, MOOD_LOW_WORK_SPEED, MOOD_MAX, MOOD_PER_WOUND,
+        MOOD_THIRSTY, MOOD_TIRED, Thought,
+    },
     observe_dweller_hover,
     random_text::{NAMES, generate_word},
+    social::{
+        FRIEND_OPINION, MOOD_PARTNER, MOOD_PER_FRIEND, MOOD_PER_RIVAL, MOOD_RELATIONS_MAX,
+        OPINION_MAX, RIVAL_OPINION, Relation, RelationKind,
+    },
+    task_assignment::{AssignmentCache, PartialAssignment, TaskAssigner, assignment_cost},
+    task_index::TaskIndex,
     tasks::{TaskBundle, WorkstationAmount},
     utils::transform_to_pos,
 };
@@ -27,23 +40,68 @@ const HEALTH_BASE: u32 = 10;
 
 const DWELLER_DETECTION_TILE_RADIUS: i32 = 15;
 
+const FOUNDERS: u32 = 10;
+
 #[derive(Message)]
-pub struct SpawnDwellersOnChunk(pub IVec2);
+pub struct SpawnDwellersOnChunk {
+    pub chunk_pos: IVec3,
+    pub count: u32,
+    // Arrivals walk in to the colony
+    pub walk_to: Option<IVec3>,
+}
+
+impl SpawnDwellersOnChunk {
+    pub fn founders(chunk_pos: IVec3) -> Self {
+        Self {
+            chunk_pos,
+            count: FOUNDERS,
+            walk_to: None,
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Reflect, Default, Debug)]
+pub enum DwellerState {
+    #[default]
+    Active,
+    // Dies once bleed_out reaches 0, unless resting in a bed or being carried to one
+    Downed {
+        bleed_out: u32,
+    },
+}
 
 #[derive(Component, Reflect, Default)]
 #[reflect(Component, Default)]
-#[require(Name::new("dweller"), SaveScoped)]
+#[require(Name::new("dweller"), SaveScoped, Layer)]
 pub struct Dweller {
     pub uuid: Uuid,
     pub name: String,
-    pub move_queue: Vec<IVec2>, // next move is at the end
+    pub move_queue: Vec<IVec3>, // next move is at the end
     pub object: Option<ObjectId>,
+    pub object_spoilage: u8, // Kept while carried, see spoilage.rs
     pub tool: Option<ObjectId>,
+    pub tool_durability: u32,
     pub armor: Option<ObjectId>,
     pub health: u32,
     pub food: u32,
     pub sleep: u32,
+    pub thirst: u32,
     pub cached_speed_ratio: f32,
+    // Work done on the current task once on its location
+    pub work: f32,
+    pub combat_skill: u32,
+    pub attack_cooldown: u32, // ticks
+    pub state: DwellerState,
+    // Downed dweller being carried to a bed
+    pub carried: Option<Uuid>,
+    pub mood: i32,
+    pub thoughts: Vec<(Thought, u32)>, // needs ticks left
+    pub beauty: i32,                   // of the surroundings
+    pub mental_break: u32,             // needs ticks left
+    pub relations: Vec<Relation>,
+    pub partner: Option<Uuid>,
+    // Parents of dwellers born in the colony, names are kept once they are gone
+    pub parents: Vec<(Uuid, String)>,
 }
 
 impl Dweller {
@@ -53,20 +111,54 @@ impl Dweller {
             name,
             move_queue: Vec::new(),
             object: None,
+            object_spoilage: 0,
             tool: None,
+            tool_durability: 0,
             armor: None,
             health: HEALTH_BASE,
             food: NEEDS_MAX,
             sleep: NEEDS_MAX,
+            thirst: NEEDS_MAX,
             cached_speed_ratio: 1.0,
+            work: 0.0,
+            combat_skill: rand::random_range(0..=3),
+            attack_cooldown: 0,
+            state: DwellerState::Active,
+            carried: None,
+            mood: MOOD_BASE,
+            thoughts: Vec::new(),
+            beauty: 0,
+            mental_break: 0,
+            relations: Vec::new(),
+            partner: None,
+            parents: Vec::new(),
         }
     }
 
+    pub fn random_name() -> String {
+        let mut name = generate_word(&NAMES, &mut rand::rng());
+        name.get_mut(0..1).unwrap().make_ascii_uppercase();
+        name
+    }
+
+    pub fn is_downed(&self) -> bool {
+        matches!(self.state, DwellerState::Downed { .. })
+    }
+
     pub fn can_do(&self, task_kind: TaskKind, task_needs: &TaskNeeds) -> bool {
+        if self.is_downed() {
+            return false;
+        }
+
         match task_kind {
             TaskKind::Workstation {
                 amount: WorkstationAmount::Finite(0),
+                ..
             } => return false,
+            TaskKind::CarryToBed if self.carried.is_none() => return false,
+            // Dwellers having a mental break refuse to work
+            TaskKind::Eat | TaskKind::Drink | TaskKind::Sleep => {}
+            _ if self.is_breaking() => return false,
             _ => {}
         }
 
@@ -101,17 +193,125 @@ impl Dweller {
             TaskNeeds::Impossible => {
                 return false;
             }
+            TaskNeeds::Tool(category, tier) => {
+                if !self.has_tool(*category, *tier) {
+                    return false;
+                }
+            }
         }
 
         true
     }
 
+    fn has_tool(&self, category: ToolCategory, tier: u8) -> bool {
+        self.tool
+            .and_then(tool_kind)
+            .is_some_and(|(tool_category, tool_tier)| {
+                tool_category == category && tool_tier >= tier
+            })
+    }
+
+    pub fn tool_tier(&self) -> u8 {
+        self.tool
+            .and_then(|tool| TOOL_TIERS.get(&tool))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn tool_category(&self) -> Option<ToolCategory> {
+        match self.tool?.data().slot() {
+            ObjectSlot::Tool { category, .. } => Some(*category),
+            _ => None,
+        }
+    }
+
+    pub fn has_tool_for(&self, task_kind: TaskKind) -> bool {
+        task_kind.tool().is_some() && self.tool_category() == task_kind.tool()
+    }
+
+    pub fn carry(&mut self, object: ObjectId, spoilage: u8) {
+        self.object = Some(object);
+        self.object_spoilage = spoilage;
+    }
+
+    pub fn equip_tool(&mut self, tool: ObjectId) {
+        if let ObjectSlot::Tool { durability, .. } = tool.data().slot() {
+            self.tool = Some(tool);
+            self.tool_durability = *durability;
+        }
+    }
+
+    // Tools break once worn out
+    pub fn wear_tool(&mut self) {
+        self.tool_durability = self.tool_durability.saturating_sub(1);
+
+        if self.tool_durability == 0
+            && let Some(tool) = self.tool.take()
+        {
+            info!("Dweller {}'s {:?} broke", self.name, tool);
+        }
+    }
+
+    pub fn tool_power(&self) -> u32 {
+        self.tool.map_or(0, |tool| match tool.data().slot() {
+            ObjectSlot::Tool { power, .. } => *power,
+            _ => 0,
+        })
+    }
+
+    // Work done per tick, the matching tool speeds it up and a low mood slows it down
+    pub fn work_speed(&self, task_kind: TaskKind) -> f32 {
+        let speed =
+            if self.has_tool_for(task_kind) && self.tool_category() != Some(ToolCategory::Weapon) {
+                1.0 + self.tool_power() as f32
+            } else {
+                1.0
+            };
+
+        if self.mood < MOOD_LOW {
+            speed * MOOD_LOW_WORK_SPEED
+        } else {
+            speed
+        }
+    }
+
     pub fn max_health(&self) -> u32 {
         HEALTH_BASE
-            + self.armor.map_or(0, |a| match a.data().slot() {
-                ObjectSlot::Armor(hp) => *hp,
-                _ => 0,
-            })
+    }
+
+    pub fn armor_mitigation(&self) -> u32 {
+        self.armor.map_or(0, |armor| match armor.data().slot() {
+            ObjectSlot::Armor(mitigation) => *mitigation,
+            _ => 0,
+        })
+    }
+
+    pub fn weapon(&self) -> &'static WeaponData {
+        WeaponData::of(self.tool)
+    }
+
+    pub fn accuracy(&self) -> f64 {
+        self.weapon().accuracy + skill_accuracy(self.combat_skill)
+    }
+
+    pub fn dodge(&self) -> f64 {
+        skill_dodge(self.combat_skill)
+    }
+
+    // Damage of the tool in hand, only weapons are better than fists
+    pub fn damage(&self) -> u32 {
+        match self.tool_category() {
+            Some(ToolCategory::Weapon) => self.tool_power(),
+            _ => 1,
+        }
+    }
+
+    // Ranged weapons shoot from afar, through tiles letting the light through
+    pub fn can_hit(&self, pos: IVec3, target: IVec3, tilemap_data: &TilemapData) -> bool {
+        let range = self.weapon().range;
+        pos.z == target.z
+            && pos.distance_squared(target) <= range.pow(2)
+            && (range == 1 || has_line_of_sight(tilemap_data, pos, target))
     }
 
     pub fn health(&mut self, x: i32) {
@@ -129,6 +329,35 @@ impl Dweller {
         self.compute_speed_ratio();
     }
 
+    // Spoiled food feeds less and is unpleasant
+    pub fn eat(&mut self, object: ObjectId, spoilage: u8) {
+        if let Some(value) = EAT_VALUES.get(&object) {
+            self.food(food_value(*value, spoilage));
+        }
+        if let Some(thought) = Thought::of_food(object, spoilage) {
+            self.think(thought);
+        }
+    }
+
+    pub fn thirst(&mut self, x: i32) {
+        self.thirst = self.thirst.saturating_add_signed(x).min(NEEDS_MAX);
+
+        if self.thirst == 0 {
+            self.health(-1);
+        }
+
+        self.compute_speed_ratio();
+    }
+
+    pub fn drink(&mut self, object: ObjectId) {
+        if let Some(value) = DRINK_VALUES.get(&object) {
+            self.thirst(*value);
+        }
+        if let Some(thought) = Thought::of_drink(object) {
+            self.think(thought);
+        }
+    }
+
     pub fn sleep(&mut self, x: i32) {
         self.sleep = self.sleep.saturating_add_signed(x).min(NEEDS_MAX);
 
@@ -149,7 +378,11 @@ impl Dweller {
         let sleep_ratio = self.sleep as f32 / NEEDS_MAX as f32;
         let sleep_speed = 1.0 - (sleep_ratio - 1.0).abs().powi(3);
 
-        self.cached_speed_ratio = health_speed.min(food_speed.min(sleep_speed).max(SPEED_MIN));
+        let thirst_ratio = self.thirst as f32 / NEEDS_MAX as f32;
+        let thirst_speed = 1.0 - (thirst_ratio - 1.0).abs().powi(4);
+
+        self.cached_speed_ratio =
+            health_speed.min(food_speed.min(sleep_speed).min(thirst_speed).max(SPEED_MIN));
     }
 
     pub fn speed_ratio(&self) -> f32 {
@@ -159,6 +392,116 @@ impl Dweller {
     pub fn is_fully_rested(&self) -> bool {
         self.sleep == NEEDS_MAX
     }
+
+    pub fn is_breaking(&self) -> bool {
+        self.mental_break > 0
+    }
+
+    // A new thought replaces the same older one
+    pub fn think(&mut self, thought: Thought) {
+        self.thoughts.retain(|(other, _)| *other != thought);
+        self.thoughts.push((thought, thought.duration()));
+        self.update_mood();
+    }
+
+    pub fn mood_factors(&self) -> Vec<(String, i32)> {
+        let mut factors = self
+            .thoughts
+            .iter()
+            .map(|(thought, _)| (format!("{thought:?}"), thought.mood()))
+            .collect::<Vec<_>>();
+
+        if self.food < NEEDS_MAX / 4 {
+            factors.push(("Hungry".to_string(), MOOD_HUNGRY));
+        }
+        if self.sleep < NEEDS_MAX / 4 {
+            factors.push(("Tired".to_string(), MOOD_TIRED));
+        }
+        if self.thirst < NEEDS_MAX / 4 {
+            factors.push(("Thirsty".to_string(), MOOD_THIRSTY));
+        }
+        let wounds = self.max_health() - self.health;
+        if wounds > 0 {
+            factors.push(("Injured".to_string(), wounds as i32 * MOOD_PER_WOUND));
+        }
+        if self.beauty != 0 {
+            factors.push(("Beauty".to_string(), self.beauty));
+        }
+
+        if self.partner.is_some() {
+            factors.push(("Partner".to_string(), MOOD_PARTNER));
+        }
+        let count = |kind| {
+            self.relations
+                .iter()
+                .filter(|relation| self.relation_kind(relation.dweller) == Some(kind))
+                .count() as i32
+        };
+        let friends = count(RelationKind::Friend);
+        if friends > 0 {
+            factors.push((
+                "Friends".to_string(),
+                (friends * MOOD_PER_FRIEND).min(MOOD_RELATIONS_MAX),
+            ));
+        }
+        let rivals = count(RelationKind::Rival);
+        if rivals > 0 {
+            factors.push((
+                "Rivals".to_string(),
+                (rivals * MOOD_PER_RIVAL).max(-MOOD_RELATIONS_MAX),
+            ));
+        }
+
+        factors
+    }
+
+    pub fn opinion_of(&self, uuid: Uuid) -> i32 {
+        self.relations
+            .iter()
+            .find(|relation| relation.dweller == uuid)
+            .map_or(0, |relation| relation.opinion)
+    }
+
+    pub fn change_opinion(&mut self, uuid: Uuid, name: &str, delta: i32) {
+        if let Some(relation) = self
+            .relations
+            .iter_mut()
+            .find(|relation| relation.dweller == uuid)
+        {
+            relation.opinion = (relation.opinion + delta).clamp(-OPINION_MAX, OPINION_MAX);
+        } else {
+            self.relations.push(Relation {
+                dweller: uuid,
+                name: name.to_string(),
+                opinion: delta.clamp(-OPINION_MAX, OPINION_MAX),
+            });
+        }
+    }
+
+    pub fn relation_kind(&self, uuid: Uuid) -> Option<RelationKind> {
+        if self.partner == Some(uuid) {
+            return Some(RelationKind::Partner);
+        }
+
+        match self.opinion_of(uuid) {
+            opinion if opinion >= FRIEND_OPINION => Some(RelationKind::Friend),
+            opinion if opinion <= RIVAL_OPINION => Some(RelationKind::Rival),
+            _ => None,
+        }
+    }
+
+    pub fn update_mood(&mut self) {
+        let factors = self
+            .mood_factors()
+            .iter()
+            .map(|(_, mood)| mood)
+            .sum::<i32>();
+        self.mood = (MOOD_BASE + factors).clamp(0, MOOD_MAX);
+    }
+}
+
+pub fn food_value(value: i32, spoilage: u8) -> i32 {
+    value * (2 * i32::from(SPOILAGE_MAX) - i32::from(spoilage)) / (2 * i32::from(SPOILAGE_MAX))
 }
 
 #[derive(Resource, Default)]
@@ -200,13 +543,18 @@ pub fn spawn_dwellers(
     tilemap_data: Res<TilemapData>,
     mut ev_spawn: MessageReader<SpawnDwellersOnChunk>,
 ) {
-    for SpawnDwellersOnChunk(chunk_pos) in ev_spawn.read() {
+    for SpawnDwellersOnChunk {
+        chunk_pos,
+        count,
+        walk_to,
+    } in ev_spawn.read()
+    {
         let Some(spawn_pos) = TilemapData::find_from_center_chunk_size(
             TilemapData::local_pos_to_global(*chunk_pos, IVec2::splat(CHUNK_SIZE as i32 / 2)),
             |pos| {
                 for dx in -1..=1 {
                     for dy in -1..=1 {
-                        let neigh_pos = pos + IVec2::new(dx, dy);
+                        let neigh_pos = pos + IVec3::new(dx, dy, 0);
 
                         let Some(tile) = tilemap_data.get(neigh_pos) else {
                             return false;
@@ -221,37 +569,41 @@ pub fn spawn_dwellers(
             },
         ) else {
             error!("No valid spawn position found for dwellers");
-            return;
+            continue;
         };
 
-        let nb_dwellers = 10;
-        let mut rng = rand::rng();
+        for _ in 0..*count {
+            let dweller = Dweller::new(Dweller::random_name());
+
+            if let Some(walk_to) = walk_to {
+                commands.spawn(TaskBundle::new(
+                    Task::new(*walk_to, TaskKind::Walk, Some(dweller.uuid)),
+                    TaskNeeds::Nothing,
+                ));
+            }
 
-        for _ in 0..nb_dwellers {
-            let mut name = generate_word(&NAMES, &mut rng);
-            name.get_mut(0..1).unwrap().make_ascii_uppercase();
-
-            let sprite_i = rng.random_range(1..=4);
-
-            commands
-                .spawn((
-                    Dweller::new(name),
-                    SpriteLoader {
-                        texture_path: format!("sprites/dweller{sprite_i}.png"),
-                    },
-                    Transform::from_xyz(
-                        spawn_pos.x as f32 * TILE_SIZE,
-                        spawn_pos.y as f32 * TILE_SIZE,
-                        Z_INDEX,
-                    ),
-                    Pickable::default(),
-                ))
-                .observe(observe_dweller_hover)
-                .observe(despawn_dweller_hover);
+            spawn_dweller(&mut commands, dweller, spawn_pos);
         }
     }
 }
 
+pub fn spawn_dweller(commands: &mut Commands, dweller: Dweller, pos: IVec3) {
+    let sprite_i = rand::random_range(1..=4);
+
+    commands
+        .spawn((
+            dweller,
+            SpriteLoader {
+                texture_path: format!("sprites/dweller{sprite_i}.png"),
+            },
+            Transform::from_xyz(pos.x as f32 * TILE_SIZE, pos.y as f32 * TILE_SIZE, Z_INDEX),
+            Layer(pos.z),
+            Pickable::default(),
+        ))
+        .observe(observe_dweller_hover)
+        .observe(despawn_dweller_hover);
+}
+
 pub fn spawn_dwellers_name(
     mut commands: Commands,
     q_dwellers: Query<(&Dweller, Entity), Added<Dweller>>,
@@ -271,20 +623,67 @@ pub fn spawn_dwellers_name(
     }
 }
 
+// Category and tier of a tool object
+fn tool_kind(tool: ObjectId) -> Option<(ToolCategory, u8)> {
+    match tool.data().slot() {
+        ObjectSlot::Tool { category, .. } => Some((
+            *category,
+            TOOL_TIERS.get(&tool).copied().unwrap_or_default(),
+        )),
+        _ => None,
+    }
+}
+
 pub fn update_dwellers(
     mut commands: Commands,
-    mut q_dwellers: Query<(&mut Dweller, &Transform)>,
+    mut q_dwellers: Query<(&mut Dweller, &Transform, &Layer)>,
     tilemap_data: Res<TilemapData>,
     mut q_tasks: Query<(Entity, &mut Task, &TaskNeeds)>,
     mut ev_task_completion: MessageWriter<TaskCompletionEvent>,
-    q_mobs: Query<(Entity, &Mob, &Transform)>,
+    q_mobs: Query<(Entity, &Mob, &Transform, &Layer)>,
+    mut task_index: ResMut<TaskIndex>,
 ) {
-    for (mut dweller, transform) in &mut q_dwellers {
-        if !dweller.move_queue.is_empty() {
+    let mut fetched_tools = HashSet::new();
+
+    // Tools required by tasks waiting for a dweller, and the ones speeding them up
+    let mut required_tools = q_tasks
+        .iter()
+        .filter(|(_, task, _)| task.dweller_id.is_none())
+        .filter_map(|(_, _, task_needs)| match task_needs {
+            TaskNeeds::Tool(category, tier) => Some((*category, *tier)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let useful_tools = q_tasks
+        .iter()
+        .filter(|(_, task, _)| task.dweller_id.is_none())
+        .filter_map(|(_, task, _)| task.kind.tool())
+        .collect::<Vec<_>>();
+
+    for (mut dweller, transform, layer) in &mut q_dwellers {
+        if dweller.attack_cooldown > 0 {
+            dweller.attack_cooldown -= 1;
+        }
+
+        let pos = transform_to_pos(transform, layer);
+
+        if dweller.is_downed() {
             continue;
         }
 
-        let pos = transform_to_pos(transform);
+        if !dweller.move_queue.is_empty() {
+            // Ranged attackers stop walking once the target is within reach
+            if dweller.weapon().range > 1
+                && task_index.of_dweller(dweller.uuid).any(|task| {
+                    task.kind == TaskKind::Attack && dweller.can_hit(pos, task.pos, &tilemap_data)
+                })
+            {
+                let next_move = dweller.move_queue.pop();
+                dweller.move_queue.clear();
+                dweller.move_queue.extend(next_move);
+            }
+            continue;
+        }
 
         // Check if dweller has a task assigned in all tasks
         let task = q_tasks
@@ -295,9 +694,15 @@ pub fn update_dwellers(
             });
 
         if let Some((entity_task, mut task, _)) = task {
-            if task.reachable_positions.contains(&pos) {
-                // Reached task location
-                ev_task_completion.write(TaskCompletionEvent { task: entity_task });
+            if task.reachable_positions.contains(&pos)
+                || (task.kind == TaskKind::Attack && dweller.can_hit(pos, task.pos, &tilemap_data))
+            {
+                // Reached task location, work until the task is done
+                dweller.work += dweller.work_speed(task.kind);
+                if dweller.work >= task.kind.work() {
+                    dweller.work = 0.0;
+                    ev_task_completion.write(TaskCompletionEvent { task: entity_task });
+                }
             } else {
                 // Task moved, try to pathfind again
                 if let Some(path) = task.pathfind(pos, &tilemap_data) {
@@ -306,21 +711,95 @@ pub fn update_dwellers(
                 } else {
                     info!("Dweller {} gives up {:?}", dweller.name, task);
                     task.dweller_id = None;
+                    task_index.refresh(entity_task, &task);
                 }
             }
             continue;
         }
 
+        // Without a task to carry them to a bed, put down the dweller being carried
+        if dweller.carried.take().is_some() {
+            debug!(
+                "Dweller {} puts down the dweller they carried",
+                dweller.name
+            );
+        }
+
+        // Dwellers having a mental break wander around
+        if dweller.is_breaking() {
+            let directions = tilemap_data.non_blocking_neighbours_pos(pos, true);
+            if let Some(direction) = directions.choose(&mut rand::rng()) {
+                dweller.move_queue.push(*direction);
+            }
+            continue;
+        }
+
+        // Fetch a tool lying on a stockpile, swapped with the one in hand (see TaskKind::Pickup).
+        // Tools required by waiting tasks come first, dwellers without a tool also take one
+        // speeding up waiting tasks
+        if dweller.object.is_none() {
+            // Index in required_tools of the need the tool fulfils, if any
+            let fetch_reason = |tool: ObjectId| {
+                let (category, tier) = tool_kind(tool)?;
+                let required =
+                    required_tools
+                        .iter()
+                        .position(|(required_category, required_tier)| {
+                            category == *required_category
+                                && tier >= *required_tier
+                                && !dweller.has_tool(*required_category, *required_tier)
+                        });
+                (required.is_some() || (dweller.tool.is_none() && useful_tools.contains(&category)))
+                    .then_some(required)
+            };
+
+            let tool_stockpile = task_index
+                .of_kind(TaskKind::Stockpile)
+                .filter(|stockpile| {
+                    !fetched_tools.contains(&stockpile.pos)
+                        && task_index
+                            .at(stockpile.pos)
+                            .all(|task| task.kind != TaskKind::Pickup)
+                })
+                .filter_map(|stockpile| {
+                    let tool = tilemap_data.get(stockpile.pos)?.object?;
+                    Some((
+                        fetch_reason(tool)?,
+                        TilemapData::manhattan_distance(pos, stockpile.pos),
+                        stockpile.pos,
+                    ))
+                })
+                .min_by_key(|(required, distance, _)| (required.is_none(), *distance));
+
+            if let Some((required, _, stockpile_pos)) = tool_stockpile {
+                // One dweller per need
+                if let Some(required) = required {
+                    required_tools.swap_remove(required);
+                }
+
+                commands.spawn(TaskBundle::new(
+                    Task::new(stockpile_pos, TaskKind::Pickup, Some(dweller.uuid)),
+                    TaskNeeds::EmptyHands,
+                ));
+                fetched_tools.insert(stockpile_pos);
+                debug!(
+                    "Dweller {} fetches a tool at {:?}",
+                    dweller.name, stockpile_pos
+                );
+                continue;
+            }
+        }
+
         // Check for nearby hostile mobs to attack
-        for (entity_mob, mob, mob_transform) in &q_mobs {
+        for (entity_mob, mob, mob_transform, mob_layer) in &q_mobs {
             if !mob.id.data().is_hostile() {
                 continue;
             }
 
-            let mob_pos = transform_to_pos(mob_transform);
+            let mob_pos = transform_to_pos(mob_transform, mob_layer);
             let distance_squared = (pos - mob_pos).length_squared();
 
-            if distance_squared <= DWELLER_DETECTION_TILE_RADIUS.pow(2) {
+            if mob_pos.z == pos.z && distance_squared <= DWELLER_DETECTION_TILE_RADIUS.pow(2) {
                 // New attack task targeting the mob
                 let task_entity = commands
                     .spawn(TaskBundle::new_as_child(
@@ -347,8 +826,12 @@ pub fn update_dwellers(
 
 pub fn assign_tasks_to_dwellers(
     tilemap_data: Res<TilemapData>,
-    mut q_dwellers: Query<(&mut Dweller, &Transform)>,
+    task_assigner: Res<TaskAssigner>,
+    mut task_index: ResMut<TaskIndex>,
+    mut cache: Local<AssignmentCache>,
+    mut q_dwellers: Query<(&mut Dweller, &Transform, &Layer)>,
     mut q_tasks: Query<(Entity, &mut Task, &TaskNeeds)>,
+    q_changed_needs: Query<Entity, Changed<TaskNeeds>>,
 ) {
     // Collect unassigned dwellers and tasks
     let assigned_dwellers = q_tasks
@@ -356,76 +839,137 @@ pub fn assign_tasks_to_dwellers(
         .filter_map(|(_, task, _)| task.dweller_id)
         .collect::<HashSet<_>>();
 
+    let dwellers_positions = q_dwellers
+        .iter()
+        .map(|(_, transform, layer)| transform_to_pos(transform, layer))
+        .collect::<HashSet<_>>();
+
+    let mut dwellers = q_dwellers
+        .iter_mut()
+        .filter_map(|(dweller, transform, layer)| {
+            if assigned_dwellers.contains(&dweller.uuid) {
+                return None;
+            }
+            let pos = transform_to_pos(transform, layer);
+            Some((dweller, pos))
+        })
+        .collect::<Vec<_>>();
+
     let mut tasks = q_tasks
         .iter_mut()
         .filter(|(_, task, _)| {
             task.dweller_id.is_none()
                 && !task.reachable_positions.is_empty()
                 && task.reachable_pathfinding
+                && !task_index.is_blocked(task)
                 // Do not assign tasks that will produce a blocking object where a dweller is standing
                 && (!matches!(task.kind, TaskKind::Build { result } if result.is_blocking())
-                    || !q_dwellers
-                        .iter()
-                        .any(|(_, t)| task.pos == transform_to_pos(t)))
+                    || !dwellers_positions.contains(&task.pos))
         })
         .collect::<Vec<_>>();
 
-    let mut dwellers = q_dwellers
-        .iter_mut()
-        .filter_map(|(dweller, transform)| {
-            if assigned_dwellers.contains(&dweller.uuid) {
-                return None;
-            }
-            let pos = transform_to_pos(transform);
-            Some((dweller, pos))
+    // Only reconsider idle dwellers and assignable tasks that changed, or all of them when paths did
+    let paths_changed = cache.paths_version != tilemap_data.paths_version;
+    let changed_needs = q_changed_needs.iter().collect::<HashSet<_>>();
+
+    let changed_dwellers = dwellers
+        .iter()
+        .map(|(dweller, pos)| {
+            paths_changed || cache.idle_dwellers.get(&dweller.uuid) != Some(&(*pos, dweller.object))
+        })
+        .collect::<Vec<_>>();
+    let changed_tasks = tasks
+        .iter()
+        .map(|(entity, task, _)| {
+            paths_changed
+                || changed_needs.contains(entity)
+                || cache.tasks.get(entity) != Some(&(task.pos, task.reachable_positions.len()))
         })
         .collect::<Vec<_>>();
 
-    // Compute all distances
-    let mut heap = BinaryHeap::new();
+    cache.idle_dwellers = dwellers
+        .iter()
+        .map(|(dweller, pos)| (dweller.uuid, (*pos, dweller.object)))
+        .collect();
+    cache.tasks = tasks
+        .iter()
+        .map(|(entity, task, _)| (*entity, (task.pos, task.reachable_positions.len())))
+        .collect();
 
-    for (dweller_i, (_, dweller_pos)) in dwellers.iter().enumerate() {
-        for (task_i, (_, task, _)) in tasks.iter().enumerate() {
-            let distance = (dweller_pos.x - task.pos.x).abs() + (dweller_pos.y - task.pos.y).abs();
-            heap.push((task.kind.priority(), -distance, dweller_i, task_i));
-        }
+    if !changed_dwellers.contains(&true) && !changed_tasks.contains(&true) {
+        return;
     }
 
-    let mut assigned_dwellers = HashSet::new();
-    let mut assigned_tasks = HashSet::new();
+    cache.update_distance_fields(
+        &dwellers
+            .iter()
+            .map(|(dweller, pos)| (dweller.uuid, *pos))
+            .collect::<Vec<_>>(),
+        &tilemap_data,
+    );
 
-    // Process the heap until it is empty or all tasks/dwellers are assigned
-    while let Some((_, _, dweller_i, task_i)) = heap.pop() {
-        if assigned_dwellers.contains(&dweller_i) || assigned_tasks.contains(&task_i) {
-            continue;
+    let partial = PartialAssignment::new(&changed_dwellers, &changed_tasks, |dweller_i, task_i| {
+        let (dweller, dweller_pos) = &dwellers[dweller_i];
+        let (_, task, task_needs) = &tasks[task_i];
+
+        if !dweller.can_do(task.kind, task_needs) {
+            return None;
         }
 
-        let (_, task, task_needs) = &mut tasks[task_i];
+        // Dwellers do not tend to themselves
+        if task.kind == TaskKind::Tend && task.pos == *dweller_pos {
+            return None;
+        }
+
+        let distance = cache
+            .distance_fields
+            .get(&dweller.uuid)?
+            .estimate(&task.reachable_positions)?;
+
+        Some(assignment_cost(
+            task.kind.priority(),
+            distance,
+            dweller.has_tool_for(task.kind),
+        ))
+    });
+
+    for (dweller_i, task_i) in partial.solve(task_assigner.0.as_ref()) {
+        let (entity, task, _) = &mut tasks[task_i];
         let (dweller, dweller_pos) = &mut dwellers[dweller_i];
 
-        if !dweller.can_do(task.kind, task_needs) {
-            continue;
-        }
+        // Tasks beyond the distance field are pathfound on their own
+        let path = cache
+            .distance_fields
+            .get(&dweller.uuid)
+            .and_then(|field| {
+                field
+                    .closest(&task.reachable_positions)
+                    .and_then(|(goal, _)| field.path_to(goal))
+            })
+            .or_else(|| task.pathfind(*dweller_pos, &tilemap_data));
 
-        // Try pathfinding to task
-        if let Some(path) = task.pathfind(*dweller_pos, &tilemap_data) {
+        if let Some(path) = path {
             task.dweller_id = Some(dweller.uuid);
+            task_index.refresh(*entity, task);
             dweller.move_queue = path;
+            dweller.work = 0.0;
 
-            assigned_dwellers.insert(dweller_i);
-            assigned_tasks.insert(task_i);
             debug!("Dweller {} got task {:?}", dweller.name, task);
         } else {
+            // The dweller may pair with another task next tick
             task.reachable_pathfinding = false;
+            cache.idle_dwellers.remove(&dweller.uuid);
         }
     }
 }
 
 pub fn update_dwellers_movement(
     time: Res<Time>,
-    mut q_dwellers: Query<(&mut Dweller, &mut Transform, &mut Sprite)>,
+    weather: If<Res<Weather>>,
+    tilemap_data: Res<TilemapData>,
+    mut q_dwellers: Query<(&mut Dweller, &mut Transform, &mut Layer, &mut Sprite)>,
 ) {
-    for (mut dweller, mut transform, mut sprite) in &mut q_dwellers {
+    for (mut dweller, mut transform, mut layer, mut sprite) in &mut q_dwellers {
         // Move to next position in queue
 
         if let Some(next_move) = dweller.move_queue.last() {
@@ -436,11 +980,19 @@ pub fn update_dwellers_movement(
 
             let direction = target - transform.translation.truncate();
 
-            let speed = SPEED * dweller.speed_ratio() * time.delta_secs();
+            // Snow slows down
+            let weather_ratio = weather
+                .state_at(&tilemap_data, transform_to_pos(&transform, &layer))
+                .speed_ratio();
+            let speed = SPEED * dweller.speed_ratio() * weather_ratio * time.delta_secs();
+
+            // Stairs lead straight to the layer above or below
+            let changes_layer = next_move.z != layer.0;
 
-            if direction.length() < speed {
+            if direction.length() < speed || changes_layer {
                 transform.translation.x = target.x;
                 transform.translation.y = target.y;
+                layer.set_if_neq(Layer(next_move.z));
                 dweller.move_queue.pop();
             } else {
                 let dir = direction.normalize();
@@ -455,15 +1007,17 @@ pub fn update_dwellers_movement(
 
 pub fn refresh_pathfinding_tasks_on_mobs(
     tilemap_data: Res<TilemapData>,
+    mut task_index: ResMut<TaskIndex>,
     mut q_dwellers: Query<&mut Dweller>,
-    mut q_tasks: Query<(&mut Task, &ChildOf)>,
-    q_mobs: Query<&Transform, With<Mob>>,
+    mut q_tasks: Query<(Entity, &mut Task, &ChildOf)>,
+    q_mobs: Query<(&Transform, &Layer), With<Mob>>,
 ) {
-    for (mut task, child_of) in &mut q_tasks {
-        if let Ok(mob_transform) = q_mobs.get(child_of.parent()) {
-            let mob_pos = transform_to_pos(mob_transform);
+    for (entity, mut task, child_of) in &mut q_tasks {
+        if let Ok((mob_transform, mob_layer)) = q_mobs.get(child_of.parent()) {
+            let mob_pos = transform_to_pos(mob_transform, mob_layer);
             task.pos = mob_pos;
             task.recompute_reachable_positions(&tilemap_data);
+            task_index.refresh(entity, &task);
             if let Some(mut dweller) = q_dwellers
                 .iter_mut()
                 .find(|dweller| Some(dweller.uuid) == task.dweller_id)
@@ -474,3 +1028,51 @@ pub fn refresh_pathfinding_tasks_on_mobs(
         }
     }
 }
+
+// Carried dwellers follow their carrier, also up and down stairs
+pub fn update_carried_dwellers(mut q_dwellers: Query<(&Dweller, &mut Transform, &mut Layer)>) {
+    let carriers = q_dwellers
+        .iter()
+        .filter_map(|(dweller, transform, layer)| {
+            dweller
+                .carried
+                .map(|carried| (carried, (transform.translation, *layer)))
+        })
+        .collect::<HashMap<_, _>>();
+
+    if carriers.is_empty() {
+        return;
+    }
+
+    for (dweller, mut transform, mut layer) in &mut q_dwellers {
+        if let Some((translation, carrier_layer)) = carriers.get(&dweller.uuid) {
+            transform.translation.x = translation.x;
+            transform.translation.y = translation.y;
+            layer.set_if_neq(*carrier_layer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spoiled_food_is_worth_half() {
+        assert_eq!(food_value(100, 0), 100);
+        assert_eq!(food_value(100, SPOILAGE_MAX), 50);
+        assert!(food_value(100, SPOILAGE_MAX / 2) < 100);
+        assert!(food_value(100, SPOILAGE_MAX / 2) > 50);
+    }
+
+    #[test]
+    fn carried_objects_keep_their_spoilage() {
+        let mut dweller = Dweller::new("Test".to_string());
+        dweller.carry(ObjectId::Bread, 40);
+        assert_eq!(dweller.object, Some(ObjectId::Bread));
+        assert_eq!(dweller.object_spoilage, 40);
+
+        dweller.carry(ObjectId::Berries, 0);
+        assert_eq!(dweller.object_spoilage, 0);
+    }
+}
@@ -1,56 +1,287 @@
-use bevy::prelude::*;
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::{platform::collections::HashSet, prelude::*};
+use rand::prelude::*;
 
 use crate::{
-    TilemapData,
-    data::EAT_VALUES,
-    dwellers::{Dweller, NEEDS_MAX},
+    CHUNK_SIZE, Layer, TilePlaced, TilemapData,
+    data::{BED_VALUES, DRINK_VALUES, EAT_VALUES, ObjectId},
+    dwellers::{Dweller, DwellerState, NEEDS_MAX, food_value},
+    mood::{BREAK_CHANCE, BREAK_DURATION, MOOD_BREAK, Thought, beauty_around, sees_corpse},
+    social::{EventLog, RelationKind},
+    task_index::TaskIndex,
     tasks::{Task, TaskBundle, TaskKind, TaskNeeds},
     utils::transform_to_pos,
 };
 
+// Dwellers walk a bit further for better food
+const FOOD_MOOD_WEIGHT: i32 = 20;
+const FOOD_DISTANCE_COST: i32 = 10;
+
+// Needs ticks before a downed dweller left on the floor dies
+const BLEED_OUT: u32 = 100;
+// Chance to heal 1 health per needs tick
+const HEAL_CHANCE: f64 = 0.01;
+// In a bed, scaled by its BED_VALUES quality
+const HEAL_CHANCE_RESTING: f64 = 0.05;
+const REST_VALUE_BED: f64 = 100.0;
+
 pub fn update_dweller_needs(
     mut commands: Commands,
-    tilemap_data: Res<TilemapData>,
-    mut q_needs: Query<(&mut Dweller, &Transform)>,
-    q_tasks: Query<&Task>,
+    mut tilemap_data: ResMut<TilemapData>,
+    mut q_needs: Query<(
+        Entity,
+        &mut Dweller,
+        &mut Transform,
+        &Layer,
+        Option<&Children>,
+    )>,
+    mut q_tasks: Query<&mut Task>,
+    mut q_task_needs: Query<&mut TaskNeeds>,
+    mut task_index: ResMut<TaskIndex>,
+    mut event_log: ResMut<EventLog>,
 ) {
-    for (mut dweller, transform) in &mut q_needs {
-        if dweller.health == 0 {
-            continue;
-        }
+    let mut rng = rand::rng();
+
+    // Bandages stand in for medicine while none is stockpiled
+    let medicine_stockpiled = task_index.of_kind(TaskKind::Stockpile).any(|stockpile| {
+        tilemap_data
+            .get(stockpile.pos)
+            .is_some_and(|tile| tile.object == Some(ObjectId::Medicine))
+    });
+
+    let carried = q_needs
+        .iter()
+        .filter_map(|(_, dweller, _, _, _)| dweller.carried)
+        .collect::<HashSet<_>>();
+    let mut died = vec![];
+
+    for (entity, mut dweller, mut transform, layer, children) in &mut q_needs {
+        let pos = transform_to_pos(&transform, layer);
 
         // Dwellers naturally get hungry and tired
         dweller.food(-1);
         dweller.sleep(-1);
+        dweller.thirst(-1);
 
-        // If they are not working on something already... (especially an Eat / Sleep task)
-        if q_tasks
-            .iter()
-            .any(|task| task.dweller_id == Some(dweller.uuid))
+        // Wounds heal slowly, faster when resting in a good bed
+        let rest_value = tilemap_data
+            .get(pos)
+            .and_then(|tile| tile.object)
+            .and_then(|object| BED_VALUES.get(&object));
+        let resting = rest_value.is_some();
+        let heal_chance = if let Some(rest_value) = rest_value {
+            (HEAL_CHANCE_RESTING * *rest_value as f64 / REST_VALUE_BED).min(1.0)
+        } else if !dweller.is_downed()
+            && dweller.food > NEEDS_MAX / 4
+            && dweller.sleep > NEEDS_MAX / 4
+            && dweller.thirst > NEEDS_MAX / 4
         {
-            continue;
+            HEAL_CHANCE
+        } else {
+            0.0
+        };
+        if dweller.health < dweller.max_health() && rng.random_bool(heal_chance) {
+            dweller.health(1);
+        }
+
+        // Thoughts fade away, surroundings weigh on the mood
+        dweller.thoughts.retain_mut(|(_, ticks)| {
+            *ticks = ticks.saturating_sub(1);
+            *ticks > 0
+        });
+        dweller.beauty = beauty_around(&tilemap_data, pos);
+        if sees_corpse(&tilemap_data, pos) {
+            dweller.think(Thought::SawCorpse);
+        }
+        dweller.update_mood();
+
+        if dweller.is_breaking() {
+            dweller.mental_break -= 1;
+            if !dweller.is_breaking() {
+                info!("Dweller {} is back to work", dweller.name);
+            }
+        } else if !dweller.is_downed() && dweller.mood < MOOD_BREAK && rng.random_bool(BREAK_CHANCE)
+        {
+            dweller.mental_break = BREAK_DURATION;
+            unassign_tasks(&dweller, &mut task_index, &mut q_tasks);
+
+            info!("Dweller {} has a mental break", dweller.name);
+        }
+
+        // Wounded dwellers resting ask to be tended, until they leave or are healed
+        let tend_task = children.and_then(|children| {
+            children.iter().find(|child| {
+                task_index
+                    .get(*child)
+                    .is_some_and(|task| task.kind == TaskKind::Tend)
+            })
+        });
+        let medicine = if dweller.health < dweller.max_health() / 2 && medicine_stockpiled {
+            ObjectId::Medicine
+        } else {
+            ObjectId::Bandage
+        };
+        match tend_task {
+            None if resting && dweller.health < dweller.max_health() => {
+                let task_entity = commands
+                    .spawn(TaskBundle::new_as_child(
+                        Task::new(pos, TaskKind::Tend, None),
+                        TaskNeeds::Objects(vec![medicine]),
+                    ))
+                    .id();
+                commands.entity(entity).add_child(task_entity);
+            }
+            Some(task_entity) if !resting || dweller.health == dweller.max_health() => {
+                commands.entity(task_entity).despawn();
+            }
+            // The wound or the stock changed, until someone is on the way
+            Some(task_entity) => {
+                if task_index
+                    .get(task_entity)
+                    .is_some_and(|task| task.dweller_id.is_none())
+                    && let Ok(mut task_needs) = q_task_needs.get_mut(task_entity)
+                {
+                    task_needs.set_if_neq(TaskNeeds::Objects(vec![medicine]));
+                }
+            }
+            _ => {}
+        }
+
+        match dweller.state {
+            DwellerState::Active if dweller.health == 0 => {
+                dweller.state = DwellerState::Downed {
+                    bleed_out: BLEED_OUT,
+                };
+                dweller.move_queue.clear();
+                unassign_tasks(&dweller, &mut task_index, &mut q_tasks);
+                transform.rotation = Quat::from_rotation_z(FRAC_PI_2);
+
+                info!("Dweller {} is downed", dweller.name);
+                continue;
+            }
+            DwellerState::Active => {}
+            DwellerState::Downed { .. } if dweller.health >= dweller.max_health() / 2 => {
+                dweller.state = DwellerState::Active;
+                transform.rotation = Quat::IDENTITY;
+
+                info!("Dweller {} got back on their feet", dweller.name);
+            }
+            DwellerState::Downed { bleed_out } => {
+                if resting || carried.contains(&dweller.uuid) {
+                    continue;
+                }
+
+                if bleed_out == 0 {
+                    event_log.push(format!("{} died", dweller.name));
+                    commands.entity(entity).despawn();
+                    died.push(dweller.uuid);
+
+                    // Leave a corpse to bury, with what they had lying around it
+                    let belongings = [
+                        dweller
+                            .object
+                            .map(|object| (object, dweller.object_spoilage)),
+                        dweller.tool.map(|tool| (tool, 0)),
+                        dweller.armor.map(|armor| (armor, 0)),
+                    ];
+                    for (object, spoilage, task_kind) in
+                        std::iter::once((ObjectId::Corpse, 0, TaskKind::Bury)).chain(
+                            belongings
+                                .into_iter()
+                                .flatten()
+                                .map(|(object, spoilage)| (object, spoilage, TaskKind::Pickup)),
+                        )
+                    {
+                        if let Some(drop_pos) =
+                            TilemapData::find_from_center_chunk_size(pos, |pos| {
+                                tilemap_data
+                                    .get(pos)
+                                    .is_some_and(|tile| tile.is_floor_free())
+                                    && !task_index.has_task_at(pos)
+                            })
+                            && let Some(tile) = tilemap_data.get(drop_pos)
+                        {
+                            tilemap_data.set(
+                                drop_pos,
+                                TilePlaced {
+                                    spoilage,
+                                    ..tile.id.with(object)
+                                },
+                            );
+                            commands.spawn(TaskBundle::new(
+                                Task::new(drop_pos, task_kind, None),
+                                TaskNeeds::EmptyHands,
+                            ));
+                        }
+                    }
+                    continue;
+                }
+                dweller.state = DwellerState::Downed {
+                    bleed_out: bleed_out - 1,
+                };
+
+                // Ask for someone to carry them to a bed
+                let rescue_requested = children.is_some_and(|children| {
+                    children.iter().any(|child| {
+                        task_index
+                            .get(child)
+                            .is_some_and(|task| task.kind == TaskKind::Rescue)
+                    })
+                });
+                if !rescue_requested {
+                    let task_entity = commands
+                        .spawn(TaskBundle::new_as_child(
+                            Task::new(pos, TaskKind::Rescue, None),
+                            TaskNeeds::EmptyHands,
+                        ))
+                        .id();
+                    commands.entity(entity).add_child(task_entity);
+                }
+                continue;
+            }
         }
 
-        let pos = transform_to_pos(transform);
+        // If they are not working on something already... (especially an Eat / Sleep task)
+        if task_index.dweller_has_task(dweller.uuid) {
+            continue;
+        }
 
         if dweller.food < NEEDS_MAX / 2 {
-            if let Some(value) = dweller.object.and_then(|object| EAT_VALUES.get(&object)) {
-                dweller.food(*value);
+            if let Some(object) = dweller
+                .object
+                .filter(|object| EAT_VALUES.contains_key(object))
+            {
+                dweller.eat(object, dweller.object_spoilage);
+                dweller.object = None;
+            } else if let Some(food_pos) =
+                TilemapData::find_best_from_center(pos, CHUNK_SIZE / 2, |food_pos| {
+                    food_score(&tilemap_data, pos, food_pos)
+                        .filter(|_| task_index.can_place(food_pos, TaskKind::Eat))
+                })
+            {
+                commands.spawn(TaskBundle::new(
+                    Task::new(food_pos, TaskKind::Eat, Some(dweller.uuid)),
+                    TaskNeeds::Nothing,
+                ));
+            }
+        }
+
+        if dweller.thirst < NEEDS_MAX / 2 {
+            if let Some(object) = dweller
+                .object
+                .filter(|object| DRINK_VALUES.contains_key(object))
+            {
+                dweller.drink(object);
                 dweller.object = None;
             } else if let Some(pos) = TilemapData::find_from_center_chunk_size(pos, |pos| {
                 tilemap_data
                     .get(pos)
-                    .is_some_and(|tile| TaskKind::Eat.is_valid_on_tile(tile))
-                    && !q_tasks
-                        .iter()
-                        .filter(|t| {
-                            !(matches!(t.kind, TaskKind::Pickup | TaskKind::Stockpile)
-                                && t.dweller_id.is_none())
-                        })
-                        .any(|t| t.pos == pos)
+                    .is_some_and(|tile| TaskKind::Drink.is_valid_on_tile(tile))
+                    && task_index.can_place(pos, TaskKind::Drink)
             }) {
                 commands.spawn(TaskBundle::new(
-                    Task::new(pos, TaskKind::Eat, Some(dweller.uuid)),
+                    Task::new(pos, TaskKind::Drink, Some(dweller.uuid)),
                     TaskNeeds::Nothing,
                 ));
             }
@@ -61,7 +292,7 @@ pub fn update_dweller_needs(
                 tilemap_data
                     .get(pos)
                     .is_some_and(|tile| TaskKind::Sleep.is_valid_on_tile(tile))
-                    && !q_tasks.iter().any(|t| t.pos == pos)
+                    && task_index.can_place(pos, TaskKind::Sleep)
             })
         {
             commands.spawn(TaskBundle::new(
@@ -70,4 +301,53 @@ pub fn update_dweller_needs(
             ));
         }
     }
+
+    // Friends and partners mourn the dead
+    for uuid in died {
+        for (_, mut dweller, _, _, _) in &mut q_needs {
+            if matches!(
+                dweller.relation_kind(uuid),
+                Some(RelationKind::Friend | RelationKind::Partner)
+            ) {
+                dweller.think(Thought::Mourning);
+            }
+            if dweller.partner == Some(uuid) {
+                dweller.partner = None;
+            }
+            if dweller.carried == Some(uuid) {
+                dweller.carried = None;
+            }
+            dweller
+                .relations
+                .retain(|relation| relation.dweller != uuid);
+        }
+    }
+}
+
+fn unassign_tasks(dweller: &Dweller, task_index: &mut TaskIndex, q_tasks: &mut Query<&mut Task>) {
+    let entities = task_index
+        .of_dweller(dweller.uuid)
+        .map(|task| task.entity)
+        .collect::<Vec<_>>();
+
+    for entity in entities {
+        if let Ok(mut task) = q_tasks.get_mut(entity) {
+            task.dweller_id = None;
+            task_index.refresh(entity, &task);
+        }
+    }
+}
+
+// Nutrition and pleasure of the food on a tile, minus the walk to get there
+fn food_score(tilemap_data: &TilemapData, pos: IVec3, food_pos: IVec3) -> Option<i32> {
+    let tile = tilemap_data.get(food_pos)?;
+    let object = tile.object?;
+    let value = EAT_VALUES.get(&object)?;
+
+    let thought = Thought::of_food(object, tile.spoilage);
+
+    Some(
+        food_value(*value, tile.spoilage) + thought.map_or(0, Thought::mood) * FOOD_MOOD_WEIGHT
+            - TilemapData::manhattan_distance(pos, food_pos) * FOOD_DISTANCE_COST,
+    )
 }
@@ -5,26 +5,52 @@ use bevy::{
 };
 
 use crate::{
-    actions::*, camera::*, dwellers::*, dwellers_needs::*, mobs::*, save_load::*, sprites::*,
-    state::*, tasks::*, tilemap::*, ui::*,
+    actions::*, camera::*, colony::*, combat::*, dwellers::*, dwellers_needs::*, mobs::*,
+    room_designer::*, save_load::*, social::*, sprites::*, state::*, task_assignment::*,
+    task_index::*, tasks::*, tilemap::*, ui::*,
 };
 
 mod actions;
+mod blueprints;
 mod camera;
+mod colony;
+mod combat;
 mod data;
 mod dwellers;
 mod dwellers_needs;
 mod mobs;
+mod mood;
 mod random_text;
+mod room_designer;
 mod save_load;
+mod social;
 mod sprites;
 mod state;
+mod task_assignment;
+mod task_index;
 mod tasks;
 mod tilemap;
 mod ui;
 mod utils;
+mod world_stats;
 
 fn main() {
+    if std::env::args().nth(1).as_deref() == Some("bench-assignment") {
+        App::new()
+            .add_plugins((MinimalPlugins, LogPlugin::default()))
+            .add_systems(Startup, run_assignment_bench)
+            .run();
+        return;
+    }
+
+    if std::env::args().nth(1).as_deref() == Some("render-world") {
+        App::new()
+            .add_plugins((MinimalPlugins, LogPlugin::default()))
+            .add_systems(Startup, run_world_render)
+            .run();
+        return;
+    }
+
     App::new()
         .add_plugins((
             DefaultPlugins
@@ -51,13 +77,20 @@ fn main() {
         )
         .add_systems(
             Startup,
-            (spawn_camera, spawn_new_terrain, spawn_ui, init_tileset),
+            (
+                spawn_camera,
+                spawn_new_terrain,
+                init_room_designer,
+                spawn_ui,
+                init_tileset,
+            ),
         )
         .add_systems(
             Update,
             (
                 chunks_with_dwellers_is_added,
                 spawn_load_save_ui,
+                update_world_preview.run_if(on_timer(Duration::from_millis(1000))),
                 wait_textures_load,
                 scan_sprites_loaders,
                 init_font,
@@ -73,17 +106,29 @@ fn main() {
                 (
                     // Game UI / "reactive" systems
                     keyboard_current_action,
+                    keyboard_room_orientation,
                     focus_any_dweller,
                     terrain_draw_selection,
+                    update_room_library_ui,
+                    update_room_ghost,
                     update_dwellers_selected,
+                    update_world_stats_ui,
+                    update_clock_ui,
+                    update_combat_log_ui,
+                    update_event_log_ui,
+                    update_family_tree_ui,
                     spawn_dwellers_name,
                     update_dwellers_equipment_sprites,
-                    update_task_needs_preview,
-                    update_task_build_preview,
-                    update_task_workstation_preview,
+                    (
+                        update_task_needs_preview,
+                        update_task_build_preview,
+                        update_task_blocked_preview,
+                        update_task_workstation_preview,
+                    ),
                     update_taking_damage,
                     update_sprite_animation,
                     update_weather,
+                    update_layer_visibility,
                 )
                     .in_set(GameplaySet),
             ),
@@ -95,9 +140,20 @@ fn main() {
                 (update_dwellers, update_mobs, assign_tasks_to_dwellers)
                     .run_if(on_timer(Duration::from_millis(200))),
                 (update_dweller_needs).run_if(on_timer(Duration::from_millis(600))),
+                (update_social).run_if(on_timer(Duration::from_millis(2000))),
+                (update_colony_growth).run_if(on_timer(Duration::from_secs(30))),
                 (dwellers_load_chunks).run_if(on_timer(Duration::from_millis(1000))),
                 (update_terrain).run_if(on_timer(Duration::from_millis(800))),
-                update_dwellers_movement,
+                (update_fluids).run_if(on_timer(Duration::from_millis(250))),
+                (update_temperatures).run_if(on_timer(Duration::from_millis(500))),
+                (update_fog_of_war).run_if(on_timer(Duration::from_millis(500))),
+                (update_fires, update_weather_events)
+                    .run_if(on_timer(Duration::from_millis(1000))),
+                (update_spoilage).run_if(on_timer(Duration::from_millis(5000))),
+                (
+                    update_dwellers_movement,
+                    update_carried_dwellers.after(update_dwellers_movement),
+                ),
                 update_mobs_movement,
                 update_unreachable_tasks,
                 update_unreachable_pathfinding_tasks.run_if(on_timer(Duration::from_millis(5000))),
@@ -107,9 +163,19 @@ fn main() {
                     update_hostile_mobs,
                 )
                     .run_if(on_timer(Duration::from_millis(1000))),
-                event_task_completion,
+                (
+                    event_task_completion,
+                    update_tasks_layer.after(event_task_completion),
+                ),
                 manage_chunks,
-                update_tilemap_from_data.after(manage_chunks),
+                (
+                    track_light_changes,
+                    update_light_levels
+                        .after(track_light_changes)
+                        .run_if(on_timer(Duration::from_millis(500))),
+                )
+                    .after(manage_chunks),
+                update_tilemap_from_data.after(update_light_levels),
             )
                 .in_set(GameplaySet),
         )
@@ -117,10 +183,17 @@ fn main() {
         .add_observer(terrain_pointer_up)
         .add_observer(observe_open_workstation_ui)
         .add_observer(load_game)
+        .add_observer(new_game)
         .add_observer(save_resources)
         .init_state::<GameState>()
         .init_resource::<CameraControl>()
         .init_resource::<CurrentAction>()
         .init_resource::<DwellersSelected>()
+        .init_resource::<TaskIndex>()
+        .init_resource::<TaskAssigner>()
+        .init_resource::<Temperatures>()
+        .init_resource::<LightLevels>()
+        .init_resource::<CombatLog>()
+        .init_resource::<EventLog>()
         .run();
 }
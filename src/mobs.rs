@@ -3,7 +3,8 @@ use pathfinding::directed::astar::astar;
 use rand::prelude::*;
 
 use crate::{
-    CHUNK_SIZE, SaveScoped, SpriteLoader, TILE_SIZE, TilemapData,
+    CHUNK_SIZE, Layer, SaveScoped, SpriteLoader, TILE_SIZE, TilemapData, Weather,
+    combat::{CombatLog, MOB_ACCURACY, mitigate, roll_hit},
     data::{MobId, ObjectId},
     dwellers::Dweller,
     sprites::TakingDamage,
@@ -14,7 +15,7 @@ const Z_INDEX: f32 = 11.0;
 const HOSTILE_MOBS_DETECTION_TILE_RADIUS: i32 = 20;
 
 #[derive(Message)]
-pub struct SpawnMobsOnChunk(pub IVec2);
+pub struct SpawnMobsOnChunk(pub IVec3);
 
 pub struct MobData {
     filename: &'static str,
@@ -22,6 +23,8 @@ pub struct MobData {
     pub loot: ObjectId,
     health: u32,
     attack: u32,
+    attack_cooldown: u32, // update_hostile_mobs ticks between attacks
+    pub dodge: f64,
 }
 
 impl MobData {
@@ -30,6 +33,8 @@ impl MobData {
         health: u32,
         speed: f32,
         attack: u32,
+        attack_cooldown: u32,
+        dodge: f64,
         loot: ObjectId,
     ) -> Self {
         MobData {
@@ -38,6 +43,8 @@ impl MobData {
             loot,
             health,
             attack,
+            attack_cooldown,
+            dodge,
         }
     }
 
@@ -52,11 +59,12 @@ impl MobData {
 
 #[derive(Component, Reflect, Default)]
 #[reflect(Component, Default)]
-#[require(Name::new("mob"), SaveScoped)]
+#[require(Name::new("mob"), SaveScoped, Layer)]
 pub struct Mob {
     pub id: MobId,
-    move_queue: Vec<IVec2>, // next move is at the end
+    move_queue: Vec<IVec3>, // next move is at the end
     pub health: u32,
+    attack_cooldown: u32, // ticks
 }
 
 impl Mob {
@@ -65,14 +73,15 @@ impl Mob {
             id,
             move_queue: Vec::new(),
             health: id.data().health,
+            attack_cooldown: 0,
         }
     }
 
-    pub fn pathfind(&mut self, start_pos: IVec2, goal_pos: IVec2, tilemap_data: &TilemapData) {
+    pub fn pathfind(&mut self, start_pos: IVec3, goal_pos: IVec3, tilemap_data: &TilemapData) {
         let path = astar(
             &start_pos,
             tilemap_data.astar_successors(),
-            |p| (p.x - goal_pos.x).abs() + (p.y - goal_pos.y).abs(),
+            |p| TilemapData::manhattan_distance(*p, goal_pos),
             |p| *p == goal_pos,
         )
         .map(|(mut path, _)| {
@@ -98,10 +107,11 @@ pub struct MobBundle {
     mob: Mob,
     sprite: SpriteLoader,
     transform: Transform,
+    layer: Layer,
 }
 
 impl MobBundle {
-    pub fn new(id: MobId, pos: IVec2) -> Self {
+    pub fn new(id: MobId, pos: IVec3) -> Self {
         MobBundle {
             mob: Mob::new(id),
             sprite: SpriteLoader {
@@ -112,6 +122,7 @@ impl MobBundle {
                 pos.y as f32 * TILE_SIZE,
                 Z_INDEX,
             ),
+            layer: Layer(pos.z),
         }
     }
 }
@@ -135,7 +146,7 @@ pub fn spawn_mobs(
             |pos| {
                 for dx in -1..=1 {
                     for dy in -1..=1 {
-                        let neigh_pos = pos + IVec2::new(dx, dy);
+                        let neigh_pos = pos + IVec3::new(dx, dy, 0);
 
                         let Some(tile) = tilemap_data.get(neigh_pos) else {
                             return false;
@@ -153,22 +164,22 @@ pub fn spawn_mobs(
             return;
         };
 
-        let nb_sheeps = rng.random_range(1..=7);
-        let nb_boars = rng.random_range(1..=5);
+        let biome = tilemap_data.biome(pos).unwrap_or_default();
 
-        for _ in 0..nb_sheeps {
-            commands.spawn(MobBundle::new(MobId::Sheep, pos));
-        }
-
-        for _ in 0..nb_boars {
-            commands.spawn(MobBundle::new(MobId::Boar, pos));
+        for (mob_id, max_count) in biome.data().mobs {
+            for _ in 0..rng.random_range(1..=*max_count) {
+                commands.spawn(MobBundle::new(*mob_id, pos));
+            }
         }
     }
 }
 
-pub fn update_mobs(tilemap_data: Res<TilemapData>, mut q_mobs: Query<(&mut Mob, &Transform)>) {
+pub fn update_mobs(
+    tilemap_data: Res<TilemapData>,
+    mut q_mobs: Query<(&mut Mob, &Transform, &Layer)>,
+) {
     let mut rng = rand::rng();
-    for (mut mob, transform) in &mut q_mobs {
+    for (mut mob, transform, layer) in &mut q_mobs {
         // If already moving, continue
         if !mob.move_queue.is_empty() {
             continue;
@@ -176,7 +187,7 @@ pub fn update_mobs(tilemap_data: Res<TilemapData>, mut q_mobs: Query<(&mut Mob,
 
         // Wander around
         if rng.random_bool(0.2) {
-            let pos = transform_to_pos(transform);
+            let pos = transform_to_pos(transform, layer);
             let directions = tilemap_data.non_blocking_neighbours_pos(pos, true);
 
             if let Some(direction) = directions.choose(&mut rng) {
@@ -188,9 +199,11 @@ pub fn update_mobs(tilemap_data: Res<TilemapData>, mut q_mobs: Query<(&mut Mob,
 
 pub fn update_mobs_movement(
     time: Res<Time>,
-    mut q_mobs: Query<(&mut Mob, &mut Transform, &mut Sprite)>,
+    weather: If<Res<Weather>>,
+    tilemap_data: Res<TilemapData>,
+    mut q_mobs: Query<(&mut Mob, &mut Transform, &mut Layer, &mut Sprite)>,
 ) {
-    for (mut mob, mut transform, mut sprite) in &mut q_mobs {
+    for (mut mob, mut transform, mut layer, mut sprite) in &mut q_mobs {
         // Move to next position in queue
 
         if let Some(next_move) = mob.move_queue.last() {
@@ -201,11 +214,18 @@ pub fn update_mobs_movement(
 
             let direction = target - transform.translation.truncate();
 
-            let speed = mob.id.data().speed;
+            let speed = mob.id.data().speed
+                * weather
+                    .state_at(&tilemap_data, transform_to_pos(&transform, &layer))
+                    .speed_ratio();
+
+            // Stairs lead straight to the layer above or below
+            let changes_layer = next_move.z != layer.0;
 
-            if direction.length() < speed * time.delta_secs() {
+            if direction.length() < speed * time.delta_secs() || changes_layer {
                 transform.translation.x = target.x;
                 transform.translation.y = target.y;
+                layer.set_if_neq(Layer(next_move.z));
                 mob.move_queue.pop();
             } else {
                 let dir = direction.normalize();
@@ -221,37 +241,60 @@ pub fn update_mobs_movement(
 pub fn update_hostile_mobs(
     mut commands: Commands,
     tilemap_data: Res<TilemapData>,
-    mut q_mobs: Query<(&mut Mob, &Transform)>,
-    mut q_dwellers: Query<(Entity, &mut Dweller, &Transform)>,
+    mut q_mobs: Query<(&mut Mob, &Transform, &Layer)>,
+    mut q_dwellers: Query<(Entity, &mut Dweller, &Transform, &Layer)>,
+    mut combat_log: ResMut<CombatLog>,
 ) {
     let mut rng = rand::rng();
-    for (mut mob, transform) in &mut q_mobs {
-        // Hostile mobs seek closest dweller within detection radius
+    for (mut mob, transform, layer) in &mut q_mobs {
+        // Hostile mobs seek closest dweller within detection radius, on their layer
         let mob_data = mob.id.data();
+        if mob.attack_cooldown > 0 {
+            mob.attack_cooldown -= 1;
+        }
+
         if mob_data.is_hostile() {
-            let pos = transform_to_pos(transform);
+            let pos = transform_to_pos(transform, layer);
 
             let target_dweller = q_dwellers
                 .iter_mut()
-                .filter_map(|(entity_dweller, dweller, dweller_transform)| {
-                    let dweller_pos = transform_to_pos(dweller_transform);
-                    let tile_distance_squared = (dweller_pos - pos).length_squared();
-
-                    if tile_distance_squared <= HOSTILE_MOBS_DETECTION_TILE_RADIUS.pow(2) {
-                        Some((entity_dweller, dweller, dweller_pos, tile_distance_squared))
-                    } else {
-                        None
-                    }
-                })
+                .filter(|(_, dweller, _, _)| !dweller.is_downed())
+                .filter_map(
+                    |(entity_dweller, dweller, dweller_transform, dweller_layer)| {
+                        let dweller_pos = transform_to_pos(dweller_transform, dweller_layer);
+                        let tile_distance_squared = (dweller_pos - pos).length_squared();
+
+                        if dweller_pos.z == pos.z
+                            && tile_distance_squared <= HOSTILE_MOBS_DETECTION_TILE_RADIUS.pow(2)
+                        {
+                            Some((entity_dweller, dweller, dweller_pos, tile_distance_squared))
+                        } else {
+                            None
+                        }
+                    },
+                )
                 .min_by_key(|(_, _, _, tile_distance_squared)| *tile_distance_squared);
 
             if let Some((entity_dweller, mut dweller, dweller_pos, tile_distance_squared)) =
                 target_dweller
             {
-                // Attack if close enough
+                // Attack if close enough, then wait for the cooldown
                 if tile_distance_squared <= 1 {
-                    dweller.health(-(mob_data.attack as i32));
-                    commands.entity(entity_dweller).insert(TakingDamage::new());
+                    if mob.attack_cooldown > 0 {
+                        continue;
+                    }
+                    mob.attack_cooldown = mob_data.attack_cooldown;
+
+                    if roll_hit(&mut rng, MOB_ACCURACY, dweller.dodge()) {
+                        let damage =
+                            mitigate(&mut rng, mob_data.attack, dweller.armor_mitigation());
+                        dweller.health(-(damage as i32));
+                        commands.entity(entity_dweller).insert(TakingDamage::new());
+
+                        combat_log.push(format!("{:?} hits {} for {damage}", mob.id, dweller.name));
+                    } else {
+                        combat_log.push(format!("{:?} misses {}", mob.id, dweller.name));
+                    }
                 } else {
                     // Else pathfind towards dweller
                     let &target_pos = tilemap_data
@@ -0,0 +1,136 @@
+use bevy::prelude::*;
+
+use crate::{
+    SPOILAGE_MAX, TilemapData,
+    data::{BEAUTY_VALUES, EAT_VALUES, ObjectId, SLEEP_VALUES},
+    has_line_of_sight,
+};
+
+pub const MOOD_MAX: i32 = 100;
+pub const MOOD_BASE: i32 = 50;
+// Below this, dwellers work slower
+pub const MOOD_LOW: i32 = 30;
+pub const MOOD_LOW_WORK_SPEED: f32 = 0.7;
+// Below this, dwellers may have a mental break and refuse to work
+pub const MOOD_BREAK: i32 = 15;
+pub const BREAK_CHANCE: f64 = 0.02;
+pub const BREAK_DURATION: u32 = 50; // needs ticks
+
+// Needs and injuries weigh on the mood while they last
+pub const MOOD_HUNGRY: i32 = -10;
+pub const MOOD_TIRED: i32 = -10;
+pub const MOOD_THIRSTY: i32 = -10;
+pub const MOOD_PER_WOUND: i32 = -3;
+
+const BEAUTY_RADIUS: i32 = 3;
+const BEAUTY_MAX: i32 = 15;
+const CORPSE_RADIUS: i32 = 5;
+
+// EAT_VALUES of cooked and proper meals, below RAW_FOOD it is a raw ingredient
+const COOKED_MEAL: i32 = 800;
+const FINE_MEAL: i32 = 500;
+const RAW_FOOD: i32 = 100;
+// Food past this spoilage is unpleasant to eat
+const SPOILED: u8 = SPOILAGE_MAX / 2;
+// SLEEP_VALUES of proper beds, below BAD_BED it is just furniture
+const GOOD_BED: i32 = 100;
+const BAD_BED: i32 = 50;
+
+#[derive(PartialEq, Eq, Clone, Copy, Reflect, Debug)]
+pub enum Thought {
+    AteCookedMeal,
+    AteFineMeal,
+    AteRawFood,
+    DrankHydromel,
+    SleptInBed,
+    SleptBadly,
+    SawCorpse,
+    AteSpoiledFood,
+    NiceChat,
+    Argument,
+    Mourning,
+}
+
+impl Thought {
+    pub fn mood(self) -> i32 {
+        match self {
+            Thought::AteCookedMeal => 14,
+            Thought::AteFineMeal => 8,
+            Thought::AteRawFood => -6,
+            Thought::DrankHydromel => 12,
+            Thought::SleptInBed => 6,
+            Thought::SleptBadly => -8,
+            Thought::SawCorpse => -15,
+            Thought::AteSpoiledFood => -10,
+            Thought::NiceChat => 3,
+            Thought::Argument => -5,
+            Thought::Mourning => -15,
+        }
+    }
+
+    // Needs ticks before the thought fades away
+    pub fn duration(self) -> u32 {
+        match self {
+            Thought::AteFineMeal | Thought::AteRawFood | Thought::AteSpoiledFood => 200,
+            Thought::AteCookedMeal | Thought::DrankHydromel | Thought::SawCorpse => 300,
+            Thought::SleptInBed | Thought::SleptBadly => 400,
+            Thought::NiceChat => 100,
+            Thought::Argument => 150,
+            Thought::Mourning => 600,
+        }
+    }
+
+    pub fn of_food(object: ObjectId, spoilage: u8) -> Option<Self> {
+        if spoilage >= SPOILED {
+            return Some(Thought::AteSpoiledFood);
+        }
+
+        match *EAT_VALUES.get(&object)? {
+            value if value >= COOKED_MEAL => Some(Thought::AteCookedMeal),
+            value if value >= FINE_MEAL => Some(Thought::AteFineMeal),
+            value if value < RAW_FOOD => Some(Thought::AteRawFood),
+            _ => None,
+        }
+    }
+
+    pub fn of_drink(object: ObjectId) -> Option<Self> {
+        (object == ObjectId::Hydromel).then_some(Thought::DrankHydromel)
+    }
+
+    pub fn of_sleep(object: ObjectId) -> Option<Self> {
+        match *SLEEP_VALUES.get(&object)? {
+            value if value >= GOOD_BED => Some(Thought::SleptInBed),
+            value if value < BAD_BED => Some(Thought::SleptBadly),
+            _ => None,
+        }
+    }
+}
+
+// Beauty of the objects around a position
+pub fn beauty_around(tilemap_data: &TilemapData, pos: IVec3) -> i32 {
+    let mut beauty = 0;
+    for dx in -BEAUTY_RADIUS..=BEAUTY_RADIUS {
+        for dy in -BEAUTY_RADIUS..=BEAUTY_RADIUS {
+            beauty += tilemap_data
+                .get(pos + IVec3::new(dx, dy, 0))
+                .and_then(|tile| tile.object)
+                .and_then(|object| BEAUTY_VALUES.get(&object))
+                .copied()
+                .unwrap_or_default();
+        }
+    }
+
+    beauty.clamp(-BEAUTY_MAX, BEAUTY_MAX)
+}
+
+pub fn sees_corpse(tilemap_data: &TilemapData, pos: IVec3) -> bool {
+    (-CORPSE_RADIUS..=CORPSE_RADIUS).any(|dx| {
+        (-CORPSE_RADIUS..=CORPSE_RADIUS).any(|dy| {
+            let corpse_pos = pos + IVec3::new(dx, dy, 0);
+            tilemap_data
+                .get(corpse_pos)
+                .is_some_and(|tile| tile.object == Some(ObjectId::Corpse))
+                && has_line_of_sight(tilemap_data, pos, corpse_pos)
+        })
+    })
+}
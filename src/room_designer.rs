@@ -0,0 +1,306 @@
+use bevy::{prelude::*, sprite::Anchor};
+
+use crate::{
+    ActionKind, SAVE_DIR, StructureData, TILE_SIZE, TilemapData,
+    actions::CurrentAction,
+    blueprints::Blueprint,
+    extract_ok, extract_some,
+    task_index::TaskIndex,
+    ui::{UiButton, get_observer_action_button},
+    utils::write_to_file,
+};
+
+const Z_INDEX: f32 = 20.0;
+
+const DEFAULT_LIBRARY: &str = "
+[Bedroom]
+=====
+=b-b=
+=---=
+==Ď==
+
+[Kitchen]
+======
+=f--t=
+=--ss=
+==Ď===
+
+[Smithy]
+=======
+=F-A-G=
+=-----=
+===Ď===
+";
+
+pub struct RoomPrefab {
+    pub name: String,
+    pub structure: StructureData,
+}
+
+// Prefab library shared by all saves, with the orientation used to stamp them
+#[derive(Resource)]
+pub struct RoomDesigner {
+    pub library: Vec<RoomPrefab>,
+    pub rotation: u8,
+    pub flipped: bool,
+}
+
+impl RoomDesigner {
+    fn library_path() -> String {
+        format!("assets/{SAVE_DIR}/room_library.txt")
+    }
+
+    // Each prefab is a [Name] line followed by its ASCII layout (see ASCII_TILES)
+    fn parse_library(content: &str) -> Vec<RoomPrefab> {
+        let mut prefabs = vec![];
+        let mut current: Option<(String, String)> = None;
+
+        let mut push = |current: Option<(String, String)>| {
+            if let Some((name, ascii)) = current {
+                prefabs.push(RoomPrefab {
+                    name,
+                    structure: StructureData::from_ascii(&ascii, vec![]),
+                });
+            }
+        };
+
+        for line in content.lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            if let Some(name) = line
+                .trim()
+                .strip_prefix('[')
+                .and_then(|line| line.strip_suffix(']'))
+            {
+                push(current.take());
+                current = Some((name.to_string(), String::new()));
+            } else if let Some((_, ascii)) = &mut current {
+                ascii.push_str(line);
+                ascii.push('\n');
+            }
+        }
+        push(current);
+
+        prefabs
+    }
+
+    pub fn load() -> Self {
+        let library = match std::fs::read_to_string(Self::library_path()) {
+            Ok(content) => Self::parse_library(&content),
+            Err(_) => {
+                debug!("No room library found, using default prefabs");
+                Self::parse_library(DEFAULT_LIBRARY)
+            }
+        };
+
+        Self {
+            library,
+            rotation: 0,
+            flipped: false,
+        }
+    }
+
+    pub fn save(&self) {
+        let content = self
+            .library
+            .iter()
+            .map(|prefab| format!("[{}]\n{}\n", prefab.name, prefab.structure.to_ascii()))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        write_to_file(Self::library_path(), content);
+    }
+
+    pub fn oriented(&self, index: usize) -> Option<StructureData> {
+        self.library
+            .get(index)
+            .map(|prefab| prefab.structure.oriented(self.rotation, self.flipped))
+    }
+
+    // Store the terrain in the rectangle as a new prefab, tiles that can't be saved
+    // in the library are left untouched when placing it
+    pub fn capture(&mut self, pos_min: IVec3, pos_max: IVec3, tilemap_data: &TilemapData) {
+        let tiles = (pos_min.y..=pos_max.y)
+            .map(|y| {
+                (pos_min.x..=pos_max.x)
+                    .map(|x| {
+                        tilemap_data
+                            .get(IVec3::new(x, y, pos_min.z))
+                            .and_then(StructureData::ascii_tile)
+                            .map(|(_, tile)| tile)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let name = format!("Room {}", self.library.len() + 1);
+        debug!("{name} saved to the room library");
+
+        self.library.push(RoomPrefab {
+            name,
+            structure: StructureData::new(tiles, vec![]),
+        });
+        self.save();
+    }
+}
+
+pub fn init_room_designer(mut commands: Commands) {
+    commands.insert_resource(RoomDesigner::load());
+}
+
+pub fn keyboard_room_orientation(
+    current_action: Res<CurrentAction>,
+    mut room_designer: ResMut<RoomDesigner>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+) {
+    if !matches!(current_action.kind, ActionKind::PlaceRoom(_)) {
+        return;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::KeyR) {
+        room_designer.rotation = (room_designer.rotation + 1) % 4;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::KeyF) {
+        room_designer.flipped = !room_designer.flipped;
+    }
+}
+
+pub fn place_room(
+    commands: &mut Commands,
+    room_designer: &RoomDesigner,
+    index: usize,
+    origin: IVec3,
+    tilemap_data: &TilemapData,
+    task_index: &TaskIndex,
+) {
+    let structure = extract_some!(room_designer.oriented(index));
+    let blueprint = Blueprint::from_structure(&structure, origin, tilemap_data);
+
+    let count = blueprint.spawn(commands, task_index);
+    debug!("Room placed at {origin}: {count} tasks");
+}
+
+#[derive(Component)]
+pub struct RoomLibraryUi;
+
+pub fn update_room_library_ui(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    room_designer: Res<RoomDesigner>,
+    q_room_library_ui: Query<Entity, With<RoomLibraryUi>>,
+    mut prefabs_count: Local<Option<usize>>,
+) {
+    // Rebuild the buttons only when prefabs are added
+    if *prefabs_count == Some(room_designer.library.len()) {
+        return;
+    }
+
+    let room_library_ui = extract_ok!(q_room_library_ui.single());
+    *prefabs_count = Some(room_designer.library.len());
+
+    commands
+        .entity(room_library_ui)
+        .despawn_related::<Children>()
+        .with_children(|c| {
+            for (index, prefab) in room_designer.library.iter().enumerate() {
+                c.spawn(UiButton)
+                    .with_child(Text::new(prefab.name.clone()))
+                    .observe(get_observer_action_button(ActionKind::PlaceRoom(index)));
+            }
+
+            c.spawn(UiButton)
+                .with_child(Text::new("Save Room"))
+                .with_child(ImageNode::new(asset_server.load("tasks/build.png")))
+                .observe(get_observer_action_button(ActionKind::SaveRoom));
+        });
+}
+
+#[derive(Component)]
+pub struct RoomGhost;
+
+// Transparent preview of the prefab under the cursor
+pub fn update_room_ghost(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    current_action: Res<CurrentAction>,
+    room_designer: Res<RoomDesigner>,
+    q_windows: Query<&Window>,
+    q_camera: Query<(&Camera, &GlobalTransform)>,
+    mut q_ghost: Query<(Entity, &mut Transform), With<RoomGhost>>,
+) {
+    let ActionKind::PlaceRoom(index) = current_action.kind else {
+        for (entity, _) in &q_ghost {
+            commands.entity(entity).despawn();
+        }
+        return;
+    };
+
+    let window = extract_ok!(q_windows.single());
+    let (camera, camera_transform) = extract_ok!(q_camera.single());
+    let cursor_translation = window
+        .cursor_position()
+        .and_then(|cursor_position| {
+            camera
+                .viewport_to_world_2d(camera_transform, cursor_position)
+                .ok()
+        })
+        .map(|world_position| ((world_position / TILE_SIZE).floor() * TILE_SIZE).extend(Z_INDEX));
+
+    // Respawn the ghost when the prefab or its orientation changes
+    if current_action.is_changed() || room_designer.is_changed() {
+        for (entity, _) in &q_ghost {
+            commands.entity(entity).despawn();
+        }
+
+        let structure = extract_some!(room_designer.oriented(index));
+
+        commands
+            .spawn((
+                RoomGhost,
+                Transform::from_translation(cursor_translation.unwrap_or_default()),
+            ))
+            .with_children(|c| {
+                for y in 0..structure.y_size() {
+                    for x in 0..structure.x_size() {
+                        let Some(tile) = structure.get_tile(x, y) else {
+                            continue;
+                        };
+
+                        let translation = (Vec2::new(x as f32, y as f32) * TILE_SIZE).extend(0.);
+
+                        c.spawn((
+                            Sprite {
+                                image: asset_server.load(tile.id.data().sprite_path()),
+                                color: Color::WHITE.with_alpha(0.4),
+                                ..default()
+                            },
+                            Anchor::BOTTOM_LEFT,
+                            Transform::from_translation(translation),
+                        ));
+
+                        if let Some(object) = tile.object {
+                            c.spawn((
+                                Sprite {
+                                    image: asset_server.load(object.data().sprite_path()),
+                                    color: Color::WHITE.with_alpha(0.4),
+                                    ..default()
+                                },
+                                Anchor::BOTTOM_LEFT,
+                                Transform::from_translation(translation + Vec3::Z),
+                            ));
+                        }
+                    }
+                }
+            });
+
+        return;
+    }
+
+    let cursor_translation = extract_some!(cursor_translation);
+    for (_, mut transform) in &mut q_ghost {
+        transform.translation = cursor_translation;
+    }
+}
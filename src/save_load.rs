@@ -1,12 +1,15 @@
 use bevy::prelude::*;
 
 use crate::{
-    GameState, init_tilemap,
-    tilemap::{ChunksWithDwellers, Weather},
+    GameState, LoadChunk, SpawnDwellersOnChunk, init_tilemap,
+    tilemap::{ChunksWithDwellers, Weather, WorldGenSettings},
     utils::write_to_file,
+    world_stats::WorldStats,
 };
 
 pub const SAVE_DIR: &str = "saves";
+// Bumped whenever the layout of saved tiles (TilePlaced) changes, other versions are not loaded
+pub const SAVE_VERSION: u32 = 6;
 
 #[derive(Resource, Reflect, Default)]
 #[reflect(Resource, Default)]
@@ -21,14 +24,36 @@ impl SaveName {
         format!("assets/{}/{}", SAVE_DIR, self.0)
     }
 
-    pub fn chunk_paths(&self, chunk_pos: IVec2) -> (String, String) {
-        let base = format!("{}/{}_{}", self.folder(), chunk_pos.x, chunk_pos.y);
+    pub fn chunk_paths(&self, chunk_pos: IVec3) -> (String, String) {
+        let base = format!(
+            "{}/{}_{}_{}",
+            self.folder(),
+            chunk_pos.x,
+            chunk_pos.y,
+            chunk_pos.z
+        );
         (format!("{base}.bin"), format!("{base}.ron"))
     }
 
     pub fn resources_path(&self) -> String {
         format!("{}/resources.ron", self.folder())
     }
+
+    fn version_path(&self) -> String {
+        format!("{}/version", self.folder())
+    }
+
+    // Saves from before versioning have no version file, they are version 0
+    pub fn version(&self) -> u32 {
+        std::fs::read_to_string(self.version_path())
+            .ok()
+            .and_then(|version| version.trim().parse().ok())
+            .unwrap_or(0)
+    }
+
+    pub fn write_version(&self) {
+        write_to_file(self.version_path(), SAVE_VERSION.to_string());
+    }
 }
 
 #[derive(Event)]
@@ -37,6 +62,12 @@ pub struct SaveResources;
 #[derive(Event)]
 pub struct LoadGame(pub String);
 
+#[derive(Event)]
+pub struct NewGame {
+    pub name: String,
+    pub settings: WorldGenSettings,
+}
+
 #[derive(Component, Default)]
 pub struct SaveScoped;
 
@@ -56,6 +87,8 @@ pub fn save_resources(
         .deny_all_resources()
         .allow_resource::<Weather>()
         .allow_resource::<ChunksWithDwellers>()
+        .allow_resource::<WorldStats>()
+        .allow_resource::<WorldGenSettings>()
         .extract_resources()
         .build();
 
@@ -63,6 +96,7 @@ pub fn save_resources(
         Ok(serialized) => {
             // Save tasks & entities with Bevy reflection
             write_to_file(save_name.resources_path(), serialized.as_bytes());
+            save_name.write_version();
         }
         Err(e) => {
             error!("Error while serializing the scene: {e:?}");
@@ -86,14 +120,25 @@ pub fn load_game(
 ) {
     info!("Loading game: {}", load_game.0);
 
+    let save_name = SaveName(load_game.0.clone());
+    let version = save_name.version();
+    if version != SAVE_VERSION {
+        error!(
+            "Can't load {}: save format version {version}, expected {SAVE_VERSION}",
+            load_game.0
+        );
+        return;
+    }
+
     // Despawn current scene
     for save_scoped in q_save_scoped.iter() {
         commands.entity(save_scoped).despawn();
     }
     commands.remove_resource::<ChunksWithDwellers>();
     commands.remove_resource::<Weather>();
-
-    let save_name = SaveName(load_game.0.clone());
+    // Replaced by the saved ones, if any
+    commands.insert_resource(WorldStats::default());
+    commands.insert_resource(WorldGenSettings::load());
 
     // Spawn resources from ron file
     let resources_path = save_name
@@ -107,3 +152,29 @@ pub fn load_game(
 
     next_state.set(GameState::Running);
 }
+
+pub fn new_game(
+    new_game: On<NewGame>,
+    mut commands: Commands,
+    q_save_scoped: Query<Entity, With<SaveScoped>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    info!("New game: {}", new_game.name);
+
+    // Despawn current scene
+    for save_scoped in q_save_scoped.iter() {
+        commands.entity(save_scoped).despawn();
+    }
+
+    let save_name = SaveName(new_game.name.clone());
+    commands.insert_resource(Weather::new(save_name.seed()));
+    commands.insert_resource(ChunksWithDwellers::default());
+    commands.insert_resource(WorldStats::default());
+    commands.insert_resource(new_game.settings.clone());
+    init_tilemap(&mut commands, save_name);
+
+    commands.write_message(LoadChunk(IVec3::ZERO));
+    commands.write_message(SpawnDwellersOnChunk::founders(IVec3::ZERO));
+
+    next_state.set(GameState::Running);
+}
@@ -0,0 +1,162 @@
+use std::collections::VecDeque;
+
+use bevy::{platform::collections::HashSet, prelude::*};
+use rand::prelude::*;
+use uuid::Uuid;
+
+use crate::{
+    Layer, TilemapData, data::ObjectId, dwellers::Dweller, mood::Thought, task_index::TaskIndex,
+    utils::transform_to_pos,
+};
+
+const EVENT_LOG_SIZE: usize = 8;
+
+pub const OPINION_MAX: i32 = 100;
+pub const FRIEND_OPINION: i32 = 30;
+pub const RIVAL_OPINION: i32 = -30;
+const PARTNER_OPINION: i32 = 75;
+const PARTNER_CHANCE: f64 = 0.2;
+
+const CHAT_RADIUS: i32 = 2;
+const CHAT_CHANCE: f64 = 0.1;
+// Sharing a table makes chats more likely
+const TABLE_CHAT_CHANCE: f64 = 0.4;
+const CHAT_SWING: i32 = 4;
+
+// Relationships weigh on the mood
+pub const MOOD_PARTNER: i32 = 8;
+pub const MOOD_PER_FRIEND: i32 = 2;
+pub const MOOD_PER_RIVAL: i32 = -3;
+pub const MOOD_RELATIONS_MAX: i32 = 6;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum RelationKind {
+    Friend,
+    Rival,
+    Partner,
+}
+
+// Opinion of another dweller, their name is kept for the family tree and the logs
+#[derive(Reflect, Clone, Debug)]
+pub struct Relation {
+    pub dweller: Uuid,
+    pub name: String,
+    pub opinion: i32,
+}
+
+// Some dwellers get along better than others, the same way both ways
+fn compatibility(a: Uuid, b: Uuid) -> i32 {
+    ((a.as_u128() ^ b.as_u128()) % 5) as i32 - 2
+}
+
+// Notable interactions, shown in the corner of the screen
+#[derive(Resource, Default)]
+pub struct EventLog(pub VecDeque<String>);
+
+impl EventLog {
+    pub fn push(&mut self, entry: String) {
+        info!("Event: {entry}");
+
+        if self.0.len() == EVENT_LOG_SIZE {
+            self.0.pop_front();
+        }
+        self.0.push_back(entry);
+    }
+}
+
+// Idle dwellers near each other, or sharing a table, chat and grow opinions of each other
+pub fn update_social(
+    mut q_dwellers: Query<(Entity, &mut Dweller, &Transform, &Layer)>,
+    tilemap_data: Res<TilemapData>,
+    task_index: Res<TaskIndex>,
+    mut event_log: ResMut<EventLog>,
+) {
+    let mut rng = rand::rng();
+
+    let candidates = q_dwellers
+        .iter()
+        .filter(|(_, dweller, _, _)| !dweller.is_downed() && !dweller.is_breaking())
+        .filter_map(|(entity, dweller, transform, layer)| {
+            let pos = transform_to_pos(transform, layer);
+            let at_table = tilemap_data
+                .neighbours(pos)
+                .iter()
+                .any(|(_, tile)| tile.object == Some(ObjectId::Table));
+
+            (at_table || !task_index.dweller_has_task(dweller.uuid))
+                .then_some((entity, pos, at_table))
+        })
+        .collect::<Vec<_>>();
+
+    // One chat per dweller at a time
+    let mut chatting = HashSet::new();
+    let mut chats = vec![];
+    for (i, (entity_a, pos_a, at_table_a)) in candidates.iter().enumerate() {
+        for (entity_b, pos_b, at_table_b) in &candidates[i + 1..] {
+            if chatting.contains(entity_a) || chatting.contains(entity_b) {
+                continue;
+            }
+            if pos_a.z != pos_b.z || pos_a.distance_squared(*pos_b) > CHAT_RADIUS.pow(2) {
+                continue;
+            }
+
+            let chance = if *at_table_a && *at_table_b {
+                TABLE_CHAT_CHANCE
+            } else {
+                CHAT_CHANCE
+            };
+            if rng.random_bool(chance) {
+                chatting.insert(*entity_a);
+                chatting.insert(*entity_b);
+                chats.push([*entity_a, *entity_b]);
+            }
+        }
+    }
+
+    for chat in chats {
+        let Ok([(_, mut a, _, _), (_, mut b, _, _)]) = q_dwellers.get_many_mut(chat) else {
+            continue;
+        };
+
+        let kinds_before = (a.relation_kind(b.uuid), b.relation_kind(a.uuid));
+
+        let delta = rng.random_range(-CHAT_SWING..=CHAT_SWING) + compatibility(a.uuid, b.uuid);
+        let (uuid_a, name_a) = (a.uuid, a.name.clone());
+        let (uuid_b, name_b) = (b.uuid, b.name.clone());
+        a.change_opinion(uuid_b, &name_b, delta);
+        b.change_opinion(uuid_a, &name_a, delta);
+
+        let thought = if delta >= 0 {
+            Thought::NiceChat
+        } else {
+            Thought::Argument
+        };
+        a.think(thought);
+        b.think(thought);
+
+        if a.partner.is_none()
+            && b.partner.is_none()
+            && a.opinion_of(uuid_b) >= PARTNER_OPINION
+            && b.opinion_of(uuid_a) >= PARTNER_OPINION
+            && rng.random_bool(PARTNER_CHANCE)
+        {
+            a.partner = Some(uuid_b);
+            b.partner = Some(uuid_a);
+            event_log.push(format!("{name_a} and {name_b} are now partners"));
+            continue;
+        }
+
+        let kinds_after = (a.relation_kind(uuid_b), b.relation_kind(uuid_a));
+        if kinds_after != kinds_before {
+            match kinds_after {
+                (Some(RelationKind::Friend), Some(RelationKind::Friend)) => {
+                    event_log.push(format!("{name_a} and {name_b} became friends"));
+                }
+                (Some(RelationKind::Rival), Some(RelationKind::Rival)) => {
+                    event_log.push(format!("{name_a} and {name_b} became rivals"));
+                }
+                _ => {}
+            }
+        }
+    }
+}
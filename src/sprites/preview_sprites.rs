@@ -1,6 +1,8 @@
 use bevy::{platform::collections::HashMap, prelude::*, sprite::Anchor};
 
-use crate::{Dweller, TILE_SIZE, Task, TaskKind, TaskNeeds, tasks::WorkstationAmount};
+use crate::{
+    Dweller, TILE_SIZE, Task, TaskKind, TaskNeeds, task_index::TaskIndex, tasks::WorkstationAmount,
+};
 
 #[derive(Component)]
 pub enum DwellerEquipmentPreview {
@@ -154,6 +156,59 @@ pub fn update_task_build_preview(
     }
 }
 
+// Sprite color of a blocked task, restored once it is unblocked
+#[derive(Component)]
+pub struct TaskBlockedTint(Color);
+
+// Grey out tasks waiting on their dependencies
+pub fn update_task_blocked_preview(
+    mut commands: Commands,
+    task_index: Res<TaskIndex>,
+    mut q_tasks: Query<
+        (
+            Entity,
+            &Task,
+            &mut Sprite,
+            Option<&TaskBlockedTint>,
+            Option<&Children>,
+        ),
+        Without<TaskBuildPreview>,
+    >,
+    mut q_build_previews: Query<&mut Sprite, With<TaskBuildPreview>>,
+) {
+    for (entity, task, mut sprite, tint, children) in &mut q_tasks {
+        let blocked = task_index.is_blocked(task);
+
+        match (blocked, tint) {
+            (true, None) => {
+                commands
+                    .entity(entity)
+                    .insert(TaskBlockedTint(sprite.color));
+                sprite.color = Color::srgba(0.3, 0.3, 0.3, 0.6);
+            }
+            (false, Some(TaskBlockedTint(color))) => {
+                sprite.color = *color;
+                commands.entity(entity).remove::<TaskBlockedTint>();
+            }
+            _ => {}
+        }
+
+        // Build previews may be spawned after the task got blocked
+        let preview_color = match (blocked, tint) {
+            (true, _) => Color::srgba(0.3, 0.3, 0.3, 0.3),
+            (false, Some(_)) => Color::WHITE.with_alpha(0.5),
+            (false, None) => continue,
+        };
+        for child in children.into_iter().flatten() {
+            if let Ok(mut preview_sprite) = q_build_previews.get_mut(*child)
+                && preview_sprite.color != preview_color
+            {
+                preview_sprite.color = preview_color;
+            }
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct TaskWorkstationPreview;
 
@@ -166,7 +221,7 @@ pub fn update_task_workstation_preview(
     for (entity, task, children) in &q_tasks {
         match task.kind {
             // Workstation amount preview
-            TaskKind::Workstation { amount } => {
+            TaskKind::Workstation { amount, .. } => {
                 if let Some(old_amount) = changes.get(&entity)
                     && *old_amount == amount
                 {
@@ -210,12 +265,23 @@ pub fn observe_dweller_hover(
         return;
     };
 
+    let mut text = format!(
+        "<3 {}\n><> {}\n~~ {}\nzZ {}\n:) {}",
+        dweller.health, dweller.food, dweller.thirst, dweller.sleep, dweller.mood
+    );
+    if dweller.is_breaking() {
+        text += " (break)";
+    }
+    for (factor, mood) in dweller.mood_factors() {
+        text += &format!("\n  {factor} {mood:+}");
+    }
+    if let Some(tool) = dweller.tool {
+        text += &format!("\n{tool:?} {}", dweller.tool_durability);
+    }
+
     commands.entity(pointer_over.entity).with_child((
         DwellerNeedsPreview,
-        Text2d::new(format!(
-            "<3 {}\n><> {}\nzZ {}",
-            dweller.health, dweller.food, dweller.sleep
-        )),
+        Text2d::new(text),
         Anchor::BOTTOM_CENTER,
         Transform::from_xyz(8., 26., 1.0).with_scale(Vec3::splat(0.25)),
     ));
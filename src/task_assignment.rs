@@ -0,0 +1,573 @@
+use std::time::Instant;
+
+use bevy::{platform::collections::HashMap, prelude::*};
+use pathfinding::prelude::{Matrix, dijkstra_reach, kuhn_munkres_min};
+use rand::prelude::*;
+use uuid::Uuid;
+
+use crate::{
+    CHUNK_SIZE, TilemapData,
+    data::{ObjectId, TileId},
+};
+
+// A higher priority task always wins over a closer one
+const PRIORITY_WEIGHT: i64 = 100_000;
+// A dweller holding the right tool wins over one up to this many tiles closer
+const TOOL_WEIGHT: i64 = 20;
+// Used by solvers that need a full matrix, must dominate any sum of real costs
+const IMPOSSIBLE_COST: i64 = 1_000_000_000;
+
+// Farther tasks are estimated, then pathfound once assigned
+const DISTANCE_FIELD_MAX_DISTANCE: i32 = 48;
+
+pub fn assignment_cost(priority: i32, distance: i32, has_tool: bool) -> i64 {
+    distance as i64 - priority as i64 * PRIORITY_WEIGHT - if has_tool { TOOL_WEIGHT } else { 0 }
+}
+
+// Dwellers x tasks cost matrix, None if the dweller can't do or can't reach the task
+pub struct AssignmentProblem {
+    dwellers: usize,
+    tasks: usize,
+    costs: Vec<Option<i64>>,
+}
+
+impl AssignmentProblem {
+    pub fn new(
+        dwellers: usize,
+        tasks: usize,
+        mut cost: impl FnMut(usize, usize) -> Option<i64>,
+    ) -> Self {
+        let mut costs = Vec::with_capacity(dwellers * tasks);
+        for dweller_i in 0..dwellers {
+            for task_i in 0..tasks {
+                costs.push(cost(dweller_i, task_i));
+            }
+        }
+
+        Self {
+            dwellers,
+            tasks,
+            costs,
+        }
+    }
+
+    pub fn cost(&self, dweller_i: usize, task_i: usize) -> Option<i64> {
+        self.costs[dweller_i * self.tasks + task_i]
+    }
+
+    pub fn total_cost(&self, assignment: &[(usize, usize)]) -> i64 {
+        assignment
+            .iter()
+            .filter_map(|(dweller_i, task_i)| self.cost(*dweller_i, *task_i))
+            .sum()
+    }
+}
+
+pub trait AssignmentStrategy: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    // Returns (dweller index, task index) pairs, each dweller and task at most once
+    fn solve(&self, problem: &AssignmentProblem) -> Vec<(usize, usize)>;
+}
+
+// Repeatedly picks the cheapest remaining pair. Fast, but often sends the far dweller to the near task
+pub struct GreedyAssignment;
+
+impl AssignmentStrategy for GreedyAssignment {
+    fn name(&self) -> &'static str {
+        "greedy"
+    }
+
+    fn solve(&self, problem: &AssignmentProblem) -> Vec<(usize, usize)> {
+        let mut pairs = (0..problem.dwellers)
+            .flat_map(|dweller_i| {
+                (0..problem.tasks).filter_map(move |task_i| {
+                    problem
+                        .cost(dweller_i, task_i)
+                        .map(|cost| (cost, dweller_i, task_i))
+                })
+            })
+            .collect::<Vec<_>>();
+
+        pairs.sort_unstable();
+
+        let mut assigned_dwellers = vec![false; problem.dwellers];
+        let mut assigned_tasks = vec![false; problem.tasks];
+        let mut assignment = vec![];
+
+        for (_, dweller_i, task_i) in pairs {
+            if assigned_dwellers[dweller_i] || assigned_tasks[task_i] {
+                continue;
+            }
+
+            assigned_dwellers[dweller_i] = true;
+            assigned_tasks[task_i] = true;
+            assignment.push((dweller_i, task_i));
+        }
+
+        assignment
+    }
+}
+
+// Minimizes the total cost of all assignments (Kuhn-Munkres)
+pub struct HungarianAssignment;
+
+impl AssignmentStrategy for HungarianAssignment {
+    fn name(&self) -> &'static str {
+        "hungarian"
+    }
+
+    fn solve(&self, problem: &AssignmentProblem) -> Vec<(usize, usize)> {
+        if problem.dwellers == 0 || problem.tasks == 0 {
+            return vec![];
+        }
+
+        // kuhn_munkres needs rows <= columns
+        let transposed = problem.dwellers > problem.tasks;
+        let (rows, columns) = if transposed {
+            (problem.tasks, problem.dwellers)
+        } else {
+            (problem.dwellers, problem.tasks)
+        };
+
+        let to_pair = |row: usize, column: usize| {
+            if transposed {
+                (column, row)
+            } else {
+                (row, column)
+            }
+        };
+
+        let weights = Matrix::from_fn(rows, columns, |(row, column)| {
+            let (dweller_i, task_i) = to_pair(row, column);
+            problem.cost(dweller_i, task_i).unwrap_or(IMPOSSIBLE_COST)
+        });
+
+        let (_, columns_of_rows) = kuhn_munkres_min(&weights);
+
+        columns_of_rows
+            .into_iter()
+            .enumerate()
+            .map(|(row, column)| to_pair(row, column))
+            .filter(|(dweller_i, task_i)| problem.cost(*dweller_i, *task_i).is_some())
+            .collect()
+    }
+}
+
+// The rows and columns touched by changed dwellers or tasks. The last solve left no possible
+// pair among the others, so only these need solving again
+pub struct PartialAssignment {
+    dwellers: Vec<usize>,
+    tasks: Vec<usize>,
+    problem: AssignmentProblem,
+}
+
+impl PartialAssignment {
+    pub fn new(
+        changed_dwellers: &[bool],
+        changed_tasks: &[bool],
+        mut cost: impl FnMut(usize, usize) -> Option<i64>,
+    ) -> Self {
+        let mut costs = HashMap::default();
+        for (dweller_i, dweller_changed) in changed_dwellers.iter().enumerate() {
+            for (task_i, task_changed) in changed_tasks.iter().enumerate() {
+                if (*dweller_changed || *task_changed)
+                    && let Some(cost) = cost(dweller_i, task_i)
+                {
+                    costs.insert((dweller_i, task_i), cost);
+                }
+            }
+        }
+
+        let mut dwellers = costs
+            .keys()
+            .map(|(dweller_i, _)| *dweller_i)
+            .collect::<Vec<_>>();
+        let mut tasks = costs.keys().map(|(_, task_i)| *task_i).collect::<Vec<_>>();
+        dwellers.sort_unstable();
+        dwellers.dedup();
+        tasks.sort_unstable();
+        tasks.dedup();
+
+        let problem = AssignmentProblem::new(dwellers.len(), tasks.len(), |row, column| {
+            costs.get(&(dwellers[row], tasks[column])).copied()
+        });
+
+        Self {
+            dwellers,
+            tasks,
+            problem,
+        }
+    }
+
+    // Same as AssignmentStrategy::solve, with the indices of the full problem
+    pub fn solve(&self, strategy: &dyn AssignmentStrategy) -> Vec<(usize, usize)> {
+        strategy
+            .solve(&self.problem)
+            .into_iter()
+            .map(|(row, column)| (self.dwellers[row], self.tasks[column]))
+            .collect()
+    }
+}
+
+#[derive(Resource)]
+pub struct TaskAssigner(pub Box<dyn AssignmentStrategy>);
+
+impl Default for TaskAssigner {
+    fn default() -> Self {
+        Self(Box::new(HungarianAssignment))
+    }
+}
+
+// Walking distances from a dweller to every tile it can reach nearby, with the paths to get there
+pub struct DistanceField {
+    origin: IVec3,
+    parents: HashMap<IVec3, (Option<IVec3>, i32)>,
+    // Every reachable tile is in the field, anything else is unreachable
+    complete: bool,
+}
+
+impl DistanceField {
+    pub fn new(origin: IVec3, tilemap_data: &TilemapData) -> Self {
+        let mut parents = HashMap::default();
+        let mut complete = true;
+
+        for item in dijkstra_reach(&origin, |p| {
+            tilemap_data
+                .non_blocking_neighbours_pos(*p, true)
+                .into_iter()
+                .map(|neighbor| (neighbor, 1))
+        }) {
+            if item.total_cost > DISTANCE_FIELD_MAX_DISTANCE {
+                complete = false;
+                break;
+            }
+            parents.insert(item.node, (item.parent, item.total_cost));
+        }
+
+        Self {
+            origin,
+            parents,
+            complete,
+        }
+    }
+
+    pub fn origin(&self) -> IVec3 {
+        self.origin
+    }
+
+    pub fn distance(&self, pos: IVec3) -> Option<i32> {
+        self.parents.get(&pos).map(|(_, distance)| *distance)
+    }
+
+    pub fn closest(&self, goals: &[IVec3]) -> Option<(IVec3, i32)> {
+        goals
+            .iter()
+            .filter_map(|goal| self.distance(*goal).map(|distance| (*goal, distance)))
+            .min_by_key(|(_, distance)| *distance)
+    }
+
+    // Exact distance to the closest goal within the field, a lower bound beyond it
+    pub fn estimate(&self, goals: &[IVec3]) -> Option<i32> {
+        if let Some((_, distance)) = self.closest(goals) {
+            return Some(distance);
+        }
+        if self.complete {
+            return None;
+        }
+
+        goals
+            .iter()
+            .map(|goal| TilemapData::manhattan_distance(self.origin, *goal))
+            .min()
+            .map(|distance| distance.max(DISTANCE_FIELD_MAX_DISTANCE + 1))
+    }
+
+    // Same format as a dweller move_queue: next move is at the end
+    pub fn path_to(&self, goal: IVec3) -> Option<Vec<IVec3>> {
+        let mut path = vec![goal];
+        let mut current = goal;
+
+        while let (Some(parent), _) = self.parents.get(&current)? {
+            path.push(*parent);
+            current = *parent;
+        }
+
+        Some(path)
+    }
+}
+
+// Kept between ticks so only idle dwellers that moved, assignable tasks that changed,
+// or everything once a path opened or closed, are reconsidered
+#[derive(Default)]
+pub struct AssignmentCache {
+    pub distance_fields: HashMap<Uuid, DistanceField>,
+    pub idle_dwellers: HashMap<Uuid, (IVec3, Option<ObjectId>)>,
+    pub tasks: HashMap<Entity, (IVec3, usize)>,
+    pub paths_version: u32,
+}
+
+impl AssignmentCache {
+    pub fn update_distance_fields(
+        &mut self,
+        idle_dwellers: &[(Uuid, IVec3)],
+        tilemap_data: &TilemapData,
+    ) {
+        if self.paths_version != tilemap_data.paths_version {
+            self.paths_version = tilemap_data.paths_version;
+            self.distance_fields.clear();
+        }
+
+        self.distance_fields
+            .retain(|uuid, field| idle_dwellers.contains(&(*uuid, field.origin())));
+
+        for (uuid, pos) in idle_dwellers {
+            self.distance_fields
+                .entry(*uuid)
+                .or_insert_with(|| DistanceField::new(*pos, tilemap_data));
+        }
+    }
+}
+
+// Maps to benchmark the strategies on
+#[derive(Clone, Copy, Debug)]
+enum SyntheticMap {
+    Open,
+    Scattered,
+    Caves,
+}
+
+impl SyntheticMap {
+    const ALL: [Self; 3] = [Self::Open, Self::Scattered, Self::Caves];
+    const CHUNKS: i32 = 2;
+
+    fn generate(self, rng: &mut StdRng) -> TilemapData {
+        let mut tilemap_data = TilemapData::default();
+
+        for chunk_x in 0..Self::CHUNKS {
+            for chunk_y in 0..Self::CHUNKS {
+                let chunk_pos = IVec3::new(chunk_x, chunk_y, 0);
+                let chunk = TilemapData::iter_chunk_positions(chunk_pos)
+                    .map(|pos| {
+                        let wall = match self {
+                            Self::Open => false,
+                            Self::Scattered => rng.random_bool(0.25),
+                            // Rooms every 16 tiles, linked by 2 tiles wide corridors
+                            Self::Caves => {
+                                let local = pos.xy().rem_euclid(IVec2::splat(16));
+                                let in_room =
+                                    local.x > 2 && local.x < 14 && local.y > 2 && local.y < 14;
+                                let in_corridor =
+                                    (7..9).contains(&local.x) || (7..9).contains(&local.y);
+                                !(in_room || in_corridor)
+                            }
+                        };
+
+                        if wall {
+                            TileId::StoneWall.place()
+                        } else {
+                            TileId::StoneFloor.place()
+                        }
+                    })
+                    .collect();
+
+                tilemap_data.set_chunk(chunk_pos, chunk);
+            }
+        }
+
+        tilemap_data
+    }
+
+    fn random_free_pos(tilemap_data: &TilemapData, rng: &mut StdRng) -> IVec3 {
+        let size = Self::CHUNKS * CHUNK_SIZE as i32;
+        loop {
+            let pos = IVec3::new(rng.random_range(0..size), rng.random_range(0..size), 0);
+            if tilemap_data
+                .get(pos)
+                .is_some_and(|tile| !tile.is_blocking())
+            {
+                return pos;
+            }
+        }
+    }
+}
+
+// Compares the strategies on generated maps
+// cargo run --release -- bench-assignment
+pub fn run_assignment_bench(mut ev_exit: MessageWriter<AppExit>) {
+    let strategies: [Box<dyn AssignmentStrategy>; 2] =
+        [Box::new(GreedyAssignment), Box::new(HungarianAssignment)];
+
+    for map in SyntheticMap::ALL {
+        let mut rng = StdRng::seed_from_u64(42);
+        let tilemap_data = map.generate(&mut rng);
+
+        for (n_dwellers, n_tasks) in [(10, 10), (10, 100), (30, 30), (50, 500)] {
+            let dwellers = (0..n_dwellers)
+                .map(|_| SyntheticMap::random_free_pos(&tilemap_data, &mut rng))
+                .collect::<Vec<_>>();
+            let tasks = (0..n_tasks)
+                .map(|_| SyntheticMap::random_free_pos(&tilemap_data, &mut rng))
+                .collect::<Vec<_>>();
+
+            let start = Instant::now();
+            let fields = dwellers
+                .iter()
+                .map(|pos| DistanceField::new(*pos, &tilemap_data))
+                .collect::<Vec<_>>();
+            let fields_duration = start.elapsed();
+
+            let problem = AssignmentProblem::new(n_dwellers, n_tasks, |dweller_i, task_i| {
+                fields[dweller_i]
+                    .distance(tasks[task_i])
+                    .map(|distance| assignment_cost(0, distance, false))
+            });
+
+            info!(
+                "{map:?} map, {n_dwellers} dwellers, {n_tasks} tasks (distance fields: {fields_duration:?})"
+            );
+
+            for strategy in &strategies {
+                let start = Instant::now();
+                let assignment = strategy.solve(&problem);
+                let duration = start.elapsed();
+
+                info!(
+                    "  {:<10} assigned {:>3}, total distance {:>6}, solved in {duration:?}",
+                    strategy.name(),
+                    assignment.len(),
+                    problem.total_cost(&assignment),
+                );
+            }
+        }
+    }
+
+    ev_exit.write(AppExit::Success);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strategies() -> [Box<dyn AssignmentStrategy>; 2] {
+        [Box::new(GreedyAssignment), Box::new(HungarianAssignment)]
+    }
+
+    fn is_valid(problem: &AssignmentProblem, assignment: &[(usize, usize)]) -> bool {
+        let mut dwellers = assignment
+            .iter()
+            .map(|(dweller_i, _)| dweller_i)
+            .collect::<Vec<_>>();
+        let mut tasks = assignment
+            .iter()
+            .map(|(_, task_i)| task_i)
+            .collect::<Vec<_>>();
+        dwellers.sort_unstable();
+        dwellers.dedup();
+        tasks.sort_unstable();
+        tasks.dedup();
+
+        dwellers.len() == assignment.len()
+            && tasks.len() == assignment.len()
+            && assignment
+                .iter()
+                .all(|(dweller_i, task_i)| problem.cost(*dweller_i, *task_i).is_some())
+    }
+
+    #[test]
+    fn assignment_cost_orders_priority_then_tool_then_distance() {
+        assert!(assignment_cost(1, 500, false) < assignment_cost(0, 0, true));
+        assert!(assignment_cost(0, 10, true) < assignment_cost(0, 10, false));
+        assert!(
+            assignment_cost(0, 10 + TOOL_WEIGHT as i32 - 1, true) < assignment_cost(0, 10, false)
+        );
+        assert!(assignment_cost(0, 5, false) < assignment_cost(0, 6, false));
+    }
+
+    #[test]
+    fn hungarian_beats_greedy_on_crossed_pairs() {
+        // Greedy sends dweller 0 to the task dweller 1 needs
+        let costs = [[1, 2], [2, 100]];
+        let problem =
+            AssignmentProblem::new(2, 2, |dweller_i, task_i| Some(costs[dweller_i][task_i]));
+
+        let greedy = GreedyAssignment.solve(&problem);
+        let hungarian = HungarianAssignment.solve(&problem);
+
+        assert_eq!(problem.total_cost(&greedy), 101);
+        assert_eq!(problem.total_cost(&hungarian), 4);
+    }
+
+    #[test]
+    fn solvers_skip_impossible_pairs() {
+        // Dweller 1 can't do anything, task 2 can't be done by anyone
+        let problem = AssignmentProblem::new(3, 3, |dweller_i, task_i| {
+            (dweller_i != 1 && task_i != 2).then_some((dweller_i + task_i) as i64)
+        });
+
+        for strategy in strategies() {
+            let assignment = strategy.solve(&problem);
+            assert!(is_valid(&problem, &assignment), "{}", strategy.name());
+            assert_eq!(assignment.len(), 2, "{}", strategy.name());
+        }
+    }
+
+    #[test]
+    fn solvers_handle_uneven_problems() {
+        for (dwellers, tasks) in [(0, 3), (3, 0), (2, 5), (5, 2)] {
+            let problem = AssignmentProblem::new(dwellers, tasks, |dweller_i, task_i| {
+                Some((dweller_i * 7 + task_i * 3) as i64 % 5)
+            });
+
+            for strategy in strategies() {
+                let assignment = strategy.solve(&problem);
+                assert!(is_valid(&problem, &assignment), "{}", strategy.name());
+                assert_eq!(assignment.len(), dwellers.min(tasks), "{}", strategy.name());
+            }
+        }
+    }
+
+    #[test]
+    fn partial_assignment_only_solves_changed_rows_and_columns() {
+        // Dweller 0 and task 0 were left unmatched by the last solve, dweller 1 is new
+        let mut evaluated = vec![];
+        let partial =
+            PartialAssignment::new(&[false, true], &[false, false], |dweller_i, task_i| {
+                evaluated.push((dweller_i, task_i));
+                Some(1)
+            });
+
+        assert_eq!(evaluated, [(1, 0), (1, 1)]);
+        for strategy in strategies() {
+            let assignment = partial.solve(strategy.as_ref());
+            assert_eq!(assignment.len(), 1, "{}", strategy.name());
+            assert_eq!(assignment[0].0, 1, "{}", strategy.name());
+        }
+
+        // Nothing changed, nothing to solve
+        let partial = PartialAssignment::new(&[false], &[false], |_, _| Some(1));
+        assert!(partial.solve(&HungarianAssignment).is_empty());
+    }
+
+    #[test]
+    fn distance_field_estimates_beyond_its_bound() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let tilemap_data = SyntheticMap::Open.generate(&mut rng);
+        let origin = IVec3::new(1, 1, 0);
+        let field = DistanceField::new(origin, &tilemap_data);
+
+        let near = IVec3::new(4, 1, 0);
+        assert_eq!(field.estimate(&[near]), Some(3));
+        assert_eq!(field.path_to(near).map(|path| path.len()), Some(4));
+
+        let far = IVec3::new(60, 1, 0);
+        assert!(field.distance(far).is_none());
+        assert_eq!(field.estimate(&[far]), Some(59));
+
+        // A fully explored field knows the rest is unreachable
+        let mut walled = TilemapData::filled_chunk(IVec3::ZERO, TileId::StoneWall.place());
+        walled.set(origin, TileId::StoneFloor.place());
+        let field = DistanceField::new(origin, &walled);
+        assert_eq!(field.estimate(&[far]), None);
+    }
+}
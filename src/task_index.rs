@@ -0,0 +1,225 @@
+use std::mem::Discriminant;
+
+use bevy::{
+    ecs::{lifecycle::HookContext, world::DeferredWorld},
+    platform::collections::HashMap,
+    prelude::*,
+};
+use uuid::Uuid;
+
+use crate::tasks::{Task, TaskKind};
+
+#[derive(Clone, Copy, Debug)]
+pub struct IndexedTask {
+    pub entity: Entity,
+    pub id: Uuid,
+    pub pos: IVec3,
+    pub kind: TaskKind,
+    pub dweller_id: Option<Uuid>,
+}
+
+impl IndexedTask {
+    fn new(entity: Entity, task: &Task) -> Self {
+        Self {
+            entity,
+            id: task.id,
+            pos: task.pos,
+            kind: task.kind,
+            dweller_id: task.dweller_id,
+        }
+    }
+}
+
+// What happens when placing a new task on a tile where another task exists
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum TaskPlacement {
+    Allowed,
+    Replaces,
+    Denied,
+}
+
+impl TaskKind {
+    pub fn placement_over(self, other: &IndexedTask) -> TaskPlacement {
+        match (self, other.kind) {
+            (TaskKind::Stockpile, TaskKind::Pickup) => TaskPlacement::Replaces,
+
+            (TaskKind::Pickup, TaskKind::Stockpile)
+            | (
+                TaskKind::Smoothen,
+                TaskKind::Stockpile
+                | TaskKind::Pickup
+                | TaskKind::Harvest
+                | TaskKind::Attack
+                | TaskKind::Workstation { .. },
+            )
+            | (TaskKind::Attack | TaskKind::Recruit | TaskKind::Extinguish, _)
+            // The Workstation task is removed once deconstructed
+            | (TaskKind::Deconstruct, TaskKind::Workstation { .. }) => TaskPlacement::Allowed,
+
+            // Dwellers can eat or drink objects waiting to be picked up or lying on stockpiles
+            (TaskKind::Eat | TaskKind::Drink, TaskKind::Pickup | TaskKind::Stockpile)
+                if other.dweller_id.is_none() =>
+            {
+                TaskPlacement::Allowed
+            }
+
+            _ => TaskPlacement::Denied,
+        }
+    }
+}
+
+// Lookup tables over all Task entities, kept in sync by the Task component hooks
+// and by refresh for in-place mutations
+#[derive(Resource, Default)]
+pub struct TaskIndex {
+    tasks: HashMap<Entity, IndexedTask>,
+    by_id: HashMap<Uuid, Entity>,
+    by_pos: HashMap<IVec3, Vec<Entity>>,
+    by_dweller: HashMap<Uuid, Vec<Entity>>,
+    by_kind: HashMap<Discriminant<TaskKind>, Vec<Entity>>,
+}
+
+impl TaskIndex {
+    fn insert(&mut self, indexed: IndexedTask) {
+        let entity = indexed.entity;
+        self.remove(entity);
+
+        self.by_id.insert(indexed.id, entity);
+        self.by_pos.entry(indexed.pos).or_default().push(entity);
+        if let Some(dweller_id) = indexed.dweller_id {
+            self.by_dweller.entry(dweller_id).or_default().push(entity);
+        }
+        self.by_kind
+            .entry(std::mem::discriminant(&indexed.kind))
+            .or_default()
+            .push(entity);
+
+        self.tasks.insert(entity, indexed);
+    }
+
+    fn remove(&mut self, entity: Entity) {
+        let Some(indexed) = self.tasks.remove(&entity) else {
+            return;
+        };
+
+        if self.by_id.get(&indexed.id) == Some(&entity) {
+            self.by_id.remove(&indexed.id);
+        }
+        remove_from(&mut self.by_pos, &indexed.pos, entity);
+        if let Some(dweller_id) = indexed.dweller_id {
+            remove_from(&mut self.by_dweller, &dweller_id, entity);
+        }
+        remove_from(
+            &mut self.by_kind,
+            &std::mem::discriminant(&indexed.kind),
+            entity,
+        );
+    }
+
+    // Hooks don't see in-place mutations (dweller assignment, Attack tasks following mobs),
+    // systems mutating a task call this right away so later systems of the tick see it
+    pub fn refresh(&mut self, entity: Entity, task: &Task) {
+        self.insert(IndexedTask::new(entity, task));
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<&IndexedTask> {
+        self.tasks.get(&entity)
+    }
+
+    pub fn get_by_id(&self, id: Uuid) -> Option<&IndexedTask> {
+        self.by_id
+            .get(&id)
+            .and_then(|entity| self.tasks.get(entity))
+    }
+
+    // A task is blocked while its dependencies exist,
+    // or while its tile still has to be cleared (e.g. a Rock dropped by a Dig)
+    pub fn is_blocked(&self, task: &Task) -> bool {
+        task.depends_on.iter().any(|id| self.by_id.contains_key(id))
+            || (matches!(task.kind, TaskKind::Build { .. })
+                && self
+                    .at(task.pos)
+                    .any(|other| matches!(other.kind, TaskKind::Dig | TaskKind::Pickup)))
+    }
+
+    pub fn at(&self, pos: IVec3) -> impl Iterator<Item = &IndexedTask> {
+        self.iter_entities(self.by_pos.get(&pos))
+    }
+
+    pub fn has_task_at(&self, pos: IVec3) -> bool {
+        self.by_pos.contains_key(&pos)
+    }
+
+    pub fn of_dweller(&self, dweller_id: Uuid) -> impl Iterator<Item = &IndexedTask> {
+        self.iter_entities(self.by_dweller.get(&dweller_id))
+    }
+
+    pub fn dweller_has_task(&self, dweller_id: Uuid) -> bool {
+        self.by_dweller.contains_key(&dweller_id)
+    }
+
+    pub fn of_kind(&self, kind: TaskKind) -> impl Iterator<Item = &IndexedTask> {
+        self.iter_entities(self.by_kind.get(&std::mem::discriminant(&kind)))
+    }
+
+    // None if a task of this kind can't be placed at pos,
+    // else the existing tasks it replaces
+    pub fn check_placement(&self, pos: IVec3, kind: TaskKind) -> Option<Vec<Entity>> {
+        let mut replaced = vec![];
+
+        for other in self.at(pos) {
+            match kind.placement_over(other) {
+                TaskPlacement::Allowed => {}
+                TaskPlacement::Replaces => replaced.push(other.entity),
+                TaskPlacement::Denied => return None,
+            }
+        }
+
+        Some(replaced)
+    }
+
+    pub fn can_place(&self, pos: IVec3, kind: TaskKind) -> bool {
+        self.at(pos)
+            .all(|other| kind.placement_over(other) == TaskPlacement::Allowed)
+    }
+
+    fn iter_entities<'a>(
+        &'a self,
+        entities: Option<&'a Vec<Entity>>,
+    ) -> impl Iterator<Item = &'a IndexedTask> {
+        entities
+            .into_iter()
+            .flatten()
+            .filter_map(|entity| self.tasks.get(entity))
+    }
+}
+
+fn remove_from<K: Eq + std::hash::Hash>(
+    map: &mut HashMap<K, Vec<Entity>>,
+    key: &K,
+    entity: Entity,
+) {
+    if let Some(entities) = map.get_mut(key) {
+        entities.retain(|e| *e != entity);
+        if entities.is_empty() {
+            map.remove(key);
+        }
+    }
+}
+
+pub fn on_insert_task(mut world: DeferredWorld, HookContext { entity, .. }: HookContext) {
+    let Some(task) = world.get::<Task>(entity) else {
+        return;
+    };
+    let indexed = IndexedTask::new(entity, task);
+
+    if let Some(mut task_index) = world.get_resource_mut::<TaskIndex>() {
+        task_index.insert(indexed);
+    }
+}
+
+pub fn on_replace_task(mut world: DeferredWorld, HookContext { entity, .. }: HookContext) {
+    if let Some(mut task_index) = world.get_resource_mut::<TaskIndex>() {
+        task_index.remove(entity);
+    }
+}
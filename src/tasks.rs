@@ -7,11 +7,18 @@ use rand::prelude::*;
 use uuid::Uuid;
 
 use crate::{
-    CHUNK_SIZE, ObjectSlot, SaveScoped, SpriteLoader, TILE_SIZE, TakingDamage, TilePlaced,
-    TilemapData,
-    data::{EAT_VALUES, ObjectId, SLEEP_VALUES, TileId, WORKSTATIONS},
-    dwellers::Dweller,
+    CHUNK_SIZE, Layer, ObjectSlot, SaveScoped, SpriteLoader, TILE_SIZE, TakingDamage, TilePlaced,
+    TilemapData, ToolCategory,
+    combat::{CombatLog, roll_hit, train_skill},
+    data::{
+        BED_VALUES, BUILD_RECIPES, DRINK_VALUES, EAT_VALUES, MEDICINE_VALUES, MINING_TIERS,
+        ObjectId, SLEEP_VALUES, TileId, WATER_DRINK_VALUE, WORKSTATIONS, workstation_recipe,
+    },
+    dwellers::{Dweller, spawn_dweller},
     mobs::Mob,
+    mood::Thought,
+    social::EventLog,
+    task_index::{TaskIndex, on_insert_task, on_replace_task},
     utils::transform_to_pos,
 };
 
@@ -32,24 +39,60 @@ pub enum TaskKind {
     },
     Workstation {
         amount: WorkstationAmount,
+        recipe: usize,
     },
     Walk,
     Eat,
+    Drink,
     Sleep,
     Flood,
     Scoop,
+    Deconstruct,
+    Extinguish,
+    Rescue,
+    CarryToBed,
+    Bury,
+    Tend,
+    Recruit,
 }
 
 impl TaskKind {
     pub fn priority(self) -> i32 {
         match self {
-            TaskKind::Attack => 2,
-            TaskKind::Eat | TaskKind::Sleep => 1,
+            TaskKind::Attack | TaskKind::Extinguish | TaskKind::Rescue | TaskKind::CarryToBed => 2,
+            TaskKind::Eat | TaskKind::Drink | TaskKind::Sleep | TaskKind::Tend => 1,
             TaskKind::Stockpile | TaskKind::Walk => -1,
             _ => 0,
         }
     }
 
+    // Tool speeding up or unlocking the task
+    pub fn tool(self) -> Option<ToolCategory> {
+        match self {
+            TaskKind::Dig | TaskKind::Smoothen => Some(ToolCategory::Pickaxe),
+            TaskKind::Harvest => Some(ToolCategory::Axe),
+            TaskKind::Build {
+                result: BuildResult::Object(ObjectId::Farm),
+            } => Some(ToolCategory::Hoe),
+            TaskKind::Build { .. } | TaskKind::Deconstruct => Some(ToolCategory::Hammer),
+            TaskKind::Fish => Some(ToolCategory::FishingRod),
+            TaskKind::Attack => Some(ToolCategory::Weapon),
+            _ => None,
+        }
+    }
+
+    // Work needed once on the task location, a dweller works 1 per tick without tool
+    pub fn work(self) -> f32 {
+        match self {
+            TaskKind::Dig | TaskKind::Fish => 4.0,
+            TaskKind::Smoothen | TaskKind::Harvest => 3.0,
+            TaskKind::Build { .. } | TaskKind::Deconstruct => 2.0,
+            TaskKind::Bury => 4.0,
+            TaskKind::Tend => 2.0,
+            _ => 1.0,
+        }
+    }
+
     pub fn is_valid_on_tile(self, tile: TilePlaced) -> bool {
         match self {
             TaskKind::Dig => matches!(
@@ -83,7 +126,7 @@ impl TaskKind {
                         .is_some_and(|object| object.data().is_carriable())
             }
             TaskKind::Fish => matches!(tile.object, Some(ObjectId::FishingSpot)),
-            TaskKind::Attack => true,
+            TaskKind::Attack | TaskKind::Recruit => true,
             TaskKind::Stockpile => {
                 !tile.id.data().is_wall()
                     && tile
@@ -97,12 +140,23 @@ impl TaskKind {
             TaskKind::Eat => tile
                 .object
                 .is_some_and(|object| EAT_VALUES.contains_key(&object)),
+            TaskKind::Drink => {
+                tile.has_water()
+                    || tile
+                        .object
+                        .is_some_and(|object| DRINK_VALUES.contains_key(&object))
+            }
             TaskKind::Sleep => tile
                 .object
                 .is_some_and(|object| SLEEP_VALUES.contains_key(&object)),
-            TaskKind::Scoop => {
-                matches!(tile.id, TileId::ShallowWater | TileId::Water | TileId::Lava)
-            }
+            TaskKind::Scoop => tile.has_water() || tile.id == TileId::Lava,
+            TaskKind::Deconstruct => BuildResult::recipe_on_tile(tile).is_some(),
+            TaskKind::Extinguish => tile.fire > 0,
+            TaskKind::Rescue => true,
+            TaskKind::CarryToBed | TaskKind::Tend => tile
+                .object
+                .is_some_and(|object| BED_VALUES.contains_key(&object)),
+            TaskKind::Bury => tile.object == Some(ObjectId::Corpse),
         }
     }
 
@@ -147,6 +201,42 @@ impl BuildResult {
             BuildResult::Tile(tile) => tile.data().is_wall(),
         }
     }
+
+    // What dwellers built on this tile and can be deconstructed (the object first), and its cost
+    pub fn recipe_on_tile(tile: TilePlaced) -> Option<(Self, &'static [ObjectId])> {
+        let result = match tile.object {
+            Some(object) if tile.built_object => BuildResult::Object(object),
+            None if tile.built_over.is_some() => BuildResult::Tile(tile.id),
+            _ => return None,
+        };
+
+        BUILD_RECIPES
+            .iter()
+            .find(|(r, _)| *r == result)
+            .map(|(r, cost)| (*r, *cost))
+    }
+
+    // Tile left once deconstructed, or burnt
+    pub fn deconstructed_tile(self, tile: TilePlaced) -> TilePlaced {
+        match self {
+            BuildResult::Object(_) => tile.id.place(),
+            BuildResult::Tile(_) => tile.built_over.unwrap_or(TileId::StoneFloor).place(),
+        }
+    }
+
+    // Tile once built, remembering what it replaced
+    pub fn built_tile(self, tile: TilePlaced) -> TilePlaced {
+        match self {
+            BuildResult::Object(object) => TilePlaced {
+                built_object: true,
+                ..tile.id.with(object)
+            },
+            BuildResult::Tile(id) => TilePlaced {
+                built_over: Some(tile.id),
+                ..id.place()
+            },
+        }
+    }
 }
 
 #[derive(PartialEq, Clone, Copy, Reflect, Debug)]
@@ -201,6 +291,7 @@ pub struct TaskBundle {
     pub needs: TaskNeeds,
     pub sprite: SpriteLoader,
     pub transform: Transform,
+    pub layer: Layer,
 }
 
 impl TaskBundle {
@@ -222,6 +313,7 @@ impl TaskBundle {
             sprite: SpriteLoader {
                 texture_path: task.kind.sprite_path(),
             },
+            layer: Layer(task.pos.z),
             task,
             transform: Transform::from_xyz(x, y, Z_INDEX),
         }
@@ -237,18 +329,33 @@ pub enum TaskNeeds {
     Objects(Vec<ObjectId>),
     AnyObject,
     Impossible,
+    // Tool in hand of this category and at least this tier
+    Tool(ToolCategory, u8),
+}
+
+impl TaskNeeds {
+    // Walls with ores need a good enough pickaxe to be dug
+    pub fn dig(tile: TilePlaced) -> Self {
+        match tile.object.and_then(|object| MINING_TIERS.get(&object)) {
+            Some(tier) => TaskNeeds::Tool(ToolCategory::Pickaxe, *tier),
+            None => TaskNeeds::Nothing,
+        }
+    }
 }
 
-#[derive(Component, Reflect, Default, Debug)]
+#[derive(Component, Reflect, Debug)]
 #[reflect(Component)]
-#[require(Name::new("task"), SaveScoped)]
+#[require(Name::new("task"), SaveScoped, Layer)]
+#[component(on_insert = on_insert_task, on_replace = on_replace_task)]
 pub struct Task {
     timestamp: u128,
+    pub id: Uuid,
     pub kind: TaskKind,
-    pub pos: IVec2,
+    pub pos: IVec3,
     pub reachable_pathfinding: bool,
-    pub reachable_positions: Vec<IVec2>,
+    pub reachable_positions: Vec<IVec3>,
     pub dweller_id: Option<Uuid>, // Dweller id, because Entity is different accross chunk saves
+    pub depends_on: Vec<Uuid>,    // Task ids that must be completed before this one
 }
 
 impl Ord for Task {
@@ -271,26 +378,40 @@ impl PartialEq for Task {
 
 impl Eq for Task {}
 
+// Every task needs its own id, see TaskIndex::by_id
+impl Default for Task {
+    fn default() -> Self {
+        Self::new(IVec3::ZERO, TaskKind::default(), None)
+    }
+}
+
 impl Task {
-    pub fn new(pos: IVec2, kind: TaskKind, dweller_id: Option<Uuid>) -> Self {
+    pub fn new(pos: IVec3, kind: TaskKind, dweller_id: Option<Uuid>) -> Self {
         Self {
             timestamp: SystemTime::now()
                 .duration_since(UNIX_EPOCH)
                 .unwrap()
                 .as_nanos(),
+            id: Uuid::new_v4(),
             kind,
             pos,
             reachable_pathfinding: true,
             reachable_positions: vec![],
             dweller_id,
+            depends_on: vec![],
         }
     }
 
+    pub fn with_dependencies(mut self, depends_on: Vec<Uuid>) -> Self {
+        self.depends_on = depends_on;
+        self
+    }
+
     pub fn recompute_reachable_positions(&mut self, tilemap_data: &TilemapData) {
         self.reachable_positions = self.compute_reachable_positions(self.pos, tilemap_data);
     }
 
-    fn compute_reachable_positions(&self, pos: IVec2, tilemap_data: &TilemapData) -> Vec<IVec2> {
+    fn compute_reachable_positions(&self, pos: IVec3, tilemap_data: &TilemapData) -> Vec<IVec3> {
         if let Some(tile) = tilemap_data.get(pos)
             && !tile.is_blocking()
             && !matches!(self.kind, TaskKind::Build { result } if result.is_blocking())
@@ -302,8 +423,8 @@ impl Task {
         tilemap_data.non_blocking_neighbours_pos(pos, true)
     }
 
-    pub fn pathfind(&self, dweller_pos: IVec2, tilemap_data: &TilemapData) -> Option<Vec<IVec2>> {
-        let goals: HashSet<IVec2> = self.reachable_positions.iter().copied().collect();
+    pub fn pathfind(&self, dweller_pos: IVec3, tilemap_data: &TilemapData) -> Option<Vec<IVec3>> {
+        let goals: HashSet<IVec3> = self.reachable_positions.iter().copied().collect();
 
         astar(
             &dweller_pos,
@@ -311,7 +432,7 @@ impl Task {
             |p| {
                 goals
                     .iter()
-                    .map(|g| (p.x - g.x).abs() + (p.y - g.y).abs())
+                    .map(|g| TilemapData::manhattan_distance(*p, *g))
                     .min()
                     .unwrap_or(0)
             },
@@ -343,6 +464,13 @@ pub fn update_unreachable_tasks(tilemap_data: Res<TilemapData>, mut q_tasks: Que
     }
 }
 
+// Tasks following a mob change layer with it
+pub fn update_tasks_layer(mut q_tasks: Query<(&Task, &mut Layer), Changed<Task>>) {
+    for (task, mut layer) in &mut q_tasks {
+        layer.set_if_neq(Layer(task.pos.z));
+    }
+}
+
 pub fn update_unreachable_pathfinding_tasks(mut q_tasks: Query<&mut Task>) {
     q_tasks.par_iter_mut().for_each(|mut task| {
         if !task.reachable_pathfinding {
@@ -360,20 +488,19 @@ pub fn event_task_completion(
     mut commands: Commands,
     mut events: MessageReader<TaskCompletionEvent>,
     mut tilemap_data: ResMut<TilemapData>,
-    mut q_mobs: Query<(Entity, &mut Mob, &Transform)>,
-    mut q_dwellers: Query<(&mut Dweller, &Transform)>,
+    mut task_index: ResMut<TaskIndex>,
+    mut q_mobs: Query<(Entity, &mut Mob, &Transform, &Layer)>,
+    mut q_dwellers: Query<(&mut Dweller, &Transform, &Layer)>,
     mut q_tasks: Query<(Entity, &mut Task, &mut TaskNeeds, Option<&ChildOf>)>,
+    mut combat_log: ResMut<CombatLog>,
+    mut event_log: ResMut<EventLog>,
 ) {
     let mut rng = rand::rng();
 
     let mut update_tasks_pos = false;
     let mut update_stockpiles = false;
     let mut update_workstations = false;
-
-    let tasks_positions = q_tasks
-        .iter()
-        .map(|(_, task, _, _)| task.pos)
-        .collect::<HashSet<_>>();
+    let mut tended = Vec::new();
 
     for event in events.read() {
         let Ok((entity, mut task, mut task_needs, task_child_of)) = q_tasks.get_mut(event.task)
@@ -381,11 +508,21 @@ pub fn event_task_completion(
             continue;
         };
 
-        let Some((mut dweller, dweller_transform)) = task.dweller_id.and_then(|dweller_id| {
-            q_dwellers
-                .iter_mut()
-                .find(|(dweller, _)| dweller.uuid == dweller_id)
-        }) else {
+        // Rescue and Tend tasks are children of the dweller in need
+        let patient = task_child_of
+            .map(ChildOf::parent)
+            .filter(|parent| q_dwellers.contains(*parent));
+        let rescued = patient
+            .and_then(|patient| q_dwellers.get(patient).ok())
+            .map(|(dweller, _, _)| dweller.uuid);
+
+        let Some((mut dweller, dweller_transform, dweller_layer)) =
+            task.dweller_id.and_then(|dweller_id| {
+                q_dwellers
+                    .iter_mut()
+                    .find(|(dweller, _, _)| dweller.uuid == dweller_id)
+            })
+        else {
             continue;
         };
 
@@ -393,14 +530,15 @@ pub fn event_task_completion(
             continue;
         };
 
-        // Some tasks can become invalid if the tile has changed
-        if !task.kind.is_valid_on_tile(tile) {
+        // Some tasks can become invalid if the tile has changed, water drains often enough for
+        // Drink to handle it itself
+        if task.kind != TaskKind::Drink && !task.kind.is_valid_on_tile(tile) {
             error!("Removing invalid task {task:?} on tile {tile:?}");
             commands.entity(entity).despawn();
             continue;
         }
 
-        let dweller_pos = transform_to_pos(dweller_transform);
+        let dweller_pos = transform_to_pos(dweller_transform, dweller_layer);
 
         let mut success = false;
 
@@ -445,16 +583,14 @@ pub fn event_task_completion(
             TaskKind::Flood => {
                 if matches!(tile.id, TileId::ShallowWater) {
                     tilemap_data.set(task.pos, TileId::Water.place());
-                } else if let Some((_, tile)) =
-                    tilemap_data.neighbours(task.pos).iter().find(|(_, tile)| {
-                        matches!(tile.id, TileId::Water | TileId::ShallowWater | TileId::Lava)
-                    })
+                } else if let Some((_, tile)) = tilemap_data
+                    .neighbours(task.pos)
+                    .iter()
+                    .find(|(_, tile)| tile.has_water() || tile.id == TileId::Lava)
                 {
                     match tile.id {
-                        TileId::Water => {
-                            tilemap_data.set(task.pos, TileId::ShallowWater.place());
-                        }
-                        tile_id => tilemap_data.set(task.pos, tile_id.place()),
+                        TileId::Lava => tilemap_data.set(task.pos, TileId::Lava.place()),
+                        _ => tilemap_data.set(task.pos, TileId::ShallowWater.place()),
                     }
                 }
 
@@ -492,15 +628,18 @@ pub fn event_task_completion(
                         ObjectId::TallGrass => Some(ObjectId::Seeds),
 
                         ObjectId::WheatPlant => {
-                            dweller.object = Some(if rng.random_bool(0.7) {
-                                ObjectId::Wheat
-                            } else {
-                                ObjectId::Seeds
-                            });
+                            dweller.carry(
+                                if rng.random_bool(0.7) {
+                                    ObjectId::Wheat
+                                } else {
+                                    ObjectId::Seeds
+                                },
+                                0,
+                            );
 
                             if rng.random_bool(0.1) {
                                 for (pos, tile) in tilemap_data.neighbours(task.pos) {
-                                    if tile.is_floor_free() && !tasks_positions.contains(&pos) {
+                                    if tile.is_floor_free() && !task_index.has_task_at(pos) {
                                         tilemap_data.set(pos, tile.id.with(ObjectId::Farm));
                                         break;
                                     }
@@ -511,7 +650,7 @@ pub fn event_task_completion(
                         }
 
                         ObjectId::BerryBush => {
-                            dweller.object = Some(ObjectId::Berries);
+                            dweller.carry(ObjectId::Berries, 0);
                             Some(ObjectId::Bush)
                         }
 
@@ -544,18 +683,32 @@ pub fn event_task_completion(
                     tilemap_data.set(task.pos, tile.id.place());
 
                     match (object.data().slot(), dweller.tool, dweller.armor) {
-                        (ObjectSlot::Tool(_), None, _) => {
-                            dweller.tool = Some(object);
+                        (ObjectSlot::Tool { .. }, None, _) => {
+                            dweller.equip_tool(object);
                             debug!("Picked up tool {:?} at {:?}", object, task.pos);
                         }
 
+                        // Tools fetched from a stockpile are swapped with the one in hand
+                        (ObjectSlot::Tool { .. }, Some(old_tool), _)
+                            if task_index
+                                .at(task.pos)
+                                .any(|other| other.kind == TaskKind::Stockpile) =>
+                        {
+                            tilemap_data.set(task.pos, tile.id.with(old_tool));
+                            dweller.equip_tool(object);
+                            debug!(
+                                "Swapped tool {:?} for {:?} at {:?}",
+                                old_tool, object, task.pos
+                            );
+                        }
+
                         (ObjectSlot::Armor(_), _, None) => {
                             dweller.armor = Some(object);
                             debug!("Picked up armor {:?} at {:?}", object, task.pos);
                         }
 
                         _ => {
-                            dweller.object = Some(object);
+                            dweller.carry(object, tile.spoilage);
                             debug!("Picked up object {:?} at {:?}", object, task.pos);
                         }
                     }
@@ -586,23 +739,36 @@ pub fn event_task_completion(
                 } else {
                     match result {
                         BuildResult::Object(object) => {
-                            tilemap_data.set(task.pos, tile.id.with(object));
+                            // The other end of stairs may have been taken since the task was placed
+                            if matches!(object, ObjectId::StairsDown | ObjectId::StairsUp)
+                                && tilemap_data
+                                    .stairs_free_link(task.pos, object)
+                                    .is_none_or(|link| task_index.has_task_at(link))
+                            {
+                                debug!("Stairs at {:?} lead nowhere, cancelling", task.pos);
+                                commands.entity(entity).despawn();
+                                continue;
+                            }
 
-                            if let Some(workstation) = WORKSTATIONS.get(&object) {
+                            tilemap_data.set(task.pos, result.built_tile(tile));
+                            tilemap_data.connect_stairs(task.pos);
+
+                            if let Some(recipe) = workstation_recipe(object, 0) {
                                 commands.spawn(TaskBundle::new(
                                     Task::new(
                                         task.pos,
                                         TaskKind::Workstation {
                                             amount: WorkstationAmount::Finite(1),
+                                            recipe: 0,
                                         },
                                         None,
                                     ),
-                                    TaskNeeds::Objects(workstation.1.clone()),
+                                    TaskNeeds::Objects(recipe.1.clone()),
                                 ));
                             }
                         }
-                        BuildResult::Tile(tile) => {
-                            tilemap_data.set(task.pos, tile.place());
+                        BuildResult::Tile(_) => {
+                            tilemap_data.set(task.pos, result.built_tile(tile));
                         }
                     }
                 }
@@ -616,28 +782,40 @@ pub fn event_task_completion(
             }
 
             TaskKind::Attack => {
-                if let Some((entity_mob, mut mob, mob_transform)) = task_child_of
+                if let Some((entity_mob, mut mob, mob_transform, mob_layer)) = task_child_of
                     .map(ChildOf::parent)
                     .and_then(|parent| q_mobs.get_mut(parent).ok())
                 {
-                    let mob_pos = transform_to_pos(mob_transform);
+                    let mob_pos = transform_to_pos(mob_transform, mob_layer);
 
-                    if dweller_pos.distance_squared(mob_pos) <= 1 {
+                    if dweller.can_hit(dweller_pos, mob_pos, &tilemap_data) {
                         //FIXME: could be triggered twice if multiple dwellers attack the same mob at the same time
 
-                        // Damage the mob
-                        let damage = dweller.armor.map_or(1, |t| match t.data().slot() {
-                            ObjectSlot::Tool(damage) => *damage,
-                            _ => 1,
-                        });
-                        mob.health(-(damage as i32));
-                        commands.entity(entity_mob).try_insert(TakingDamage::new());
+                        // Wait for the weapon to be ready
+                        if dweller.attack_cooldown > 0 {
+                            continue;
+                        }
+                        dweller.attack_cooldown = dweller.weapon().cooldown;
+
+                        if roll_hit(&mut rng, dweller.accuracy(), mob.id.data().dodge) {
+                            let damage = dweller.damage();
+                            mob.health(-(damage as i32));
+                            commands.entity(entity_mob).try_insert(TakingDamage::new());
+                            train_skill(&mut rng, &mut dweller.combat_skill);
+
+                            combat_log
+                                .push(format!("{} hits {:?} for {damage}", dweller.name, mob.id));
+                        } else {
+                            combat_log.push(format!("{} misses {:?}", dweller.name, mob.id));
+                        }
+
+                        if dweller.tool_category() == Some(ToolCategory::Weapon) {
+                            dweller.wear_tool();
+                        }
 
                         dweller.sleep(-5);
                         dweller.food(-5);
 
-                        debug!("Attacked mob at {:?}", mob_pos);
-
                         // If the mob is dead, drop loot and despawn
                         if mob.health == 0 {
                             if let Some(loot_tile) = tilemap_data.get(mob_pos) {
@@ -656,12 +834,38 @@ pub fn event_task_completion(
 
                             commands.entity(entity_mob).try_despawn();
 
-                            debug!("Killed mob at {:?}", mob_transform.translation);
+                            combat_log.push(format!("{} kills {:?}", dweller.name, mob.id));
                             success = true;
                         }
                     } else {
                         task.pos = mob_pos;
                         task.recompute_reachable_positions(&tilemap_data);
+                        task_index.refresh(entity, &task);
+                    }
+                } else {
+                    error!("SHOULD NEVER HAPPEN: {task:?} has no parent entity");
+                    success = true; // Remove the task anyway
+                }
+            }
+
+            TaskKind::Recruit => {
+                if let Some((entity_mob, _, mob_transform, mob_layer)) = task_child_of
+                    .map(ChildOf::parent)
+                    .and_then(|parent| q_mobs.get(parent).ok())
+                {
+                    let mob_pos = transform_to_pos(mob_transform, mob_layer);
+
+                    if task.reachable_positions.contains(&dweller_pos) {
+                        // The wanderer joins the colony where they stand
+                        commands.entity(entity_mob).try_despawn();
+                        spawn_dweller(&mut commands, Dweller::new(Dweller::random_name()), mob_pos);
+
+                        event_log.push(format!("{} recruited a wanderer", dweller.name));
+                        success = true;
+                    } else {
+                        task.pos = mob_pos;
+                        task.recompute_reachable_positions(&tilemap_data);
+                        task_index.refresh(entity, &task);
                     }
                 } else {
                     error!("SHOULD NEVER HAPPEN: {task:?} has no parent entity");
@@ -672,7 +876,7 @@ pub fn event_task_completion(
             TaskKind::Fish => {
                 //TODO: more fishing loot
                 if dweller.object.is_none() {
-                    dweller.object = Some(ObjectId::Fish);
+                    dweller.carry(ObjectId::Fish, 0);
                 } else if let Some(tile) = tilemap_data.get(dweller_pos)
                     && tile.is_floor_free()
                 {
@@ -695,7 +899,13 @@ pub fn event_task_completion(
                 if tile.object.is_none()
                     && let Some(object) = dweller.object
                 {
-                    tilemap_data.set(task.pos, tile.id.with(object));
+                    tilemap_data.set(
+                        task.pos,
+                        TilePlaced {
+                            spoilage: dweller.object_spoilage,
+                            ..tile.id.with(object)
+                        },
+                    );
 
                     debug!("Stockpiled object at {:?}", task.pos);
                     update_tasks_pos = true;
@@ -703,11 +913,14 @@ pub fn event_task_completion(
                 }
             }
 
-            TaskKind::Workstation { .. } => {
-                if let Some(recipe) = tile.object.and_then(|object| WORKSTATIONS.get(&object)) {
+            TaskKind::Workstation { recipe, .. } => {
+                if let Some(recipe) = tile
+                    .object
+                    .and_then(|object| workstation_recipe(object, recipe))
+                {
                     if about_to_finish {
                         for (pos, tile) in tilemap_data.neighbours(task.pos) {
-                            if tile.is_floor_free() && !tasks_positions.contains(&pos) {
+                            if tile.is_floor_free() && !task_index.has_task_at(pos) {
                                 tilemap_data.set(pos, tile.id.with(recipe.0));
 
                                 if recipe.0.data().is_carriable() {
@@ -737,21 +950,50 @@ pub fn event_task_completion(
             }
 
             TaskKind::Eat => {
-                if let Some(value) = tile.object.and_then(|object| EAT_VALUES.get(&object)) {
+                if let Some(object) = tile.object
+                    && let Some(value) = EAT_VALUES.get(&object)
+                {
                     tilemap_data.set(task.pos, tile.id.place());
-                    dweller.food(*value);
+                    dweller.eat(object, tile.spoilage);
 
                     debug!("Ate {:?}", value);
                     success = true;
                 }
             }
 
+            TaskKind::Drink => {
+                // The water may have drained or the drink been taken since the task was placed,
+                // the dweller looks for another one on the next needs tick
+                if !TaskKind::Drink.is_valid_on_tile(tile) {
+                    debug!("Nothing left to drink at {:?}, cancelling", task.pos);
+                    commands.entity(entity).despawn();
+                    continue;
+                }
+
+                if let Some(object) = tile.object
+                    && DRINK_VALUES.contains_key(&object)
+                {
+                    tilemap_data.set(task.pos, tile.id.place());
+                    dweller.drink(object);
+                } else {
+                    dweller.thirst(WATER_DRINK_VALUE);
+                }
+
+                debug!("Drank at {:?}", task.pos);
+                success = true;
+            }
+
             TaskKind::Sleep => {
-                if let Some(value) = tile.object.and_then(|object| SLEEP_VALUES.get(&object)) {
+                if let Some(object) = tile.object
+                    && let Some(value) = SLEEP_VALUES.get(&object)
+                {
                     dweller.sleep(*value);
 
                     debug!("Zzzzz {:?}", value);
                     if dweller.is_fully_rested() {
+                        if let Some(thought) = Thought::of_sleep(object) {
+                            dweller.think(thought);
+                        }
                         success = true;
                     }
                 }
@@ -760,12 +1002,15 @@ pub fn event_task_completion(
             TaskKind::Scoop => {
                 if dweller.object.is_none() {
                     match tile.id {
-                        TileId::ShallowWater | TileId::Water => {
-                            dweller.object = Some(if rng.random_bool(0.001) {
-                                ObjectId::Fish
-                            } else {
-                                ObjectId::WaterBucket
-                            });
+                        _ if tile.has_water() => {
+                            dweller.carry(
+                                if rng.random_bool(0.001) {
+                                    ObjectId::Fish
+                                } else {
+                                    ObjectId::WaterBucket
+                                },
+                                0,
+                            );
                             dweller.sleep(-5);
                             debug!("Scooped water at {:?}", task.pos);
                         }
@@ -779,11 +1024,136 @@ pub fn event_task_completion(
                     success = true;
                 }
             }
+
+            TaskKind::Deconstruct => {
+                if let Some((result, cost)) = BuildResult::recipe_on_tile(tile) {
+                    // Stairs are removed at both ends
+                    if let Some(link) = tilemap_data.stairs_destination(task.pos)
+                        && let Some(link_tile) = tilemap_data.get(link)
+                    {
+                        tilemap_data.set(link, link_tile.id.place());
+                    }
+
+                    tilemap_data.set(task.pos, result.deconstructed_tile(tile));
+
+                    // Refund half of the cost, rounded up
+                    let mut refund = cost.iter().take(cost.len().div_ceil(2));
+
+                    let drop_positions = std::iter::once(task.pos)
+                        .chain(
+                            tilemap_data
+                                .neighbours(task.pos)
+                                .into_iter()
+                                .map(|(pos, _)| pos),
+                        )
+                        .collect::<Vec<_>>();
+
+                    for pos in drop_positions {
+                        let Some(tile) = tilemap_data.get(pos) else {
+                            continue;
+                        };
+                        if !tile.is_floor_free() || (pos != task.pos && task_index.has_task_at(pos))
+                        {
+                            continue;
+                        }
+                        let Some(object) = refund.next() else {
+                            break;
+                        };
+
+                        tilemap_data.set(pos, tile.id.with(*object));
+                        commands.spawn(TaskBundle::new(
+                            Task::new(pos, TaskKind::Pickup, None),
+                            TaskNeeds::EmptyHands,
+                        ));
+                    }
+
+                    // Remove the linked Workstation task
+                    for other in task_index.at(task.pos) {
+                        if matches!(other.kind, TaskKind::Workstation { .. }) {
+                            commands.entity(other.entity).try_despawn();
+                        }
+                    }
+
+                    dweller.sleep(-3);
+
+                    debug!("Deconstructed {:?} at {:?}", result, task.pos);
+                    update_tasks_pos = true;
+                    success = true;
+                }
+            }
+
+            TaskKind::Extinguish => {
+                tilemap_data.set(task.pos, TilePlaced { fire: 0, ..tile });
+
+                dweller.sleep(-2);
+
+                debug!("Extinguished fire at {:?}", task.pos);
+                success = true;
+            }
+
+            TaskKind::Rescue => {
+                let bed = TilemapData::find_from_center_chunk_size(task.pos, |pos| {
+                    tilemap_data
+                        .get(pos)
+                        .is_some_and(|tile| TaskKind::CarryToBed.is_valid_on_tile(tile))
+                        && task_index.can_place(pos, TaskKind::CarryToBed)
+                });
+
+                if let (Some(rescued), Some(bed)) = (rescued, bed) {
+                    dweller.carried = Some(rescued);
+                    commands.spawn(TaskBundle::new(
+                        Task::new(bed, TaskKind::CarryToBed, Some(dweller.uuid)),
+                        TaskNeeds::Nothing,
+                    ));
+                    debug!("Carrying downed dweller at {:?} to {:?}", task.pos, bed);
+                } else {
+                    debug!("No bed to carry the downed dweller at {:?} to", task.pos);
+                }
+                success = true;
+            }
+
+            TaskKind::CarryToBed => {
+                dweller.carried = None;
+
+                debug!("Carried downed dweller to bed at {:?}", task.pos);
+                success = true;
+            }
+
+            TaskKind::Bury => {
+                tilemap_data.set(task.pos, tile.id.with(ObjectId::Grave));
+
+                dweller.sleep(-3);
+
+                debug!("Buried corpse at {:?}", task.pos);
+                success = true;
+            }
+
+            TaskKind::Tend => {
+                // The bandage or medicine is consumed with the TaskNeeds
+                if let Some(patient) = patient
+                    && let Some(value) = dweller
+                        .object
+                        .and_then(|object| MEDICINE_VALUES.get(&object))
+                {
+                    tended.push((patient, *value));
+                }
+
+                debug!("Tended dweller at {:?}", task.pos);
+                success = true;
+            }
         }
 
         if success {
             let mut remove_task = true;
 
+            // Wear the tool used for the task, weapons wear on each hit instead
+            if task.kind != TaskKind::Attack
+                && task.kind.tool().is_some()
+                && dweller.tool_category() == task.kind.tool()
+            {
+                dweller.wear_tool();
+            }
+
             match *task_needs {
                 TaskNeeds::Objects(ref mut objects) => {
                     if let Some(dweller_object) = dweller.object {
@@ -835,7 +1205,7 @@ pub fn event_task_completion(
                     );
                 }
 
-                TaskNeeds::Nothing | TaskNeeds::EmptyHands => {}
+                TaskNeeds::Nothing | TaskNeeds::EmptyHands | TaskNeeds::Tool(..) => {}
             }
 
             // Do not remove permanent tasks
@@ -845,8 +1215,14 @@ pub fn event_task_completion(
                     remove_task = false;
                 }
 
-                TaskKind::Workstation { ref mut amount } if remove_task => {
-                    if let Some(recipe) = tile.object.and_then(|object| WORKSTATIONS.get(&object)) {
+                TaskKind::Workstation {
+                    ref mut amount,
+                    recipe,
+                } if remove_task => {
+                    if let Some(recipe) = tile
+                        .object
+                        .and_then(|object| workstation_recipe(object, recipe))
+                    {
                         amount.decrease();
                         *task_needs = TaskNeeds::Objects(recipe.1.clone());
                     }
@@ -860,10 +1236,17 @@ pub fn event_task_completion(
                 commands.entity(entity).try_despawn();
             } else {
                 task.dweller_id = None;
+                task_index.refresh(entity, &task);
             }
         }
     }
 
+    for (patient, value) in tended {
+        if let Ok((mut dweller, _, _)) = q_dwellers.get_mut(patient) {
+            dweller.health(value as i32);
+        }
+    }
+
     if update_tasks_pos {
         for (_, mut task, _, _) in &mut q_tasks {
             task.recompute_reachable_positions(&tilemap_data);
@@ -872,11 +1255,11 @@ pub fn event_task_completion(
 
     // Set Stockpile tasks to AnyObject if there is nothing on the tile
     if update_stockpiles {
-        for (_, task, mut task_needs, _) in &mut q_tasks {
-            if matches!(task.kind, TaskKind::Stockpile)
-                && tilemap_data
-                    .get(task.pos)
-                    .is_some_and(TilePlaced::is_floor_free)
+        for stockpile in task_index.of_kind(TaskKind::Stockpile) {
+            if tilemap_data
+                .get(stockpile.pos)
+                .is_some_and(TilePlaced::is_floor_free)
+                && let Ok((_, _, mut task_needs, _)) = q_tasks.get_mut(stockpile.entity)
             {
                 *task_needs = TaskNeeds::AnyObject;
             }
@@ -885,13 +1268,15 @@ pub fn event_task_completion(
 
     // Remove Workstation tasks if the workstation is gone
     if update_workstations {
-        for (entity, task, _, _) in &q_tasks {
-            if matches!(task.kind, TaskKind::Workstation { .. })
-                && tilemap_data
-                    .get(task.pos)
-                    .is_some_and(TilePlaced::is_floor_free)
+        for workstation in task_index.of_kind(TaskKind::Workstation {
+            amount: WorkstationAmount::Infinite,
+            recipe: 0,
+        }) {
+            if tilemap_data
+                .get(workstation.pos)
+                .is_some_and(TilePlaced::is_floor_free)
             {
-                commands.entity(entity).despawn();
+                commands.entity(workstation.entity).try_despawn();
             }
         }
     }
@@ -900,6 +1285,7 @@ pub fn event_task_completion(
 pub fn update_pickups(
     par_commands: ParallelCommands,
     tilemap_data: Res<TilemapData>,
+    task_index: Res<TaskIndex>,
     q_tasks: Query<(Ref<Task>, Ref<TaskNeeds>)>,
     q_dwellers: Query<&Dweller>,
 ) {
@@ -956,11 +1342,13 @@ pub fn update_pickups(
         }
 
         if task.dweller_id.is_some()
+            || task_index.is_blocked(&task)
             || matches!(
                 task.kind,
                 TaskKind::Stockpile
                     | TaskKind::Workstation {
-                        amount: WorkstationAmount::Finite(0)
+                        amount: WorkstationAmount::Finite(0),
+                        ..
                     }
             )
         {
@@ -1043,3 +1431,62 @@ pub fn update_pickups(
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_built_tiles_are_deconstructed() {
+        let grass = TileId::GrassFloor.place();
+        assert!(BuildResult::recipe_on_tile(grass).is_none());
+        assert!(BuildResult::recipe_on_tile(TileId::WoodFloor.with(ObjectId::Bush)).is_none());
+        assert!(BuildResult::recipe_on_tile(TileId::DungeonWall.place()).is_none());
+
+        let floor = BuildResult::Tile(TileId::WoodFloor).built_tile(grass);
+        assert_eq!(
+            BuildResult::recipe_on_tile(floor).map(|(result, _)| result),
+            Some(BuildResult::Tile(TileId::WoodFloor))
+        );
+        assert_eq!(
+            BuildResult::Tile(TileId::WoodFloor)
+                .deconstructed_tile(floor)
+                .id,
+            TileId::GrassFloor
+        );
+
+        // The object goes first, the floor stays built
+        let bed = BuildResult::Object(ObjectId::Bed).built_tile(floor);
+        assert_eq!(
+            BuildResult::recipe_on_tile(bed).map(|(result, _)| result),
+            Some(BuildResult::Object(ObjectId::Bed))
+        );
+    }
+
+    #[test]
+    fn built_state_survives_tilemap_updates() {
+        let mut tilemap_data = TilemapData::default();
+        let pos = IVec3::new(1, 1, 0);
+        tilemap_data.set(pos, TileId::Water.place());
+        tilemap_data.set(
+            pos,
+            BuildResult::Tile(TileId::Bridge).built_tile(TileId::Water.place()),
+        );
+
+        let bridge = tilemap_data.get(pos).unwrap();
+        tilemap_data.set(pos, BuildResult::Object(ObjectId::Farm).built_tile(bridge));
+        tilemap_data.set(pos, bridge.id.with(ObjectId::WheatPlant));
+        let tile = tilemap_data.get(pos).unwrap();
+        assert!(tile.built_object);
+        assert_eq!(tile.built_over, Some(TileId::Water));
+
+        tilemap_data.set(pos, bridge.id.place());
+        assert!(!tilemap_data.get(pos).unwrap().built_object);
+        assert_eq!(
+            BuildResult::Tile(TileId::Bridge)
+                .deconstructed_tile(tilemap_data.get(pos).unwrap())
+                .id,
+            TileId::Water
+        );
+    }
+}
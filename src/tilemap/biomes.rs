@@ -0,0 +1,93 @@
+use bevy::prelude::*;
+use noise::{NoiseFn, Simplex};
+
+use crate::{
+    CHUNK_SIZE,
+    data::{BiomeId, MobId, ObjectId, TileId},
+};
+
+const CLIMATE_SCALE: f64 = 0.01;
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Climate {
+    Temperate,
+    Desert,
+}
+
+impl Climate {
+    pub const ALL: [Climate; 2] = [Climate::Temperate, Climate::Desert];
+}
+
+pub struct BiomeData {
+    // Position in the (temperature, humidity) noise space, the closest biome is picked
+    pub temperature: f64,
+    pub humidity: f64,
+    pub climate: Climate,
+    pub ground: TileId,
+    pub pool: Option<TileId>,
+    // Vegetation grows in zones where the noise is above this threshold, lower is denser
+    pub vegetation_threshold: f64,
+    pub trees: &'static [(ObjectId, u32)],
+    pub plants: &'static [(ObjectId, u32)],
+    // Ores of the mountain veins
+    pub ores: &'static [(ObjectId, u32)],
+    // Mobs spawned in herds of up to this size
+    pub mobs: &'static [(MobId, u32)],
+}
+
+impl BiomeData {
+    // Weighted pick with roll between 0 and 1
+    pub fn pick(table: &[(ObjectId, u32)], roll: f64) -> Option<ObjectId> {
+        let total = table.iter().map(|(_, weight)| weight).sum::<u32>();
+        let mut target = (roll.clamp(0.0, 1.0) * total as f64) as u32;
+
+        for (object, weight) in table {
+            if target < *weight {
+                return Some(*object);
+            }
+            target -= weight;
+        }
+
+        table.last().map(|(object, _)| *object)
+    }
+}
+
+pub struct BiomeNoise {
+    temperature: Simplex,
+    humidity: Simplex,
+}
+
+impl BiomeNoise {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            temperature: Simplex::new(seed),
+            humidity: Simplex::new(seed + 2),
+        }
+    }
+
+    pub fn biome_at(&self, pos: IVec2) -> BiomeId {
+        let point = [pos.x as f64 * CLIMATE_SCALE, pos.y as f64 * CLIMATE_SCALE];
+        let temperature = self.temperature.get(point);
+        let humidity = self.humidity.get(point);
+
+        let distance = |biome: &BiomeId| {
+            let data = biome.data();
+            (data.temperature - temperature).powi(2) + (data.humidity - humidity).powi(2)
+        };
+
+        BiomeId::ALL
+            .iter()
+            .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+impl BiomeId {
+    // Biome at the chunk center
+    // Biomes only exist on the surface, chunk_pos is the (x, y) of a surface chunk
+    pub fn of_chunk(seed: u32, chunk_pos: IVec2) -> Self {
+        let center = chunk_pos * CHUNK_SIZE as i32 + IVec2::splat(CHUNK_SIZE as i32 / 2);
+        BiomeNoise::new(seed).biome_at(center)
+    }
+}
@@ -4,50 +4,46 @@ use bevy::{
 };
 
 use crate::{
-    SaveName, SpawnDwellersOnChunk, TilePlaced, TilemapData,
+    Layer, MIN_LAYER, NewGame, SaveName, TilePlaced, TilemapData, WorldGenSettings,
+    data::BiomeId,
     dwellers::Dweller,
-    generate_terrain, init_tilemap,
+    generate_terrain,
     mobs::Mob,
     random_text::{WORLD_NAMES, generate_word},
     sprites::SpriteLoader,
     tasks::{Task, TaskNeeds},
-    tilemap::{CHUNK_SIZE, TILE_SIZE, Weather},
+    tilemap::{CHUNK_SIZE, TILE_SIZE},
     utils::{transform_to_pos, write_to_file},
 };
 
 const LOAD_CHUNKS_RADIUS: i32 = 1;
 
 #[derive(Message)]
-pub struct LoadChunk(pub IVec2);
+pub struct LoadChunk(pub IVec3);
 
 #[derive(Message)]
-pub struct SaveChunk(pub IVec2, pub bool); // bool: despawn after save
+pub struct SaveChunk(pub IVec3, pub bool); // bool: despawn after save
 
 #[derive(Resource, Default, Reflect)]
 #[reflect(Resource)]
-pub struct ChunksWithDwellers(pub HashSet<IVec2>);
+pub struct ChunksWithDwellers(pub HashSet<IVec3>);
 
-pub fn spawn_new_terrain(
-    mut commands: Commands,
-    mut ev_load_chunk: MessageWriter<LoadChunk>,
-    mut ev_spawn_dwellers: MessageWriter<SpawnDwellersOnChunk>,
-) {
+pub fn random_world_name() -> String {
     let mut rng = rand::rng();
-    let name = (0..2)
+    (0..2)
         .map(|_| {
             let mut word = generate_word(&WORLD_NAMES, &mut rng);
             word.get_mut(0..1).unwrap().make_ascii_uppercase();
             word
         })
-        .collect::<String>();
-
-    let save_name = SaveName(name);
-    commands.insert_resource(Weather::new(save_name.seed()));
-    commands.insert_resource(ChunksWithDwellers::default());
-    init_tilemap(&mut commands, save_name);
+        .collect()
+}
 
-    ev_load_chunk.write(LoadChunk(IVec2::ZERO));
-    ev_spawn_dwellers.write(SpawnDwellersOnChunk(IVec2::ZERO));
+pub fn spawn_new_terrain(mut commands: Commands) {
+    commands.trigger(NewGame {
+        name: random_world_name(),
+        settings: WorldGenSettings::load(),
+    });
 }
 
 pub fn load_chunks(
@@ -57,8 +53,12 @@ pub fn load_chunks(
     mut ev_load: MessageReader<LoadChunk>,
     mut ev_save: MessageReader<SaveChunk>,
     save_name: Res<SaveName>,
+    world_gen_settings: Res<WorldGenSettings>,
     mut tilemap_data: ResMut<TilemapData>,
-    q_save_entities: Query<(Entity, &Transform), Or<(With<Mob>, With<Task>, With<Dweller>)>>,
+    q_save_entities: Query<
+        (Entity, &Transform, &Layer),
+        Or<(With<Mob>, With<Task>, With<Dweller>)>,
+    >,
 ) {
     for LoadChunk(chunk_pos) in ev_load.read() {
         if tilemap_data.chunks.contains_key(chunk_pos) {
@@ -69,10 +69,15 @@ pub fn load_chunks(
         let path_ron = path_ron.trim_start_matches("assets/").to_string();
 
         // Try to load the chunk from the save
-        if let Some(chunk_data) = std::fs::read(path_bin)
-            .ok()
-            .and_then(|data| bitcode::decode::<Vec<TilePlaced>>(&data).ok())
-        {
+        let saved = std::fs::read(&path_bin).ok();
+        let decoded = saved
+            .as_ref()
+            .and_then(|data| bitcode::decode::<Vec<TilePlaced>>(data).ok());
+        if saved.is_some() && decoded.is_none() {
+            error!("Chunk {chunk_pos} in {path_bin} can't be decoded, generating it again");
+        }
+
+        if let Some(chunk_data) = decoded {
             debug!("Loading chunk {} from save file", chunk_pos);
 
             // Load in TilemapData
@@ -85,9 +90,22 @@ pub fn load_chunks(
 
             debug!("Generating chunk {}", chunk_pos);
 
-            let chunk_data = generate_terrain(&mut commands, save_name.seed(), *chunk_pos);
+            let chunk_data = generate_terrain(
+                &mut commands,
+                &world_gen_settings,
+                save_name.seed(),
+                *chunk_pos,
+            );
             tilemap_data.set_chunk(*chunk_pos, chunk_data);
         }
+
+        // The biome only depends on the seed, no need to save it
+        if chunk_pos.z == 0 {
+            tilemap_data.biomes.insert(
+                *chunk_pos,
+                BiomeId::of_chunk(save_name.seed(), chunk_pos.xy()),
+            );
+        }
     }
 
     for SaveChunk(chunk_pos, despawn) in ev_save.read() {
@@ -101,20 +119,22 @@ pub fn load_chunks(
         let (path_bin, path_ron) = save_name.chunk_paths(*chunk_pos);
 
         let chunk_encoded = bitcode::encode(chunk);
+        save_name.write_version();
 
-        let chunk_min = chunk_pos.as_vec2() * CHUNK_SIZE as f32 * TILE_SIZE;
+        let chunk_min = chunk_pos.xy().as_vec2() * CHUNK_SIZE as f32 * TILE_SIZE;
         let chunk_max = chunk_min + Vec2::splat(CHUNK_SIZE as f32 * TILE_SIZE);
 
         let entities = q_save_entities
             .iter()
-            .filter(|(_, transform)| {
+            .filter(|(_, transform, layer)| {
                 let pos = transform.translation.truncate();
-                pos.x >= chunk_min.x
+                layer.0 == chunk_pos.z
+                    && pos.x >= chunk_min.x
                     && pos.x < chunk_max.x
                     && pos.y >= chunk_min.y
                     && pos.y < chunk_max.y
             })
-            .map(|(entity, _)| entity)
+            .map(|(entity, _, _)| entity)
             .collect::<Vec<_>>();
 
         // NOTE: we save Children components referencing entities that are not saved.
@@ -134,6 +154,7 @@ pub fn load_chunks(
                 .allow_component::<Task>()
                 .allow_component::<TaskNeeds>()
                 .allow_component::<SpriteLoader>()
+                .allow_component::<Layer>()
                 .allow_component::<Transform>()
                 .allow_component::<GlobalTransform>()
                 .allow_component::<Children>()
@@ -182,21 +203,22 @@ pub fn chunks_with_dwellers_is_added(
 }
 
 pub fn dwellers_load_chunks(
-    q_dwellers: Query<&Transform, With<Dweller>>,
+    q_dwellers: Query<(&Transform, &Layer), With<Dweller>>,
     tilemap_data: Res<TilemapData>,
     mut ev_load_chunk: MessageWriter<LoadChunk>,
     mut ev_unload_chunk: MessageWriter<SaveChunk>,
-    mut chunks_ttl: Local<HashMap<IVec2, u32>>,
+    mut chunks_ttl: Local<HashMap<IVec3, u32>>,
     mut chunks_with_dwellers: ResMut<ChunksWithDwellers>,
 ) {
     if q_dwellers.is_empty() {
+        chunks_with_dwellers.0.clear();
         return;
     }
     // Update ChunksWithDwellers
     chunks_with_dwellers.0 = q_dwellers
         .iter()
-        .map(|transform| {
-            let pos = transform_to_pos(transform);
+        .map(|(transform, layer)| {
+            let pos = transform_to_pos(transform, layer);
             let (chunk_pos, _) = TilemapData::pos_to_chunk_pos_and_local_index(pos);
             chunk_pos
         })
@@ -205,14 +227,20 @@ pub fn dwellers_load_chunks(
     // Track which chunks have been loaded
     let mut chunks_just_loaded = HashSet::new();
 
-    // Load chunks around dwellers
+    // Load chunks around dwellers, and on the layers above and below so stairs lead somewhere
     for chunk_pos in &chunks_with_dwellers.0 {
-        for dx in -LOAD_CHUNKS_RADIUS..=LOAD_CHUNKS_RADIUS {
-            for dy in -LOAD_CHUNKS_RADIUS..=LOAD_CHUNKS_RADIUS {
-                let neigh_chunk_pos = chunk_pos + IVec2::new(dx, dy);
-                ev_load_chunk.write(LoadChunk(neigh_chunk_pos));
-                chunks_ttl.insert(neigh_chunk_pos, 10);
-                chunks_just_loaded.insert(neigh_chunk_pos);
+        for dz in -1..=1 {
+            if !(MIN_LAYER..=0).contains(&(chunk_pos.z + dz)) {
+                continue;
+            }
+
+            for dx in -LOAD_CHUNKS_RADIUS..=LOAD_CHUNKS_RADIUS {
+                for dy in -LOAD_CHUNKS_RADIUS..=LOAD_CHUNKS_RADIUS {
+                    let neigh_chunk_pos = chunk_pos + IVec3::new(dx, dy, dz);
+                    ev_load_chunk.write(LoadChunk(neigh_chunk_pos));
+                    chunks_ttl.insert(neigh_chunk_pos, 10);
+                    chunks_just_loaded.insert(neigh_chunk_pos);
+                }
             }
         }
     }
@@ -0,0 +1,313 @@
+use bevy::{platform::collections::HashSet, prelude::*};
+
+use crate::{TilePlaced, TilemapData, data::TileId};
+
+// Flowing fluids have a level from 1 to FLUID_MAX and drain when nothing feeds them.
+// Sources (rivers, lava lakes, flooded tiles) never drain.
+// Flowing water keeps the floor under it, flowing lava melts it.
+pub const FLUID_MAX: u8 = 7;
+pub const FLUID_SOURCE: u8 = u8::MAX;
+
+const WATER_FALLOFF: u8 = 1;
+const LAVA_FALLOFF: u8 = 3;
+// Lava only flows once every LAVA_SLOWNESS ticks
+const LAVA_SLOWNESS: u32 = 4;
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+enum Fluid {
+    Water,
+    Lava,
+}
+
+impl Fluid {
+    fn of(tile: TilePlaced) -> Option<Self> {
+        if tile.fluid == 0 {
+            return None;
+        }
+
+        match tile.id {
+            TileId::Lava => Some(Fluid::Lava),
+            _ => Some(Fluid::Water),
+        }
+    }
+
+    fn falloff(self) -> u8 {
+        match self {
+            Fluid::Water => WATER_FALLOFF,
+            Fluid::Lava => LAVA_FALLOFF,
+        }
+    }
+
+    fn flowing_tile(self, tile: TilePlaced, level: u8) -> TilePlaced {
+        match self {
+            Fluid::Water => TilePlaced {
+                fluid: level,
+                fire: 0,
+                ..tile
+            },
+            // Lava burns objects
+            Fluid::Lava => TilePlaced {
+                id: TileId::Lava,
+                object: None,
+                fluid: level,
+                fire: 0,
+                built_over: None,
+                ..tile
+            },
+        }
+    }
+
+    fn drained_tile(self, tile: TilePlaced) -> TilePlaced {
+        match self {
+            Fluid::Water => TilePlaced { fluid: 0, ..tile },
+            Fluid::Lava => TileId::StoneFloor.place(),
+        }
+    }
+}
+
+impl TilePlaced {
+    // Dug ground and built floors hold fluids, soil soaks them up
+    pub fn can_hold_fluid(self) -> bool {
+        matches!(
+            self.id,
+            TileId::StoneFloor | TileId::DungeonFloor | TileId::WoodFloor
+        ) && self
+            .object
+            .is_none_or(|object| !object.data().is_blocking())
+    }
+
+    // Water tiles, and floors flooded by flowing water
+    pub fn has_water(self) -> bool {
+        Fluid::of(self) == Some(Fluid::Water)
+    }
+
+    // Flooded floors are drawn as shallow water
+    pub fn displayed_id(self) -> TileId {
+        if self.has_water() && !self.id.is_fluid() {
+            TileId::ShallowWater
+        } else {
+            self.id
+        }
+    }
+
+    fn fluid_level(self) -> u8 {
+        if self.fluid == FLUID_SOURCE {
+            FLUID_MAX + 1
+        } else {
+            self.fluid
+        }
+    }
+}
+
+fn next_tile(
+    tilemap_data: &TilemapData,
+    pos: IVec3,
+    tile: TilePlaced,
+    lava_tick: bool,
+) -> Option<TilePlaced> {
+    let neighbours = tilemap_data.neighbours(pos);
+    let current = Fluid::of(tile);
+
+    // Flowing lava touching water cools down into stone, generated lava lakes are kept
+    if current == Some(Fluid::Lava)
+        && tile.fluid != FLUID_SOURCE
+        && neighbours
+            .iter()
+            .any(|(_, other)| Fluid::of(*other) == Some(Fluid::Water))
+    {
+        return Some(TileId::StoneWall.place());
+    }
+
+    if tile.fluid == FLUID_SOURCE || (current == Some(Fluid::Lava) && !lava_tick) {
+        return None;
+    }
+
+    // Highest fluid level the neighbours can give to this tile
+    let fed = neighbours
+        .iter()
+        .filter_map(|(_, other)| {
+            let fluid = Fluid::of(*other)?;
+            if fluid == Fluid::Lava && !lava_tick {
+                return None;
+            }
+            Some((fluid, other.fluid_level().saturating_sub(fluid.falloff())))
+        })
+        .filter(|(_, level)| *level > 0)
+        .max_by_key(|(_, level)| *level);
+
+    match current {
+        Some(fluid) => {
+            let fed_level = fed
+                .filter(|(other, _)| *other == fluid)
+                .map_or(0, |(_, level)| level);
+
+            // Rise to the fed level, or drain one level at a time
+            let level = fed_level.max(tile.fluid - 1).min(FLUID_MAX);
+
+            if level == tile.fluid {
+                None
+            } else if level == 0 {
+                Some(fluid.drained_tile(tile))
+            } else {
+                Some(fluid.flowing_tile(tile, level))
+            }
+        }
+
+        None => {
+            if !tile.can_hold_fluid() {
+                return None;
+            }
+
+            let (fluid, level) = fed?;
+            Some(fluid.flowing_tile(tile, level.min(FLUID_MAX)))
+        }
+    }
+}
+
+pub fn update_fluids(mut tilemap_data: ResMut<TilemapData>, mut ticks: Local<u32>) {
+    *ticks = ticks.wrapping_add(1);
+    let lava_tick = ticks.is_multiple_of(LAVA_SLOWNESS);
+
+    // Chunks where fluids or the tiles holding them changed, with their loaded neighbours as
+    // fluids flow across borders. Unloaded chunks are never read nor written.
+    // Lava only flows on lava ticks, so changed chunks are kept until one
+    let changed = if lava_tick {
+        std::mem::take(&mut tilemap_data.fluid_chunks)
+    } else {
+        tilemap_data.fluid_chunks.clone()
+    };
+    let chunks = changed
+        .into_iter()
+        .flat_map(|chunk_pos| {
+            [IVec3::ZERO, IVec3::X, IVec3::Y, IVec3::NEG_X, IVec3::NEG_Y]
+                .map(|offset| chunk_pos + offset)
+        })
+        .filter(|chunk_pos| tilemap_data.chunks.contains_key(chunk_pos))
+        .collect::<HashSet<_>>();
+
+    // Compute all changes before applying them, so fluids flow one tile per tick
+    let changes = chunks
+        .into_iter()
+        .flat_map(TilemapData::iter_chunk_positions)
+        .filter_map(|pos| {
+            let tile = tilemap_data.get(pos)?;
+            if tile.fluid == 0 && !tile.can_hold_fluid() {
+                return None;
+            }
+
+            next_tile(&tilemap_data, pos, tile, lava_tick).map(|tile| (pos, tile))
+        })
+        .collect::<Vec<_>>();
+
+    for (pos, tile) in changes {
+        tilemap_data.set_fluid(pos, tile);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::ObjectId;
+
+    fn next_at(tilemap_data: &TilemapData, pos: IVec3, lava_tick: bool) -> Option<TilePlaced> {
+        next_tile(tilemap_data, pos, tilemap_data.get(pos)?, lava_tick)
+    }
+
+    #[test]
+    fn water_floods_floors_and_keeps_them() {
+        let mut tilemap_data = TilemapData::filled_chunk(IVec3::ZERO, TileId::WoodFloor.place());
+        let source = IVec3::new(5, 5, 0);
+        tilemap_data.set_fluid(source, TileId::Water.place());
+
+        let flooded = next_at(&tilemap_data, source + IVec3::X, false).unwrap();
+        assert_eq!(flooded.id, TileId::WoodFloor);
+        assert_eq!(flooded.fluid, FLUID_MAX);
+        assert!(flooded.has_water());
+        assert_eq!(flooded.displayed_id(), TileId::ShallowWater);
+
+        // Two tiles away, nothing flowed yet
+        assert!(next_at(&tilemap_data, source + IVec3::X * 2, false).is_none());
+        // Sources never change
+        assert!(next_at(&tilemap_data, source, false).is_none());
+    }
+
+    #[test]
+    fn unfed_water_drains_back_to_the_floor() {
+        let mut tilemap_data = TilemapData::filled_chunk(IVec3::ZERO, TileId::DungeonFloor.place());
+        let pos = IVec3::new(5, 5, 0);
+        tilemap_data.set_fluid(
+            pos,
+            TilePlaced {
+                fluid: 1,
+                ..TileId::DungeonFloor.place()
+            },
+        );
+
+        let drained = next_at(&tilemap_data, pos, false).unwrap();
+        assert_eq!(drained.id, TileId::DungeonFloor);
+        assert_eq!(drained.fluid, 0);
+        assert!(!drained.has_water());
+    }
+
+    #[test]
+    fn soil_soaks_fluids_up() {
+        let mut tilemap_data = TilemapData::filled_chunk(IVec3::ZERO, TileId::GrassFloor.place());
+        let source = IVec3::new(5, 5, 0);
+        tilemap_data.set_fluid(source, TileId::Water.place());
+
+        assert!(next_at(&tilemap_data, source + IVec3::X, false).is_none());
+    }
+
+    #[test]
+    fn lava_flows_slowly_and_melts_floors() {
+        let mut tilemap_data = TilemapData::filled_chunk(IVec3::ZERO, TileId::WoodFloor.place());
+        let source = IVec3::new(5, 5, 0);
+        tilemap_data.set_fluid(source, TileId::Lava.place());
+        tilemap_data.set(source + IVec3::X, TileId::WoodFloor.with(ObjectId::Bed));
+
+        assert!(next_at(&tilemap_data, source + IVec3::X, false).is_none());
+
+        let melted = next_at(&tilemap_data, source + IVec3::X, true).unwrap();
+        assert_eq!(melted.id, TileId::Lava);
+        assert_eq!(melted.object, None);
+        assert_eq!(melted.fluid, FLUID_MAX + 1 - LAVA_FALLOFF);
+    }
+
+    #[test]
+    fn only_flowing_lava_cools_next_to_water() {
+        let mut tilemap_data = TilemapData::filled_chunk(IVec3::ZERO, TileId::StoneFloor.place());
+        let lava = IVec3::new(5, 5, 0);
+        tilemap_data.set_fluid(lava, TileId::Lava.place());
+        tilemap_data.set_fluid(lava + IVec3::X, TileId::Water.place());
+
+        // Generated lava lakes next to rivers are kept
+        assert!(next_at(&tilemap_data, lava, true).is_none());
+
+        tilemap_data.set_fluid(
+            lava,
+            TilePlaced {
+                fluid: 3,
+                ..TileId::Lava.place()
+            },
+        );
+        assert_eq!(
+            next_at(&tilemap_data, lava, false).map(|tile| tile.id),
+            Some(TileId::StoneWall)
+        );
+    }
+
+    #[test]
+    fn only_fluid_changes_wake_fluids_up() {
+        let mut tilemap_data = TilemapData::filled_chunk(IVec3::ZERO, TileId::StoneFloor.place());
+        let pos = IVec3::new(5, 5, 0);
+        tilemap_data.fluid_chunks.clear();
+
+        // Fluids flow around passable objects
+        tilemap_data.set(pos, TileId::StoneFloor.with(ObjectId::Bed));
+        assert!(tilemap_data.fluid_chunks.is_empty());
+
+        // A wall dug out or built changes where fluids can flow
+        tilemap_data.set(pos, TileId::StoneWall.place());
+        assert!(tilemap_data.fluid_chunks.contains(&IVec3::ZERO));
+    }
+}
@@ -0,0 +1,82 @@
+use bevy::{platform::collections::HashSet, prelude::*};
+
+use crate::{
+    Layer, TilemapData, dwellers::Dweller, utils::transform_to_pos, world_stats::WorldStats,
+};
+
+const VISION_RADIUS: i32 = 12;
+
+// Bresenham line, the tiles between from and to must let the light through, layers block the sight
+pub fn has_line_of_sight(tilemap_data: &TilemapData, from: IVec3, to: IVec3) -> bool {
+    if from.z != to.z {
+        return false;
+    }
+
+    let delta = (to - from).abs();
+    let step = (to - from).signum();
+    let mut error = delta.x - delta.y;
+    let mut pos = from;
+
+    loop {
+        let double_error = 2 * error;
+        if double_error > -delta.y {
+            error -= delta.y;
+            pos.x += step.x;
+        }
+        if double_error < delta.x {
+            error += delta.x;
+            pos.y += step.y;
+        }
+
+        if pos == to {
+            return true;
+        }
+
+        if tilemap_data
+            .get(pos)
+            .is_none_or(|tile| !tile.id.is_transparent())
+        {
+            return false;
+        }
+    }
+}
+
+pub fn update_fog_of_war(
+    mut tilemap_data: ResMut<TilemapData>,
+    mut world_stats: ResMut<WorldStats>,
+    q_dwellers: Query<(&Transform, &Layer), With<Dweller>>,
+) {
+    let mut discovered = HashSet::new();
+
+    for (transform, layer) in &q_dwellers {
+        let center = transform_to_pos(transform, layer);
+
+        for y in -VISION_RADIUS..=VISION_RADIUS {
+            for x in -VISION_RADIUS..=VISION_RADIUS {
+                let offset = IVec3::new(x, y, 0);
+                if offset.length_squared() > VISION_RADIUS * VISION_RADIUS {
+                    continue;
+                }
+
+                let pos = center + offset;
+                if tilemap_data.get(pos).is_some_and(|tile| !tile.discovered)
+                    && has_line_of_sight(&tilemap_data, center, pos)
+                {
+                    discovered.insert(pos);
+                }
+            }
+        }
+    }
+
+    if discovered.is_empty() {
+        return;
+    }
+
+    world_stats.tiles_discovered += discovered.len() as u32;
+
+    // Discovering tiles doesn't change pathfinding, don't trigger systems watching TilemapData
+    let tilemap_data = tilemap_data.bypass_change_detection();
+    for pos in discovered {
+        tilemap_data.discover(pos);
+    }
+}
@@ -0,0 +1,244 @@
+use std::collections::VecDeque;
+
+use bevy::{
+    platform::collections::{HashMap, HashSet},
+    prelude::*,
+};
+
+use crate::{
+    CHUNK_SIZE, TilePlaced, TilemapData, Weather,
+    data::{LIGHT_SOURCES, TileId},
+};
+
+pub const LIGHT_MAX: u8 = 15;
+const MOONLIGHT: u8 = 4;
+const LAVA_LIGHT: u8 = 10;
+const FIRE_LIGHT: u8 = 13;
+
+// Light never travels further than its level, so chunks are lit from sources up to this distance
+const MARGIN: i32 = LIGHT_MAX as i32;
+
+// Per tile light level of loaded chunks, recomputed when tiles or the sun change so it is not saved
+#[derive(Resource, Default)]
+pub struct LightLevels {
+    chunks: HashMap<IVec3, Vec<u8>>,
+    sun: u8,
+    dirty: HashSet<IVec3>, // Chunks to relight on the next timer tick
+    pub changed: HashSet<IVec3>,
+}
+
+impl LightLevels {
+    pub fn get(&self, pos: IVec3) -> u8 {
+        let (chunk_pos, tile_index) = TilemapData::pos_to_chunk_pos_and_local_index(pos);
+        self.chunks
+            .get(&chunk_pos)
+            .and_then(|chunk| chunk.get(tile_index))
+            .copied()
+            .unwrap_or(LIGHT_MAX)
+    }
+
+    pub fn color(&self, pos: IVec3) -> Color {
+        let brightness = self.get(pos) as f32 / LIGHT_MAX as f32;
+        Color::srgb(brightness, brightness, brightness)
+    }
+
+    fn relight(&mut self, tilemap_data: &TilemapData, chunk_pos: IVec3) {
+        if !tilemap_data.chunks.contains_key(&chunk_pos) {
+            return;
+        }
+
+        let levels = compute_chunk_light(tilemap_data, chunk_pos, self.sun);
+        let old_levels = self.chunks.insert(chunk_pos, levels.clone());
+
+        // Only the tiles whose light changed need to be redrawn
+        for (i, (pos, level)) in TilemapData::iter_chunk_positions(chunk_pos)
+            .zip(levels)
+            .enumerate()
+        {
+            if old_levels.as_ref().is_none_or(|old| old[i] != level) {
+                self.changed.insert(pos);
+            }
+        }
+    }
+}
+
+impl TilePlaced {
+    // Open to the sky unless under the rock of mountains and underground layers
+    pub fn is_outdoors(self) -> bool {
+        !self.roofed && !self.id.data().is_wall()
+    }
+
+    fn emitted_light(self, sun: u8) -> u8 {
+        let mut light = self
+            .object
+            .and_then(|object| LIGHT_SOURCES.get(&object).copied())
+            .unwrap_or(0);
+
+        if self.is_outdoors() {
+            light = light.max(sun);
+        }
+        if self.id == TileId::Lava {
+            light = light.max(LAVA_LIGHT);
+        }
+        if self.fire > 0 {
+            light = light.max(FIRE_LIGHT);
+        }
+
+        light
+    }
+}
+
+fn sun_light(weather: &Weather) -> u8 {
+    MOONLIGHT + (weather.daylight() * (LIGHT_MAX - MOONLIGHT) as f32).round() as u8
+}
+
+// Flood fill the light from the sources in and around the chunk, walls are lit but stop the light
+fn compute_chunk_light(tilemap_data: &TilemapData, chunk_pos: IVec3, sun: u8) -> Vec<u8> {
+    let size = CHUNK_SIZE as i32;
+    let area_min = (chunk_pos.xy() * size - IVec2::splat(MARGIN)).extend(chunk_pos.z);
+    let area_size = size + 2 * MARGIN;
+
+    let index = |pos: IVec3| {
+        let local = (pos - area_min).xy();
+        (local.x >= 0 && local.y >= 0 && local.x < area_size && local.y < area_size)
+            .then_some((local.y * area_size + local.x) as usize)
+    };
+
+    let mut levels = vec![0; (area_size * area_size) as usize];
+    let mut queue = VecDeque::new();
+
+    for y in 0..area_size {
+        for x in 0..area_size {
+            let pos = area_min + IVec3::new(x, y, 0);
+            let Some(tile) = tilemap_data.get(pos) else {
+                continue;
+            };
+
+            let light = tile.emitted_light(sun);
+            if light > 0 {
+                levels[(y * area_size + x) as usize] = light;
+                queue.push_back(pos);
+            }
+        }
+    }
+
+    while let Some(pos) = queue.pop_front() {
+        let light = levels[index(pos).unwrap()];
+        if light <= 1
+            || tilemap_data
+                .get(pos)
+                .is_none_or(|tile| !tile.id.is_transparent())
+        {
+            continue;
+        }
+
+        for offset in [IVec3::X, IVec3::Y, IVec3::NEG_X, IVec3::NEG_Y] {
+            let neighbour_pos = pos + offset;
+            let Some(neighbour_index) = index(neighbour_pos) else {
+                continue;
+            };
+
+            if levels[neighbour_index] < light - 1 {
+                levels[neighbour_index] = light - 1;
+                queue.push_back(neighbour_pos);
+            }
+        }
+    }
+
+    TilemapData::iter_chunk_positions(chunk_pos)
+        .map(|pos| levels[index(pos).unwrap()])
+        .collect()
+}
+
+// Lights new chunks right away, other changes are only collected for update_light_levels
+pub fn track_light_changes(
+    tilemap_data: Res<TilemapData>,
+    weather: If<Res<Weather>>,
+    mut light_levels: ResMut<LightLevels>,
+) {
+    light_levels
+        .chunks
+        .retain(|chunk_pos, _| tilemap_data.chunks.contains_key(chunk_pos));
+
+    // Chunks close enough to a changed tile to have their light changed
+    let dirty_chunks = tilemap_data
+        .tiles_to_update
+        .keys()
+        .flat_map(|pos| {
+            [-MARGIN, 0, MARGIN].into_iter().flat_map(move |y| {
+                [-MARGIN, 0, MARGIN].map(|x| {
+                    TilemapData::pos_to_chunk_pos_and_local_index(*pos + IVec3::new(x, y, 0)).0
+                })
+            })
+        })
+        .collect::<Vec<_>>();
+    light_levels.dirty.extend(dirty_chunks);
+
+    let new_chunks = tilemap_data
+        .chunks
+        .keys()
+        .filter(|chunk_pos| !light_levels.chunks.contains_key(*chunk_pos))
+        .copied()
+        .collect::<Vec<_>>();
+    if new_chunks.is_empty() {
+        return;
+    }
+
+    // The first chunks are lit before update_light_levels ever ran
+    if light_levels.chunks.is_empty() {
+        light_levels.sun = sun_light(&weather);
+    }
+
+    // Light from a new chunk reaches into its neighbours
+    for chunk_pos in new_chunks {
+        light_levels.relight(&tilemap_data, chunk_pos);
+        for y in -1..=1 {
+            for x in -1..=1 {
+                if x != 0 || y != 0 {
+                    light_levels.dirty.insert(chunk_pos + IVec3::new(x, y, 0));
+                }
+            }
+        }
+    }
+}
+
+pub fn update_light_levels(
+    tilemap_data: Res<TilemapData>,
+    weather: If<Res<Weather>>,
+    mut light_levels: ResMut<LightLevels>,
+) {
+    let sun = sun_light(&weather);
+    if sun != light_levels.sun {
+        light_levels.sun = sun;
+        light_levels.dirty.extend(tilemap_data.chunks.keys());
+    }
+
+    let dirty_chunks = std::mem::take(&mut light_levels.dirty);
+    for chunk_pos in dirty_chunks {
+        light_levels.relight(&tilemap_data, chunk_pos);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dug_floors_stay_under_the_rock() {
+        let rock = TilePlaced {
+            roofed: true,
+            ..TileId::StoneWall.place()
+        };
+        let mut tilemap_data = TilemapData::filled_chunk(IVec3::ZERO, rock);
+
+        tilemap_data.set(IVec3::ZERO, TileId::StoneFloor.place());
+        assert!(!tilemap_data.get(IVec3::ZERO).unwrap().is_outdoors());
+    }
+
+    #[test]
+    fn open_floors_are_outdoors() {
+        assert!(TileId::GrassFloor.place().is_outdoors());
+        assert!(TileId::WoodFloor.place().is_outdoors());
+        assert!(!TileId::StoneWall.place().is_outdoors());
+    }
+}
@@ -1,19 +1,33 @@
+mod biomes;
 mod chunks;
+mod fluids;
+mod fog_of_war;
+mod lighting;
 mod objects;
+mod spoilage;
 mod structures;
+mod temperature;
 mod terrain;
 mod tilemap_chunk;
 mod tilemap_data;
 mod tiles;
 mod tileset;
 mod weather;
+mod world_gen;
 
+pub use biomes::*;
 pub use chunks::*;
+pub use fluids::*;
+pub use fog_of_war::*;
+pub use lighting::*;
 pub use objects::*;
+pub use spoilage::*;
 pub use structures::*;
+pub use temperature::*;
 pub use terrain::*;
 pub use tilemap_chunk::*;
 pub use tilemap_data::*;
 pub use tiles::*;
 pub use tileset::*;
 pub use weather::*;
+pub use world_gen::*;
@@ -1,3 +1,5 @@
+use bevy::prelude::*;
+
 pub struct Object {
     filename: &'static str,
     blocking: bool,
@@ -7,8 +9,22 @@ pub struct Object {
 pub enum ObjectSlot {
     Uncarriable,
     Object,
-    Tool(u32),  // damage
-    Armor(u32), // hp
+    Tool {
+        category: ToolCategory,
+        power: u32, // damage for weapons, work speed bonus for the others
+        durability: u32,
+    },
+    Armor(u32), // damage mitigation in percent
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Reflect, Debug)]
+pub enum ToolCategory {
+    Pickaxe,
+    Axe,
+    Hoe,
+    FishingRod,
+    Hammer,
+    Weapon,
 }
 
 impl Object {
@@ -36,12 +52,25 @@ impl Object {
         Self::new(filename, true, ObjectSlot::Uncarriable)
     }
 
-    pub const fn tool(filename: &'static str, damage: u32) -> Self {
-        Self::new(filename, false, ObjectSlot::Tool(damage))
+    pub const fn tool(
+        filename: &'static str,
+        category: ToolCategory,
+        power: u32,
+        durability: u32,
+    ) -> Self {
+        Self::new(
+            filename,
+            false,
+            ObjectSlot::Tool {
+                category,
+                power,
+                durability,
+            },
+        )
     }
 
-    pub const fn armor(filename: &'static str, hp: u32) -> Self {
-        Self::new(filename, false, ObjectSlot::Armor(hp))
+    pub const fn armor(filename: &'static str, mitigation: u32) -> Self {
+        Self::new(filename, false, ObjectSlot::Armor(mitigation))
     }
 
     pub fn is_carriable(&self) -> bool {
@@ -0,0 +1,73 @@
+use bevy::prelude::*;
+use rand::prelude::*;
+
+use crate::{
+    TilePlaced, TilemapData,
+    data::SPOILAGE_RATES,
+    task_index::TaskIndex,
+    tasks::{TaskKind, TaskNeeds},
+};
+
+pub const SPOILAGE_MAX: u8 = u8::MAX;
+// Food on stockpiles indoors spoils slower
+const STORED_SPOILAGE_CHANCE: f64 = 0.3;
+
+pub fn update_spoilage(
+    mut commands: Commands,
+    mut tilemap_data: ResMut<TilemapData>,
+    task_index: Res<TaskIndex>,
+    mut q_task_needs: Query<&mut TaskNeeds>,
+) {
+    let mut rng = rand::rng();
+
+    let mut spoiling = vec![];
+    for (chunk_pos, tiles) in &tilemap_data.chunks {
+        for (pos, tile) in TilemapData::iter_chunk_positions(*chunk_pos).zip(tiles) {
+            let Some(rate) = tile.object.and_then(|object| SPOILAGE_RATES.get(&object)) else {
+                continue;
+            };
+
+            let stockpile = task_index
+                .at(pos)
+                .find(|task| task.kind == TaskKind::Stockpile);
+            if stockpile.is_some()
+                && !tile.is_outdoors()
+                && !rng.random_bool(STORED_SPOILAGE_CHANCE)
+            {
+                continue;
+            }
+
+            spoiling.push((pos, *tile, *rate, stockpile.map(|task| task.entity)));
+        }
+    }
+
+    for (pos, tile, rate, stockpile) in spoiling {
+        let spoilage = tile.spoilage.saturating_add(rate);
+
+        if spoilage < SPOILAGE_MAX {
+            tilemap_data.set(pos, TilePlaced { spoilage, ..tile });
+            continue;
+        }
+
+        debug!("{:?} rotted away at {:?}", tile.object, pos);
+        tilemap_data.set(
+            pos,
+            TilePlaced {
+                object: None,
+                ..tile
+            },
+        );
+
+        // The stockpile can take a new object, there is nothing left to pick up or eat
+        if let Some(stockpile) = stockpile
+            && let Ok(mut task_needs) = q_task_needs.get_mut(stockpile)
+        {
+            *task_needs = TaskNeeds::AnyObject;
+        }
+        for task in task_index.at(pos) {
+            if matches!(task.kind, TaskKind::Pickup | TaskKind::Eat) {
+                commands.entity(task.entity).despawn();
+            }
+        }
+    }
+}
@@ -1,10 +1,20 @@
-use bevy::math::UVec2;
+use bevy::prelude::*;
+use rand::prelude::*;
 
-use crate::{data::MobId, TilePlaced};
+use crate::{
+    BiomeData, CHUNK_SIZE, TilePlaced, TilemapData,
+    data::{ASCII_LOOT_TILES, ASCII_TILES, BiomeId, MobId, ObjectId, StructureId},
+};
 
+// Written for tiles without an ASCII character, so blank rows and columns are kept
+pub const ASCII_UNTOUCHED: char = '_';
+
+#[derive(Clone)]
 pub struct StructureData {
     tiles: Vec<Vec<Option<TilePlaced>>>,
     mobs: Vec<(UVec2, MobId)>,
+    // Tiles receiving an object from the loot table of the structure
+    loot: Vec<UVec2>,
 }
 
 impl StructureData {
@@ -15,9 +25,69 @@ impl StructureData {
                 .into_iter()
                 .map(|(x, y, m)| (UVec2::new(x, y), m))
                 .collect(),
+            loot: vec![],
         }
     }
 
+    // Rows are read top to bottom, unknown characters (e.g. spaces or ASCII_UNTOUCHED) leave the
+    // terrain untouched
+    pub fn from_ascii(ascii: &str, mobs: Vec<(u32, u32, MobId)>) -> Self {
+        let lines = ascii.trim_end().lines().collect::<Vec<_>>();
+        let mut loot = vec![];
+
+        let grid = lines
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(y, line)| {
+                line.chars()
+                    .enumerate()
+                    .map(|(x, ch)| {
+                        if let Some(tile) = ASCII_LOOT_TILES.get(&ch) {
+                            loot.push(UVec2::new(x as u32, y as u32));
+                            return Some(*tile);
+                        }
+                        ASCII_TILES.get(&ch).copied()
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            loot,
+            ..Self::new(grid, mobs)
+        }
+    }
+
+    // Tile as written in ASCII, ignoring its fluid, fire and discovery state
+    pub fn ascii_tile(tile: TilePlaced) -> Option<(char, TilePlaced)> {
+        ASCII_TILES
+            .iter()
+            .find(|(_, t)| t.id == tile.id && t.object == tile.object)
+            .map(|(ch, t)| (*ch, *t))
+    }
+
+    pub fn to_ascii(&self) -> String {
+        let x_size = self.x_size();
+
+        self.tiles
+            .iter()
+            .rev()
+            .map(|row| {
+                (0..x_size)
+                    .map(|x| {
+                        row.get(x)
+                            .copied()
+                            .flatten()
+                            .and_then(Self::ascii_tile)
+                            .map_or(ASCII_UNTOUCHED, |(ch, _)| ch)
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
     pub fn x_size(&self) -> usize {
         self.tiles.iter().map(Vec::len).max().unwrap_or_default()
     }
@@ -38,19 +108,35 @@ impl StructureData {
         &self.mobs
     }
 
-    pub fn flip_vertical(&self) -> Self {
-        let mut tiles = self.tiles.clone();
-        tiles.reverse();
+    pub fn loot(&self) -> &[UVec2] {
+        &self.loot
+    }
+
+    // Same tiles, with the mobs and loot spots moved along
+    fn with_tiles(
+        &self,
+        tiles: Vec<Vec<Option<TilePlaced>>>,
+        map: impl Fn(UVec2) -> UVec2,
+    ) -> Self {
         StructureData {
             tiles,
             mobs: self
                 .mobs
                 .iter()
-                .map(|(pos, mob)| (UVec2::new(pos.x, self.y_size() as u32 - 1 - pos.y), *mob))
+                .map(|(pos, mob)| (map(*pos), *mob))
                 .collect(),
+            loot: self.loot.iter().map(|pos| map(*pos)).collect(),
         }
     }
 
+    pub fn flip_vertical(&self) -> Self {
+        let mut tiles = self.tiles.clone();
+        tiles.reverse();
+        self.with_tiles(tiles, |pos| {
+            UVec2::new(pos.x, self.y_size() as u32 - 1 - pos.y)
+        })
+    }
+
     pub fn flip_horizontal(&self) -> Self {
         let mut tiles = self.tiles.clone();
         let x_size = self.x_size();
@@ -62,14 +148,9 @@ impl StructureData {
                 row.pop();
             }
         }
-        StructureData {
-            tiles,
-            mobs: self
-                .mobs
-                .iter()
-                .map(|(pos, mob)| (UVec2::new(self.x_size() as u32 - 1 - pos.x, pos.y), *mob))
-                .collect(),
-        }
+        self.with_tiles(tiles, |pos| {
+            UVec2::new(self.x_size() as u32 - 1 - pos.x, pos.y)
+        })
     }
 
     fn rotate_pos(&self, pos: UVec2, clockwise: bool) -> UVec2 {
@@ -80,6 +161,20 @@ impl StructureData {
         }
     }
 
+    pub fn oriented(&self, rotation: u8, flipped: bool) -> Self {
+        let mut structure = if flipped {
+            self.flip_horizontal()
+        } else {
+            self.clone()
+        };
+
+        for _ in 0..rotation % 4 {
+            structure = structure.rotate(true);
+        }
+
+        structure
+    }
+
     pub fn rotate(&self, clockwise: bool) -> Self {
         let mut tiles = vec![vec![None; self.y_size()]; self.x_size()];
 
@@ -90,13 +185,268 @@ impl StructureData {
             }
         }
 
-        StructureData {
-            tiles,
-            mobs: self
-                .mobs
-                .iter()
-                .map(|(pos, mob)| (self.rotate_pos(*pos, clockwise), *mob))
-                .collect(),
+        self.with_tiles(tiles, |pos| self.rotate_pos(pos, clockwise))
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum StructureLocation {
+    Surface,
+    Mountain,
+}
+
+// Where and how often a structure is generated
+pub struct StructureSpawn {
+    pub structure: StructureId,
+    pub location: StructureLocation,
+    // Any biome if empty
+    pub biomes: &'static [BiomeId],
+    // The world is split in square regions of this many chunks, each with one candidate chunk
+    pub spacing: i32,
+    // Minimum distance in chunks between two structures of this kind, less than spacing
+    pub min_distance: i32,
+    // Chance for the candidate chunk of a region to hold the structure
+    pub chance: f64,
+    // Weighted objects placed on the loot spots
+    pub loot: &'static [(ObjectId, u32)],
+}
+
+impl StructureSpawn {
+    // Stable random generator for a region, the same whichever chunk asks for it
+    fn region_rng(&self, seed: u32, region: IVec2) -> StdRng {
+        let hash = [
+            seed as u64,
+            region.x as u64,
+            region.y as u64,
+            self.structure as u64,
+        ]
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, value| {
+            (hash ^ value).wrapping_mul(0x0100_0000_01b3)
+        });
+
+        StdRng::seed_from_u64(hash)
+    }
+
+    // Structure anchored in this chunk, with its random orientation and position
+    pub fn place(&self, seed: u32, chunk_pos: IVec2) -> Option<PlacedStructure> {
+        debug_assert!(
+            self.min_distance < self.spacing,
+            "{:?}: min_distance must be less than spacing",
+            self.structure
+        );
+
+        let region = chunk_pos.div_euclid(IVec2::splat(self.spacing));
+        let mut rng = self.region_rng(seed, region);
+
+        // Leaving min_distance chunks at the end of each region keeps candidates apart
+        let range = self.spacing - self.min_distance;
+        let candidate = region * self.spacing
+            + IVec2::new(rng.random_range(0..range), rng.random_range(0..range));
+
+        if candidate != chunk_pos || !rng.random_bool(self.chance) {
+            return None;
+        }
+
+        let mut structure = self.structure.data();
+        debug_assert!(
+            structure.size().max_element() <= self.spacing as u32 * CHUNK_SIZE,
+            "{:?} is larger than its spacing",
+            self.structure
+        );
+
+        if rng.random_bool(0.5) {
+            structure = structure.flip_horizontal();
+        }
+
+        if rng.random_bool(0.5) {
+            structure = structure.flip_vertical();
+        }
+
+        let rotation = rng.random_range(0..=3);
+        let clockwise = rng.random_bool(0.5);
+        for _ in 0..rotation {
+            structure = structure.rotate(clockwise);
+        }
+
+        // Anywhere in the chunk, the structure can overlap the neighbouring chunks
+        let local_pos = IVec2::new(
+            rng.random_range(0..CHUNK_SIZE as i32),
+            rng.random_range(0..CHUNK_SIZE as i32),
+        );
+
+        let loot = structure
+            .loot()
+            .iter()
+            .filter_map(|pos| BiomeData::pick(self.loot, rng.random()).map(|loot| (*pos, loot)))
+            .collect();
+
+        Some(PlacedStructure {
+            pos: chunk_pos * CHUNK_SIZE as i32 + local_pos,
+            structure,
+            loot,
+        })
+    }
+}
+
+// Structures are only placed on the surface, layer 0
+pub struct PlacedStructure {
+    pub pos: IVec2,
+    pub structure: StructureData,
+    pub loot: Vec<(UVec2, ObjectId)>,
+}
+
+impl PlacedStructure {
+    pub fn center(&self) -> IVec2 {
+        self.pos + self.structure.size().as_ivec2() / 2
+    }
+
+    pub fn overlaps(&self, other: &PlacedStructure) -> bool {
+        let end = self.pos + self.structure.size().as_ivec2();
+        let other_end = other.pos + other.structure.size().as_ivec2();
+        self.pos.cmplt(other_end).all() && other.pos.cmplt(end).all()
+    }
+
+    // Write the part of the structure inside the chunk, with the mobs standing in it
+    pub fn stamp(
+        &self,
+        chunk_pos: IVec3,
+        tiles: &mut [TilePlaced],
+        mobs: &mut Vec<(MobId, IVec3)>,
+    ) {
+        let locate = |pos: IVec2| TilemapData::pos_to_chunk_pos_and_local_index(pos.extend(0));
+
+        for y in 0..self.structure.y_size() {
+            for x in 0..self.structure.x_size() {
+                if let Some(tile) = self.structure.get_tile(x, y) {
+                    let (tile_chunk_pos, i) = locate(self.pos + IVec2::new(x as i32, y as i32));
+                    if tile_chunk_pos == chunk_pos {
+                        // Structures carved in mountains stay under the rock
+                        tiles[i] = TilePlaced {
+                            roofed: tiles[i].roofed,
+                            ..*tile
+                        };
+                    }
+                }
+            }
+        }
+
+        for (pos, object) in &self.loot {
+            let (tile_chunk_pos, i) = locate(self.pos + pos.as_ivec2());
+            if tile_chunk_pos == chunk_pos {
+                tiles[i].object = Some(*object);
+            }
         }
+
+        for (pos, mob) in self.structure.mobs() {
+            let pos = self.pos + pos.as_ivec2();
+            if locate(pos).0 == chunk_pos {
+                mobs.push((*mob, pos.extend(0)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{STRUCTURE_SPAWNS, TileId};
+
+    #[test]
+    fn structure_spawns_fit_their_spacing() {
+        for spawn in STRUCTURE_SPAWNS {
+            assert!(spawn.min_distance < spawn.spacing, "{:?}", spawn.structure);
+            assert!(
+                spawn.structure.data().size().max_element() <= spawn.spacing as u32 * CHUNK_SIZE,
+                "{:?}",
+                spawn.structure
+            );
+        }
+    }
+
+    #[test]
+    fn ascii_round_trip() {
+        let ascii = "====\n=b-=\n=-t=\n=Ď==";
+        let structure = StructureData::from_ascii(ascii, vec![]);
+
+        assert_eq!(structure.size(), UVec2::new(4, 4));
+        assert_eq!(structure.to_ascii(), ascii);
+        assert_eq!(
+            StructureData::from_ascii(&structure.to_ascii(), vec![]).to_ascii(),
+            ascii
+        );
+
+        // Rows are stored bottom to top
+        let door = structure.get_tile(1, 0).unwrap();
+        assert_eq!(door.id, TileId::WoodFloor);
+        assert_eq!(door.object, Some(ObjectId::Door));
+    }
+
+    #[test]
+    fn ascii_keeps_untouched_rows_and_columns() {
+        let grass = Some(TileId::GrassFloor.place());
+        let structure = StructureData::new(
+            vec![
+                vec![None, None, None],
+                vec![grass, None, None],
+                vec![None, None, None],
+            ],
+            vec![],
+        );
+
+        let ascii = structure.to_ascii();
+        assert_eq!(ascii, "___\n,__\n___");
+
+        let parsed = StructureData::from_ascii(&ascii, vec![]);
+        assert_eq!(parsed.size(), UVec2::new(3, 3));
+        assert!(parsed.get_tile(0, 0).is_none());
+        assert_eq!(
+            parsed.get_tile(0, 1).map(|tile| tile.id),
+            Some(TileId::GrassFloor)
+        );
+    }
+
+    #[test]
+    fn ascii_tile_ignores_tile_state() {
+        let tile = TilePlaced {
+            discovered: true,
+            fire: 3,
+            ..TileId::WoodFloor.with(ObjectId::Bed)
+        };
+        assert_eq!(StructureData::ascii_tile(tile).map(|(ch, _)| ch), Some('b'));
+        assert!(StructureData::ascii_tile(TileId::StoneWall.place()).is_none());
+    }
+
+    #[test]
+    fn rotations_keep_mobs_on_their_tile() {
+        let structure = StructureData::from_ascii("...\n#..", vec![(0, 0, MobId::Undead)]);
+
+        for rotation in 0..4 {
+            for flipped in [false, true] {
+                let oriented = structure.oriented(rotation, flipped);
+                let (pos, _) = oriented.mobs()[0];
+                assert_eq!(
+                    oriented
+                        .get_tile(pos.x as usize, pos.y as usize)
+                        .map(|tile| tile.id),
+                    Some(TileId::DungeonWall),
+                    "rotation {rotation}, flipped {flipped}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn footprints_overlap_only_when_sharing_tiles() {
+        let placed = |x, y| PlacedStructure {
+            pos: IVec2::new(x, y),
+            structure: StructureData::from_ascii("...\n...", vec![]),
+            loot: vec![],
+        };
+
+        assert!(placed(0, 0).overlaps(&placed(2, 1)));
+        assert!(placed(2, 1).overlaps(&placed(0, 0)));
+        assert!(!placed(0, 0).overlaps(&placed(3, 0)));
+        assert!(!placed(0, 0).overlaps(&placed(0, -2)));
     }
 }
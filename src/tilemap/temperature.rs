@@ -0,0 +1,279 @@
+use bevy::{
+    platform::collections::{HashMap, HashSet},
+    prelude::*,
+};
+use rand::prelude::*;
+
+use crate::{
+    Layer, TakingDamage, TilePlaced, TilemapData, Weather,
+    data::{FLAMMABLE_OBJECTS, FLAMMABLE_TILES, ObjectId, TileId, WORKSTATION_HEAT},
+    dwellers::Dweller,
+    mobs::Mob,
+    task_index::TaskIndex,
+    tasks::{BuildResult, Task, TaskBundle, TaskKind, TaskNeeds, WorkstationAmount},
+    utils::transform_to_pos,
+};
+
+pub const AMBIENT_TEMPERATURE: f32 = 15.0;
+const LAVA_TEMPERATURE: f32 = 1000.0;
+const FIRE_TEMPERATURE: f32 = 600.0;
+
+const DIFFUSION: f32 = 0.25;
+const COOLING: f32 = 0.05;
+
+const IGNITION_TEMPERATURE: f32 = 250.0;
+const IGNITION_CHANCE: f64 = 0.3;
+const SPREAD_CHANCE: f32 = 0.15;
+
+const HARMFUL_TEMPERATURE: f32 = 150.0;
+const FIRE_DAMAGE: i32 = 5;
+const HEAT_DAMAGE: i32 = 1;
+
+// Per tile temperature of loaded chunks, recomputed from heat sources so it is not saved
+#[derive(Resource, Default)]
+pub struct Temperatures {
+    chunks: HashMap<IVec3, Vec<f32>>,
+}
+
+impl Temperatures {
+    pub fn get(&self, pos: IVec3) -> f32 {
+        let (chunk_pos, tile_index) = TilemapData::pos_to_chunk_pos_and_local_index(pos);
+        self.chunks
+            .get(&chunk_pos)
+            .and_then(|chunk| chunk.get(tile_index))
+            .copied()
+            .unwrap_or(AMBIENT_TEMPERATURE)
+    }
+}
+
+impl TilePlaced {
+    // Objects burn before the tile they are on
+    pub fn fuel(self) -> Option<u8> {
+        match self.object {
+            Some(object) => FLAMMABLE_OBJECTS.get(&object).copied(),
+            None => FLAMMABLE_TILES.get(&self.id).copied(),
+        }
+        .filter(|_| self.fluid == 0)
+    }
+
+    fn burnt(self) -> TilePlaced {
+        match self.object {
+            Some(_) => TilePlaced {
+                object: None,
+                fire: 0,
+                ..self
+            },
+            None => BuildResult::Tile(self.id).deconstructed_tile(self),
+        }
+    }
+
+    fn heat(self) -> Option<f32> {
+        if self.fire > 0 {
+            Some(FIRE_TEMPERATURE)
+        } else if self.id == TileId::Lava {
+            Some(LAVA_TEMPERATURE)
+        } else {
+            None
+        }
+    }
+}
+
+// Set the tile on fire if it can burn, and ask dwellers to put it out
+pub fn ignite(
+    commands: &mut Commands,
+    tilemap_data: &mut TilemapData,
+    task_index: &TaskIndex,
+    pos: IVec3,
+) -> bool {
+    let Some(tile) = tilemap_data.get(pos) else {
+        return false;
+    };
+    let Some(fuel) = tile.fuel() else {
+        return false;
+    };
+
+    tilemap_data.set(pos, TilePlaced { fire: fuel, ..tile });
+    debug!("Fire at {pos:?}");
+
+    if task_index.can_place(pos, TaskKind::Extinguish)
+        && !task_index
+            .at(pos)
+            .any(|task| task.kind == TaskKind::Extinguish)
+    {
+        commands.spawn(TaskBundle::new(
+            Task::new(pos, TaskKind::Extinguish, None),
+            TaskNeeds::Objects(vec![ObjectId::WaterBucket]),
+        ));
+    }
+
+    true
+}
+
+pub fn update_temperatures(
+    tilemap_data: Res<TilemapData>,
+    task_index: Res<TaskIndex>,
+    mut temperatures: ResMut<Temperatures>,
+) {
+    temperatures
+        .chunks
+        .retain(|chunk_pos, _| tilemap_data.chunks.contains_key(chunk_pos));
+
+    // Workstations only heat up while a dweller works on them
+    let workstations_heat = task_index
+        .of_kind(TaskKind::Workstation {
+            amount: WorkstationAmount::Infinite,
+            recipe: 0,
+        })
+        .filter(|task| task.dweller_id.is_some())
+        .filter_map(|task| {
+            let object = tilemap_data.get(task.pos)?.object?;
+            WORKSTATION_HEAT.get(&object).map(|heat| (task.pos, *heat))
+        })
+        .collect::<HashMap<_, _>>();
+
+    let new_chunks = tilemap_data
+        .chunks
+        .iter()
+        .map(|(chunk_pos, tiles)| {
+            let chunk = TilemapData::iter_chunk_positions(*chunk_pos)
+                .zip(tiles)
+                .map(|(pos, tile)| {
+                    let temperature = temperatures.get(pos);
+
+                    let neighbours = [IVec3::X, IVec3::Y, IVec3::NEG_X, IVec3::NEG_Y]
+                        .map(|offset| temperatures.get(pos + offset));
+                    let average = neighbours.iter().sum::<f32>() / neighbours.len() as f32;
+
+                    let mut temperature = temperature + (average - temperature) * DIFFUSION;
+                    temperature += (AMBIENT_TEMPERATURE - temperature) * COOLING;
+
+                    if let Some(heat) = tile.heat().or(workstations_heat.get(&pos).copied()) {
+                        temperature = temperature.max(heat);
+                    }
+
+                    temperature
+                })
+                .collect::<Vec<_>>();
+
+            (*chunk_pos, chunk)
+        })
+        .collect();
+
+    temperatures.chunks = new_chunks;
+}
+
+pub fn update_fires(
+    mut commands: Commands,
+    mut tilemap_data: ResMut<TilemapData>,
+    temperatures: Res<Temperatures>,
+    weather: If<Res<Weather>>,
+    task_index: Res<TaskIndex>,
+    mut q_dwellers: Query<(Entity, &mut Dweller, &Transform, &Layer)>,
+    mut q_mobs: Query<(Entity, &mut Mob, &Transform, &Layer)>,
+) {
+    let mut rng = rand::rng();
+
+    let mut burning = vec![];
+    let mut ignited = HashSet::new();
+
+    for (chunk_pos, tiles) in &tilemap_data.chunks {
+        for (pos, tile) in TilemapData::iter_chunk_positions(*chunk_pos).zip(tiles) {
+            if tile.fire > 0 {
+                burning.push((pos, *tile));
+            } else if tile.fuel().is_some()
+                && temperatures.get(pos) > IGNITION_TEMPERATURE
+                && rng.random_bool(IGNITION_CHANCE)
+            {
+                ignited.insert(pos);
+            }
+        }
+    }
+
+    for (pos, tile) in &burning {
+        // Water puts out fires
+        if tile.fluid > 0 {
+            tilemap_data.set(*pos, TilePlaced { fire: 0, ..*tile });
+            continue;
+        }
+
+        if tile.fire == 1 {
+            tilemap_data.set(*pos, tile.burnt());
+
+            for task in task_index.at(*pos) {
+                if task.kind == TaskKind::Extinguish {
+                    commands.entity(task.entity).try_despawn();
+                }
+            }
+        } else {
+            tilemap_data.set(
+                *pos,
+                TilePlaced {
+                    fire: tile.fire - 1,
+                    ..*tile
+                },
+            );
+        }
+
+        // Fire spreads faster downwind and during heatwaves
+        let spread_chance = SPREAD_CHANCE * weather.state_at(&tilemap_data, *pos).fire_spread();
+
+        for (neighbour_pos, neighbour) in tilemap_data.neighbours(*pos) {
+            if neighbour.fire > 0 || neighbour.fuel().is_none() {
+                continue;
+            }
+
+            let direction = (neighbour_pos - *pos).xy().as_vec2();
+            let chance = spread_chance * (1.0 + 2.0 * weather.wind.dot(direction)).max(0.2);
+
+            if rng.random_bool(chance.clamp(0.0, 1.0) as f64) {
+                ignited.insert(neighbour_pos);
+            }
+        }
+    }
+
+    for pos in ignited {
+        ignite(&mut commands, &mut tilemap_data, &task_index, pos);
+    }
+
+    // Burn dwellers and mobs standing in fire or close to it
+    let damage = |pos: IVec3| {
+        if tilemap_data.get(pos).is_some_and(|tile| tile.fire > 0) {
+            Some(FIRE_DAMAGE)
+        } else if temperatures.get(pos) > HARMFUL_TEMPERATURE {
+            Some(HEAT_DAMAGE)
+        } else {
+            None
+        }
+    };
+
+    // Tiles next to lava stay above HARMFUL_TEMPERATURE, the heat spares dwellers scooping from them
+    let scooping = task_index
+        .of_kind(TaskKind::Scoop)
+        .filter_map(|task| Some((task.dweller_id?, task.pos)))
+        .collect::<HashMap<_, _>>();
+
+    for (entity, mut dweller, transform, layer) in &mut q_dwellers {
+        let pos = transform_to_pos(transform, layer);
+        let scoops_from = scooping.get(&dweller.uuid).is_some_and(|scoop_pos| {
+            scoop_pos.z == pos.z && (*scoop_pos - pos).abs().max_element() <= 1
+        });
+
+        if let Some(damage) = damage(pos).filter(|damage| !(scoops_from && *damage == HEAT_DAMAGE))
+        {
+            dweller.health(-damage);
+            commands.entity(entity).try_insert(TakingDamage::new());
+        }
+    }
+
+    for (entity, mut mob, transform, layer) in &mut q_mobs {
+        if let Some(damage) = damage(transform_to_pos(transform, layer)) {
+            mob.health(-damage);
+            commands.entity(entity).try_insert(TakingDamage::new());
+
+            if mob.health == 0 {
+                debug!("Mob burnt at {:?}", transform.translation);
+                commands.entity(entity).try_despawn();
+            }
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use bevy::{platform::collections::HashSet, prelude::*};
+use bevy::prelude::*;
 use noise::{
     Abs, Billow, Fbm, NoiseFn, OpenSimplex, Perlin, Simplex, Worley,
     core::worley::distance_functions::euclidean_squared,
@@ -6,54 +6,177 @@ use noise::{
 use rand::prelude::*;
 
 use crate::{
-    CHUNK_SIZE, MobBundle, SpawnMobsOnChunk, TilePlaced, TilemapData,
-    data::{MobId, ObjectId, StructureId, TileId},
+    BiomeData, BiomeNoise, CHUNK_SIZE, MobBundle, PlacedStructure, Season, SpawnMobsOnChunk,
+    StructureLocation, TilePlaced, TilemapData, Weather, WorldGenSettings,
+    data::{LAYER_ORES, MobId, ObjectId, STRUCTURE_SPAWNS, TileId},
+    task_index::TaskIndex,
     tasks::{Task, TaskBundle, TaskKind, TaskNeeds},
 };
 
-const MOBS_SCALE: f64 = 0.1;
-const MOBS_THRESHOLD: f64 = 0.0;
+pub struct GeneratedChunk {
+    pub tiles: Vec<TilePlaced>,
+    pub spawn_mobs: bool,
+    pub structure_mobs: Vec<(MobId, IVec3)>,
+}
 
-const CLIMATE_SCALE: f64 = 0.01;
-const DESERT_THRESHOLD: f64 = 0.5;
+pub fn generate_terrain(
+    commands: &mut Commands,
+    settings: &WorldGenSettings,
+    seed: u32,
+    chunk_pos: IVec3,
+) -> Vec<TilePlaced> {
+    let chunk = generate_chunk(settings, seed, chunk_pos);
 
-const STRUCTURES_SCALE: f64 = 0.2;
+    if chunk.spawn_mobs {
+        commands.write_message(SpawnMobsOnChunk(chunk_pos));
+    }
 
-const MOUNTAINS_SCALE: f64 = 0.004;
-const MOUNTAINS_DIRT_THRESHOLD: f64 = -0.3;
-const MOUNTAINS_STONE_THRESHOLD: f64 = -0.2;
+    for (mob, pos) in chunk.structure_mobs {
+        commands.spawn(MobBundle::new(mob, pos));
+    }
 
-const MOUNTAINS_CAVES_THRESHOLD: f64 = 0.0;
-const CAVES_TUNNELS_SCALE: f64 = 0.05;
-const CAVES_TUNNELS_THRESHOLD: f64 = 0.05;
-const CAVES_ROOMS_SCALE: f64 = 0.1;
-const CAVES_ROOMS_THRESHOLD: f64 = 0.9;
+    chunk.tiles
+}
 
-const MOUNTAINS_LAVA_THRESHOLD: f64 = 0.5;
-const LAVA_SCALE: f64 = 0.1;
-const LAVA_THRESHOLD: f64 = 0.0;
+// Terrain under a structure, from the noise deciding between mountains and plains
+fn structure_location(
+    settings: &WorldGenSettings,
+    noise_mountains: &Billow<Perlin>,
+    pos: IVec2,
+) -> Option<StructureLocation> {
+    let mountain_noise_value = noise_mountains.get([
+        pos.x as f64 * settings.mountains_scale,
+        pos.y as f64 * settings.mountains_scale,
+    ]);
 
-const ORES_SCALE: f64 = 0.2;
-const ORES_THRESHOLD: f64 = 0.7;
+    if mountain_noise_value > settings.mountains_stone_threshold {
+        Some(StructureLocation::Mountain)
+    } else if mountain_noise_value < settings.mountains_dirt_threshold
+        && mountain_noise_value > -settings.river_shore_threshold
+    {
+        Some(StructureLocation::Surface)
+    } else {
+        None
+    }
+}
 
-const RIVER_DEEP_THRESHOLD: f64 = 0.75;
-const RIVER_THRESHOLD: f64 = 0.7;
-const RIVER_SHORE_THRESHOLD: f64 = 0.65;
+// Structures anchored in the chunk whose location and biome match their spawn rules,
+// with their index in STRUCTURE_SPAWNS
+fn anchored_structures(
+    settings: &WorldGenSettings,
+    seed: u32,
+    noise_mountains: &Billow<Perlin>,
+    biome_noise: &BiomeNoise,
+    anchor_chunk_pos: IVec2,
+) -> Vec<(usize, PlacedStructure)> {
+    STRUCTURE_SPAWNS
+        .iter()
+        .enumerate()
+        .filter_map(|(index, spawn)| {
+            let placed = spawn.place(seed, anchor_chunk_pos)?;
+            let center = placed.center();
+            (structure_location(settings, noise_mountains, center) == Some(spawn.location)
+                && (spawn.biomes.is_empty()
+                    || spawn.biomes.contains(&biome_noise.biome_at(center))))
+            .then_some((index, placed))
+        })
+        .collect()
+}
 
-const VEGETATION_ZONES_SCALE: f64 = 0.05;
-const VEGETATION_ZONES_THRESHOLD: f64 = 0.4;
+// Same ore for a whole vein cell
+fn ore_vein(
+    settings: &WorldGenSettings,
+    noise_ores_kind: &Worley,
+    ores: &[(ObjectId, u32)],
+    u: f64,
+    v: f64,
+) -> ObjectId {
+    let roll = (noise_ores_kind.get([u * settings.ores_kind_scale, v * settings.ores_kind_scale])
+        * 100.0)
+        .rem_euclid(1.0);
+    BiomeData::pick(ores, roll).unwrap_or(ObjectId::CopperOre)
+}
 
-const VEGETATION_SCALE: f64 = 0.5;
-const TREE_THRESHOLD: f64 = 0.4;
-const PLANT_THRESHOLD: f64 = 0.6;
+// Solid stone with caves, ores and lava getting more common with depth
+fn generate_underground(
+    settings: &WorldGenSettings,
+    seed: u32,
+    chunk_pos: IVec3,
+) -> GeneratedChunk {
+    let depth = -chunk_pos.z;
+    let layer_seed = seed.wrapping_add(depth as u32 * 100);
+
+    let mut rng: StdRng =
+        SeedableRng::seed_from_u64((layer_seed as i32 + chunk_pos.x + chunk_pos.y) as u64);
+    let noise_caves_tunnels = Abs::new(OpenSimplex::new(layer_seed));
+    let noise_caves_rooms = Worley::new(layer_seed + 1).set_distance_function(euclidean_squared);
+    let noise_ores = Perlin::new(layer_seed + 2);
+    let noise_ores_kind = Worley::new(layer_seed + 4);
+    let noise_lava = Fbm::<Perlin>::new(layer_seed + 3);
+
+    let ores_threshold = settings.ores_threshold - depth as f64 * settings.layer_ores_bonus;
+    let lava_threshold = settings.layer_lava_threshold - depth as f64 * settings.layer_lava_bonus;
+    let ores = LAYER_ORES[(depth as usize - 1).min(LAYER_ORES.len() - 1)];
+
+    let tiles = TilemapData::iter_chunk_positions(chunk_pos)
+        .map(|pos| {
+            let u = pos.x as f64;
+            let v = pos.y as f64;
+
+            if noise_lava.get([u * settings.lava_scale, v * settings.lava_scale]) > lava_threshold {
+                return TileId::Lava.place();
+            }
+
+            let tunnels_noise_value = noise_caves_tunnels.get([
+                u * settings.caves_tunnels_scale,
+                v * settings.caves_tunnels_scale,
+            ]);
+            let rooms_noise_value = noise_caves_rooms.get([
+                u * settings.caves_rooms_scale,
+                v * settings.caves_rooms_scale,
+            ]);
+
+            if tunnels_noise_value < settings.caves_tunnels_threshold
+                || rooms_noise_value > settings.caves_rooms_threshold
+            {
+                if rng.random_bool(0.0005) {
+                    return TileId::StoneFloor.with(ObjectId::MobLair);
+                }
+                return TileId::StoneFloor.place();
+            }
+
+            if noise_ores.get([u * settings.ores_scale, v * settings.ores_scale]) > ores_threshold {
+                TileId::StoneWall.with(ore_vein(settings, &noise_ores_kind, ores, u, v))
+            } else {
+                TileId::StoneWall.place()
+            }
+        })
+        .map(|tile| TilePlaced {
+            roofed: true,
+            ..tile
+        })
+        .collect();
+
+    GeneratedChunk {
+        tiles,
+        spawn_mobs: false,
+        structure_mobs: vec![],
+    }
+}
+
+// Pure generation, also used for the world previews
+pub fn generate_chunk(settings: &WorldGenSettings, seed: u32, chunk_pos: IVec3) -> GeneratedChunk {
+    if chunk_pos.z < 0 {
+        return generate_underground(settings, seed, chunk_pos);
+    }
 
-pub fn generate_terrain(commands: &mut Commands, seed: u32, chunk_pos: IVec2) -> Vec<TilePlaced> {
     let mut rng: StdRng =
         SeedableRng::seed_from_u64((seed as i32 + chunk_pos.x + chunk_pos.y) as u64);
     let noise_mountains = Billow::<Perlin>::new(seed);
     let noise_climate = Simplex::new(seed);
-    let noise_structures = Simplex::new(seed + 1);
+    let biome_noise = BiomeNoise::new(seed);
     let noise_ores = Perlin::new(seed);
+    let noise_ores_kind = Worley::new(seed + 4);
     let noise_vegetation = Worley::new(seed);
     let noise_vegetation_zones = Perlin::new(seed + 1);
     let noise_caves_tunnels = Abs::new(OpenSimplex::new(seed));
@@ -61,74 +184,75 @@ pub fn generate_terrain(commands: &mut Commands, seed: u32, chunk_pos: IVec2) ->
     let noise_lava = Fbm::<Perlin>::new(seed);
 
     // Generate mobs
-    if noise_climate.get([
-        chunk_pos.x as f64 * MOBS_SCALE,
-        chunk_pos.y as f64 * MOBS_SCALE,
-    ]) > MOBS_THRESHOLD
-    {
-        commands.write_message(SpawnMobsOnChunk(chunk_pos));
-    }
+    let spawn_mobs = noise_climate.get([
+        chunk_pos.x as f64 * settings.mobs_scale,
+        chunk_pos.y as f64 * settings.mobs_scale,
+    ]) > settings.mobs_threshold;
 
     // Generate terrain
     let mut tiles = Vec::with_capacity((CHUNK_SIZE * CHUNK_SIZE) as usize);
 
-    let mut mountainy_count = 0;
-    let mut plainy_count = 0;
-
     // Generate tiles
     for pos in TilemapData::iter_chunk_positions(chunk_pos) {
         let u = pos.x as f64;
         let v = pos.y as f64;
 
-        let climate_noise_value = noise_climate.get([u * CLIMATE_SCALE, v * CLIMATE_SCALE]);
-
         // Mountains
-        let mountain_noise_value = noise_mountains.get([u * MOUNTAINS_SCALE, v * MOUNTAINS_SCALE]);
+        let mountain_noise_value =
+            noise_mountains.get([u * settings.mountains_scale, v * settings.mountains_scale]);
 
         // Ore
-        let ores_noise_value = noise_ores.get([u * ORES_SCALE, v * ORES_SCALE]);
+        let ores_noise_value = noise_ores.get([u * settings.ores_scale, v * settings.ores_scale]);
 
-        if mountain_noise_value > MOUNTAINS_DIRT_THRESHOLD {
+        if mountain_noise_value > settings.mountains_dirt_threshold {
             let mut tile = TileId::DirtWall.place();
 
-            if mountain_noise_value > MOUNTAINS_STONE_THRESHOLD {
-                if ores_noise_value > ORES_THRESHOLD {
-                    tile = TileId::StoneWall.with(ObjectId::CopperOre);
+            if mountain_noise_value > settings.mountains_stone_threshold {
+                if ores_noise_value > settings.ores_threshold {
+                    let ores = biome_noise.biome_at(pos.xy()).data().ores;
+                    tile = TileId::StoneWall.with(ore_vein(settings, &noise_ores_kind, ores, u, v));
                 } else {
                     tile = TileId::StoneWall.place();
                 }
 
                 // Caves
-                let tunnels_noise_value =
-                    noise_caves_tunnels.get([u * CAVES_TUNNELS_SCALE, v * CAVES_TUNNELS_SCALE]);
-                let rooms_noise_value =
-                    noise_caves_rooms.get([u * CAVES_ROOMS_SCALE, v * CAVES_ROOMS_SCALE]);
-
-                if mountain_noise_value > MOUNTAINS_CAVES_THRESHOLD
-                    && (tunnels_noise_value < CAVES_TUNNELS_THRESHOLD
-                        || rooms_noise_value > CAVES_ROOMS_THRESHOLD)
+                let tunnels_noise_value = noise_caves_tunnels.get([
+                    u * settings.caves_tunnels_scale,
+                    v * settings.caves_tunnels_scale,
+                ]);
+                let rooms_noise_value = noise_caves_rooms.get([
+                    u * settings.caves_rooms_scale,
+                    v * settings.caves_rooms_scale,
+                ]);
+
+                if mountain_noise_value > settings.mountains_caves_threshold
+                    && (tunnels_noise_value < settings.caves_tunnels_threshold
+                        || rooms_noise_value > settings.caves_rooms_threshold)
                 {
                     tile = TileId::StoneFloor.place();
                 }
             }
 
             // Lava
-            if mountain_noise_value > MOUNTAINS_LAVA_THRESHOLD {
-                let lava_noise_value = noise_lava.get([u * LAVA_SCALE, v * LAVA_SCALE]);
-                if lava_noise_value > LAVA_THRESHOLD {
+            if mountain_noise_value > settings.mountains_lava_threshold {
+                let lava_noise_value =
+                    noise_lava.get([u * settings.lava_scale, v * settings.lava_scale]);
+                if lava_noise_value > settings.lava_threshold {
                     tile = TileId::Lava.place();
                 }
             }
 
-            tiles.push(tile);
-            mountainy_count += 1;
+            tiles.push(TilePlaced {
+                roofed: true,
+                ..tile
+            });
 
             continue;
         }
 
         // Rivers
-        if mountain_noise_value < -RIVER_DEEP_THRESHOLD {
-            if ores_noise_value > ORES_THRESHOLD {
+        if mountain_noise_value < -settings.river_deep_threshold {
+            if ores_noise_value > settings.ores_threshold {
                 tiles.push(TileId::Water.with(ObjectId::FishingSpot));
             } else {
                 tiles.push(TileId::Water.place());
@@ -136,44 +260,44 @@ pub fn generate_terrain(commands: &mut Commands, seed: u32, chunk_pos: IVec2) ->
             continue;
         }
 
-        if mountain_noise_value < -RIVER_THRESHOLD {
+        if mountain_noise_value < -settings.river_threshold {
             tiles.push(TileId::ShallowWater.place());
             continue;
         }
 
         // River shores
-        if mountain_noise_value < -RIVER_SHORE_THRESHOLD {
+        if mountain_noise_value < -settings.river_shore_threshold {
             tiles.push(TileId::SandFloor.place());
             continue;
         }
 
+        let biome = biome_noise.biome_at(pos.xy()).data();
+
+        let vegetation_zones_noise_value = noise_vegetation_zones.get([
+            u * settings.vegetation_zones_scale,
+            v * settings.vegetation_zones_scale,
+        ]);
+
+        // Pools
+        if let Some(pool) = biome.pool
+            && vegetation_zones_noise_value < -settings.pools_threshold
+        {
+            tiles.push(pool.place());
+            continue;
+        }
+
         // Vegetation
         let vegetation_noise_value =
-            noise_vegetation.get([u * VEGETATION_SCALE, v * VEGETATION_SCALE]);
+            noise_vegetation.get([u * settings.vegetation_scale, v * settings.vegetation_scale]);
 
-        let vegetation_zones_noise_value =
-            noise_vegetation_zones.get([u * VEGETATION_ZONES_SCALE, v * VEGETATION_ZONES_SCALE]);
+        // Same roll for a whole vegetation cell
+        let vegetation_roll = (vegetation_noise_value * 100.0).rem_euclid(1.0);
 
-        let vegetation = if vegetation_zones_noise_value > VEGETATION_ZONES_THRESHOLD {
-            if vegetation_noise_value > TREE_THRESHOLD {
-                if climate_noise_value > DESERT_THRESHOLD {
-                    Some(ObjectId::PalmTree)
-                } else {
-                    Some(ObjectId::Tree)
-                }
-            } else if vegetation_noise_value < -PLANT_THRESHOLD {
-                if climate_noise_value > DESERT_THRESHOLD {
-                    Some(ObjectId::Cactus)
-                } else {
-                    let bush_chance = vegetation_noise_value * 100.0 % 2.0;
-                    if bush_chance > -0.3 {
-                        Some(ObjectId::BerryBush)
-                    } else if bush_chance > -0.4 {
-                        Some(ObjectId::Bush)
-                    } else {
-                        Some(ObjectId::TallGrass)
-                    }
-                }
+        let vegetation = if vegetation_zones_noise_value > biome.vegetation_threshold {
+            if vegetation_noise_value > settings.tree_threshold {
+                BiomeData::pick(biome.trees, vegetation_roll)
+            } else if vegetation_noise_value < -settings.plant_threshold {
+                BiomeData::pick(biome.plants, vegetation_roll)
             } else {
                 None
             }
@@ -183,90 +307,78 @@ pub fn generate_terrain(commands: &mut Commands, seed: u32, chunk_pos: IVec2) ->
             None
         };
 
-        let mut ground_tile = if climate_noise_value > DESERT_THRESHOLD {
-            TileId::SandFloor.place()
-        } else {
-            TileId::GrassFloor.place()
-        };
+        let mut ground_tile = biome.ground.place();
 
         if let Some(object) = vegetation {
             ground_tile = ground_tile.id.with(object);
         }
 
         tiles.push(ground_tile);
-        plainy_count += 1;
-    }
-
-    let mountainy = mountainy_count as f32 / (CHUNK_SIZE * CHUNK_SIZE) as f32 > 0.5;
-    let plainy = plainy_count as f32 / (CHUNK_SIZE * CHUNK_SIZE) as f32 > 0.5;
-
-    // Generate structures
-    let structure_noise_value = noise_structures.get([
-        chunk_pos.x as f64 * STRUCTURES_SCALE,
-        chunk_pos.y as f64 * STRUCTURES_SCALE,
-    ]);
-
-    let mut structure = match structure_noise_value {
-        0.0..=0.5 if mountainy => StructureId::DungeonCircleRoom,
-        0.5..1.0 if plainy => StructureId::Outpost,
-        _ => return tiles,
     }
-    .data();
 
-    if rng.random_bool(0.5) {
-        structure = structure.flip_horizontal();
-    }
-
-    if rng.random_bool(0.5) {
-        structure = structure.flip_vertical();
-    }
-
-    let rotation = rng.random_range(0..=3);
-    let clockwise = rng.random_bool(0.5);
-    for _ in 0..rotation {
-        structure = structure.rotate(clockwise);
-    }
-
-    let structure_size = structure.size().as_ivec2();
-
-    let structure_local_pos = IVec2::new(
-        rng.random_range(0..CHUNK_SIZE as i32 - structure_size.x),
-        rng.random_range(0..CHUNK_SIZE as i32 - structure_size.y),
-    );
-
-    let structure_pos = TilemapData::local_pos_to_global(chunk_pos, structure_local_pos);
+    // Structures are smaller than a chunk, only those anchored in this chunk or the previous
+    // ones can reach into it
+    let mut structure_mobs = vec![];
+    let anchored = |anchor_chunk_pos| {
+        anchored_structures(
+            settings,
+            seed,
+            &noise_mountains,
+            &biome_noise,
+            anchor_chunk_pos,
+        )
+    };
+    for anchor_chunk_pos in [
+        chunk_pos.xy(),
+        chunk_pos.xy() - IVec2::X,
+        chunk_pos.xy() - IVec2::Y,
+        chunk_pos.xy() - IVec2::ONE,
+    ] {
+        for (index, placed) in anchored(anchor_chunk_pos) {
+            // Overlapping structures can only be anchored in neighbouring chunks, the one
+            // first in STRUCTURE_SPAWNS then in chunk order wins whichever chunk generates
+            let priority = (index, anchor_chunk_pos.y, anchor_chunk_pos.x);
+            let overlapped = (-1..=1)
+                .flat_map(|y| (-1..=1).map(move |x| anchor_chunk_pos + IVec2::new(x, y)))
+                .any(|other_chunk_pos| {
+                    anchored(other_chunk_pos)
+                        .iter()
+                        .any(|(other_index, other)| {
+                            (*other_index, other_chunk_pos.y, other_chunk_pos.x) < priority
+                                && other.overlaps(&placed)
+                        })
+                });
 
-    for x in 0..structure.x_size() {
-        for y in 0..structure.y_size() {
-            if let Some(tile) = structure.get_tile(x, y) {
-                let pos = structure_pos + IVec2::new(x as i32, y as i32);
-                let (_, i) = TilemapData::pos_to_chunk_pos_and_local_index(pos);
-                tiles[i] = *tile;
+            if !overlapped {
+                placed.stamp(chunk_pos, &mut tiles, &mut structure_mobs);
             }
         }
     }
 
-    // Add mobs to the structure
-    for (pos, mob) in structure.mobs() {
-        let mob_pos = structure_pos + pos.as_ivec2();
-        commands.spawn(MobBundle::new(*mob, mob_pos));
+    GeneratedChunk {
+        tiles,
+        spawn_mobs,
+        structure_mobs,
     }
-
-    tiles
 }
 
 pub fn update_terrain(
     mut commands: Commands,
     mut tilemap_data: ResMut<TilemapData>,
-    q_tasks: Query<&Task>,
+    task_index: Res<TaskIndex>,
+    weather: If<Res<Weather>>,
 ) {
     let mut to_set = vec![]; //because cant modify tilemap_data while iterating
 
     let mut rng = rand::rng();
-
-    let tasks_positions = q_tasks.iter().map(|task| task.pos).collect::<HashSet<_>>();
+    let season = weather.season();
 
     for (chunk_pos, chunk) in &tilemap_data.chunks {
+        let weather_state = weather.state_at(
+            &tilemap_data,
+            TilemapData::local_pos_to_global(*chunk_pos, IVec2::ZERO),
+        );
+
         for pos in TilemapData::iter_chunk_positions(*chunk_pos) {
             let (_, i) = TilemapData::pos_to_chunk_pos_and_local_index(pos);
             let tile = chunk[i];
@@ -276,7 +388,7 @@ pub fn update_terrain(
                     ObjectId::MobLair => {
                         if rng.random_bool(0.001) {
                             for (pos, tile) in tilemap_data.neighbours(pos) {
-                                if tile.is_floor_free() && !tasks_positions.contains(&pos) {
+                                if tile.is_floor_free() && !task_index.has_task_at(pos) {
                                     commands.spawn(MobBundle::new(MobId::Snake, pos));
                                     break;
                                 }
@@ -288,7 +400,8 @@ pub fn update_terrain(
                         let chance = match tile.id {
                             TileId::GrassFloor => 0.03,
                             _ => 0.01,
-                        };
+                        } * season.crop_growth()
+                            * weather_state.crop_growth();
 
                         if rng.random_bool(chance) {
                             to_set.push((pos, tile.id.with(ObjectId::WheatPlant)));
@@ -302,7 +415,7 @@ pub fn update_terrain(
                                 ..
                             }) = tilemap_data.get(pos)
                             {
-                                return !tasks_positions.contains(&pos);
+                                return task_index.can_place(pos, TaskKind::Harvest);
                             }
 
                             false
@@ -314,8 +427,15 @@ pub fn update_terrain(
                         }
                     }
 
+                    // Frost kills the crops, farms grow again in spring
+                    ObjectId::WheatPlant if season == Season::Winter => {
+                        if rng.random_bool(0.02) {
+                            to_set.push((pos, tile.id.with(ObjectId::Farm)));
+                        }
+                    }
+
                     ObjectId::Bush => {
-                        if rng.random_bool(0.005) {
+                        if rng.random_bool(0.005 * season.berry_growth()) {
                             to_set.push((pos, tile.id.with(ObjectId::BerryBush)));
                         }
                     }
@@ -334,7 +454,7 @@ pub fn update_terrain(
                             && rng.random_bool(0.1) =>
                     {
                         for (pos, tile) in tilemap_data.neighbours(pos) {
-                            if tile.is_floor_free() && !tasks_positions.contains(&pos) {
+                            if tile.is_floor_free() && !task_index.has_task_at(pos) {
                                 to_set.push((pos, tile.id.with(ObjectId::Honeycomb)));
                                 commands.spawn(TaskBundle::new(
                                     Task::new(pos, TaskKind::Pickup, None),
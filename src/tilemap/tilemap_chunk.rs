@@ -1,10 +1,11 @@
 use bevy::{
+    platform::collections::HashMap,
     prelude::*,
     sprite_render::{AlphaMode2d, TileData, TilemapChunk, TilemapChunkTileData},
 };
 
 use crate::{
-    ChunkWeatherMaterial, SaveName, SaveScoped, TilePlaced, TilemapData, Tileset,
+    ChunkWeatherMaterial, Layer, LightLevels, SaveName, SaveScoped, TilemapData, Tileset,
     utils::transform_to_pos,
 };
 
@@ -41,9 +42,8 @@ fn new_tilemap(tileset: Handle<Image>, pos: Vec3) -> impl Bundle {
     )
 }
 
-fn chunk_pos_is_transform(chunk_pos: IVec2, transform: &Transform) -> bool {
-    let pos = transform_to_pos(transform);
-    pos.div_euclid(IVec2::splat(CHUNK_SIZE as i32)) == chunk_pos
+fn chunk_pos_is_transform(chunk_pos: IVec3, transform: &Transform, layer: &Layer) -> bool {
+    TilemapData::pos_to_chunk_pos_and_local_index(transform_to_pos(transform, layer)).0 == chunk_pos
 }
 
 pub fn manage_chunks(
@@ -51,7 +51,7 @@ pub fn manage_chunks(
     tilemap_textures: If<Res<Tileset>>,
     mut tilemap_data: ResMut<TilemapData>,
     save_name: Res<SaveName>,
-    q_chunk_layers: Query<(Entity, &Transform), With<ChunkLayer>>,
+    q_chunk_layers: Query<(Entity, &Transform, &Layer), With<ChunkLayer>>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ChunkWeatherMaterial>>,
 ) {
@@ -67,7 +67,7 @@ pub fn manage_chunks(
         // Check if the TilemapChunk exists
         if q_chunk_layers
             .iter()
-            .any(|(_, t)| chunk_pos_is_transform(chunk_pos, t))
+            .any(|(_, t, layer)| chunk_pos_is_transform(chunk_pos, t, layer))
         {
             continue;
         }
@@ -82,18 +82,21 @@ pub fn manage_chunks(
         // Tile layer
         commands.spawn((
             ChunkTileLayer,
+            Layer(chunk_pos.z),
             new_tilemap(tilemap_textures.texture.clone(), chunk_pos_f32.extend(0.0)),
         ));
 
         // Object layer
         commands.spawn((
             ChunkObjectLayer,
+            Layer(chunk_pos.z),
             new_tilemap(tilemap_textures.texture.clone(), chunk_pos_f32.extend(1.0)),
         ));
 
         // Weather layer
         commands.spawn((
             ChunkWeatherLayer,
+            Layer(chunk_pos.z),
             Mesh2d(meshes.add(Rectangle::from_length(CHUNK_SIZE as f32 * TILE_SIZE))),
             MeshMaterial2d(materials.add(ChunkWeatherMaterial::new(save_name.seed()))),
             Transform::from_translation(chunk_pos_f32.extend(100.0)),
@@ -105,8 +108,8 @@ pub fn manage_chunks(
     let chunks_to_remove = std::mem::take(&mut tilemap_data.chunks_to_remove);
 
     for chunk_pos in chunks_to_remove {
-        for (entity, transform) in q_chunk_layers {
-            if chunk_pos_is_transform(chunk_pos, transform) {
+        for (entity, transform, layer) in q_chunk_layers {
+            if chunk_pos_is_transform(chunk_pos, transform, layer) {
                 commands.entity(entity).despawn();
             }
         }
@@ -115,52 +118,70 @@ pub fn manage_chunks(
 
 pub fn update_tilemap_from_data(
     mut q_chunks_tile_layer: Query<
-        (&mut TilemapChunkTileData, &Transform),
+        (&mut TilemapChunkTileData, &Transform, &Layer),
         (With<ChunkTileLayer>, Without<ChunkObjectLayer>),
     >,
     mut q_chunks_object_layer: Query<
-        (&mut TilemapChunkTileData, &Transform),
+        (&mut TilemapChunkTileData, &Transform, &Layer),
         (With<ChunkObjectLayer>, Without<ChunkTileLayer>),
     >,
     mut tilemap_data: ResMut<TilemapData>,
+    mut light_levels: ResMut<LightLevels>,
     mut tilemap_textures: If<ResMut<Tileset>>,
 ) {
-    let tiles_to_update = tilemap_data.tiles_to_update.drain().collect::<Vec<_>>();
+    let mut tiles_to_update = tilemap_data
+        .tiles_to_update
+        .drain()
+        .collect::<HashMap<_, _>>();
+
+    // Redraw the tiles whose light changed
+    for pos in light_levels.changed.drain() {
+        if let Some(tile) = tilemap_data.get(pos) {
+            tiles_to_update.entry(pos).or_insert(tile);
+        }
+    }
 
     for (pos, tile) in tiles_to_update {
         let (chunk_pos, tile_index) = TilemapData::pos_to_chunk_pos_and_local_index(pos);
 
         // retrieve Tile layer
-        let Some((mut tile_layer_chunk_data, _)) = q_chunks_tile_layer
+        let Some((mut tile_layer_chunk_data, _, _)) = q_chunks_tile_layer
             .iter_mut()
-            .find(|(_, t)| chunk_pos_is_transform(chunk_pos, t))
+            .find(|(_, t, layer)| chunk_pos_is_transform(chunk_pos, t, layer))
         else {
             error!("Chunk not found for tile at pos {:?}", pos);
             continue;
         };
 
         // retrieve Object layer
-        let Some((mut object_layer_chunk_data, _)) = q_chunks_object_layer
+        let Some((mut object_layer_chunk_data, _, _)) = q_chunks_object_layer
             .iter_mut()
-            .find(|(_, t)| chunk_pos_is_transform(chunk_pos, t))
+            .find(|(_, t, layer)| chunk_pos_is_transform(chunk_pos, t, layer))
         else {
             error!("Chunk not found for object at pos {:?}", pos);
             continue;
         };
 
-        // Lighting: darken tiles surrounded by walls
-        let color = if tilemap_data
-            .neighbours(pos)
-            .iter()
-            .any(|(_, TilePlaced { id: tile, .. })| tile.is_transparent())
-        {
-            Color::WHITE
+        // Fog of war: hide tiles no dweller has seen
+        if !tile.discovered {
+            tile_layer_chunk_data[tile_index] = Some(TileData {
+                tileset_index: tilemap_textures.get_atlas_index_tile(tile.id.data()),
+                color: Color::BLACK,
+                ..default()
+            });
+            object_layer_chunk_data[tile_index] = None;
+            continue;
+        }
+
+        // Fire: tint burning tiles
+        let color = if tile.fire > 0 {
+            Color::srgb(1.0, 0.45, 0.15)
         } else {
-            Color::BLACK.with_alpha(0.8)
+            light_levels.color(pos)
         };
 
         tile_layer_chunk_data[tile_index] = Some(TileData {
-            tileset_index: tilemap_textures.get_atlas_index_tile(tile.id.data()),
+            tileset_index: tilemap_textures.get_atlas_index_tile(tile.displayed_id().data()),
             color,
             ..default()
         });
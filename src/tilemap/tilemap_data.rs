@@ -1,60 +1,214 @@
-use bevy::{platform::collections::HashMap, prelude::*};
+use bevy::{
+    platform::collections::{HashMap, HashSet},
+    prelude::*,
+};
 
-use crate::{CHUNK_SIZE, TilePlaced, save_load::SaveName};
+use crate::{
+    CHUNK_SIZE, TilePlaced,
+    data::{BiomeId, ObjectId, TileId},
+    save_load::SaveName,
+};
+
+// Deepest layer, the surface is layer 0
+pub const MIN_LAYER: i32 = -3;
 
 pub fn init_tilemap(commands: &mut Commands, save_name: SaveName) {
     commands.insert_resource(save_name);
     commands.insert_resource(TilemapData::default());
 }
 
+// Layer of an entity, tile positions are (x, y, layer)
+#[derive(Component, Reflect, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[reflect(Component, Default)]
+pub struct Layer(pub i32);
+
+// Chunks are keyed by (x, y, layer), each layer is an infinite plane of its own
 #[derive(Resource, Default)]
 pub struct TilemapData {
-    pub chunks: HashMap<IVec2, Vec<TilePlaced>>,
-    pub tiles_to_update: HashMap<IVec2, TilePlaced>,
-    pub chunks_to_remove: Vec<IVec2>,
+    pub chunks: HashMap<IVec3, Vec<TilePlaced>>,
+    pub biomes: HashMap<IVec3, BiomeId>,
+    pub tiles_to_update: HashMap<IVec3, TilePlaced>,
+    pub chunks_to_remove: Vec<IVec3>,
+    // Bumped whenever a path may have opened or closed, see task_assignment.rs
+    pub paths_version: u32,
+    // Chunks where fluids may have started or stopped flowing, see update_fluids
+    pub fluid_chunks: HashSet<IVec3>,
 }
 
 impl TilemapData {
-    pub fn pos_to_chunk_pos_and_local_index(pos: IVec2) -> (IVec2, usize) {
+    pub fn pos_to_chunk_pos_and_local_index(pos: IVec3) -> (IVec3, usize) {
         let size = CHUNK_SIZE as i32;
         let size_vec = IVec2::splat(size);
-        let chunk_pos = pos.div_euclid(size_vec);
-        let local = pos.rem_euclid(size_vec);
+        let chunk_pos = pos.xy().div_euclid(size_vec).extend(pos.z);
+        let local = pos.xy().rem_euclid(size_vec);
         let index = (local.y * size + local.x) as usize;
         (chunk_pos, index)
     }
 
-    pub fn chunk_pos_and_local_index_to_pos(chunk_pos: IVec2, local_index: usize) -> IVec2 {
+    pub fn chunk_pos_and_local_index_to_pos(chunk_pos: IVec3, local_index: usize) -> IVec3 {
         let size = CHUNK_SIZE as i32;
         let local_x = (local_index as i32) % size;
         let local_y = (local_index as i32) / size;
 
-        chunk_pos * size + IVec2::new(local_x, local_y)
+        Self::local_pos_to_global(chunk_pos, IVec2::new(local_x, local_y))
+    }
+
+    pub fn local_pos_to_global(chunk_pos: IVec3, local_pos: IVec2) -> IVec3 {
+        (chunk_pos.xy() * CHUNK_SIZE as i32 + local_pos).extend(chunk_pos.z)
+    }
+
+    // Walking distance lower bound, each layer change takes at least one stairs step
+    pub fn manhattan_distance(a: IVec3, b: IVec3) -> i32 {
+        let delta = a - b;
+        delta.x.abs() + delta.y.abs() + delta.z.abs()
     }
 
-    pub fn local_pos_to_global(chunk_pos: IVec2, local_pos: IVec2) -> IVec2 {
-        chunk_pos * CHUNK_SIZE as i32 + local_pos
+    // Position linked by stairs, on the layer below for StairsDown and above for StairsUp
+    pub fn stairs_link(pos: IVec3, object: ObjectId) -> Option<IVec3> {
+        let link = match object {
+            ObjectId::StairsDown => pos - IVec3::Z,
+            ObjectId::StairsUp => pos + IVec3::Z,
+            _ => return None,
+        };
+
+        (MIN_LAYER..=0).contains(&link.z).then_some(link)
     }
 
-    pub fn iter_chunk_positions(chunk_pos: IVec2) -> impl Iterator<Item = IVec2> {
+    // Stairs lead somewhere when the other end has the opposite stairs
+    pub fn stairs_destination(&self, pos: IVec3) -> Option<IVec3> {
+        let link = Self::stairs_link(pos, self.get(pos)?.object?)?;
+        let back = Self::stairs_link(link, self.get(link)?.object?)?;
+        (back == pos).then_some(link)
+    }
+
+    // Other end of stairs about to be built at pos, if it can hold the opposite stairs:
+    // walls are dug through, but objects and fluids are never overwritten
+    pub fn stairs_free_link(&self, pos: IVec3, object: ObjectId) -> Option<IVec3> {
+        let link = Self::stairs_link(pos, object)?;
+        let tile = self.get(link)?;
+
+        (tile.object.is_none() && tile.fluid == 0 && !tile.id.is_fluid()).then_some(link)
+    }
+
+    // Dig the other end of stairs just built, with the opposite stairs
+    pub fn connect_stairs(&mut self, pos: IVec3) -> bool {
+        let Some(object) = self.get(pos).and_then(|tile| tile.object) else {
+            return false;
+        };
+        let Some(link) = self.stairs_free_link(pos, object) else {
+            return false;
+        };
+        let Some(tile) = self.get(link) else {
+            return false;
+        };
+
+        let other_end = if object == ObjectId::StairsDown {
+            ObjectId::StairsUp
+        } else {
+            ObjectId::StairsDown
+        };
+
+        let floor = if tile.id.data().is_wall() {
+            TileId::StoneFloor
+        } else {
+            tile.id
+        };
+
+        self.set(
+            link,
+            TilePlaced {
+                built_object: true,
+                ..floor.with(other_end)
+            },
+        );
+        self.discover(link);
+        true
+    }
+
+    pub fn iter_chunk_positions(chunk_pos: IVec3) -> impl Iterator<Item = IVec3> {
         (0..CHUNK_SIZE).flat_map(move |y| {
             (0..CHUNK_SIZE)
                 .map(move |x| Self::local_pos_to_global(chunk_pos, IVec2::new(x as i32, y as i32)))
         })
     }
 
-    pub fn set(&mut self, pos: IVec2, tile: TilePlaced) {
+    pub fn set(&mut self, pos: IVec3, mut tile: TilePlaced) {
+        // Keep the fluid level when only the object changes
+        if let Some(old_tile) = self.get(pos)
+            && old_tile.id == tile.id
+        {
+            tile.fluid = old_tile.fluid;
+        }
+
+        self.set_fluid(pos, tile);
+    }
+
+    // Also overwrites the fluid level, but never hides a discovered tile
+    pub fn set_fluid(&mut self, pos: IVec3, mut tile: TilePlaced) {
+        let mut fluid_changed = true;
+
+        if let Some(old_tile) = self.get(pos) {
+            if old_tile.discovered {
+                tile.discovered = true;
+            }
+            // Built tiles and objects stay built while they only change state (e.g. crops growing)
+            if old_tile.id == tile.id && tile.built_over.is_none() {
+                tile.built_over = old_tile.built_over;
+            }
+            if old_tile.object.is_some() && tile.object.is_some() {
+                tile.built_object |= old_tile.built_object;
+            }
+            // The rock above stays when the tile is dug out
+            tile.roofed |= old_tile.roofed;
+            let is_stairs =
+                |object| matches!(object, Some(ObjectId::StairsDown | ObjectId::StairsUp));
+            if old_tile.is_blocking() != tile.is_blocking()
+                || (old_tile.object != tile.object
+                    && (is_stairs(old_tile.object) || is_stairs(tile.object)))
+            {
+                self.paths_version = self.paths_version.wrapping_add(1);
+            }
+            fluid_changed = old_tile.fluid != tile.fluid
+                || old_tile.id != tile.id
+                || old_tile.can_hold_fluid() != tile.can_hold_fluid();
+        }
+
+        // Objects bring their own spoilage, see Dweller::object_spoilage
+        if tile.object.is_none() {
+            tile.built_object = false;
+            tile.spoilage = 0;
+        }
+
         self.tiles_to_update.insert(pos, tile);
-        self.tiles_to_update.extend(self.neighbours(pos)); // necessary for lighting
 
         let (chunk_pos, tile_index) = Self::pos_to_chunk_pos_and_local_index(pos);
+        if fluid_changed {
+            self.fluid_chunks.insert(chunk_pos);
+        }
         self.chunks
             .entry(chunk_pos)
             .or_insert_with(|| vec![TilePlaced::default(); (CHUNK_SIZE * CHUNK_SIZE) as usize])
             [tile_index] = tile;
     }
 
-    pub fn get(&self, pos: IVec2) -> Option<TilePlaced> {
+    pub fn discover(&mut self, pos: IVec3) {
+        if let Some(tile) = self.get(pos) {
+            self.set_fluid(
+                pos,
+                TilePlaced {
+                    discovered: true,
+                    ..tile
+                },
+            );
+        }
+    }
+
+    pub fn biome(&self, pos: IVec3) -> Option<BiomeId> {
+        let (chunk_pos, _) = Self::pos_to_chunk_pos_and_local_index(pos);
+        self.biomes.get(&chunk_pos).copied()
+    }
+
+    pub fn get(&self, pos: IVec3) -> Option<TilePlaced> {
         let (chunk_pos, tile_index) = Self::pos_to_chunk_pos_and_local_index(pos);
         self.chunks
             .get(&chunk_pos)
@@ -62,7 +216,7 @@ impl TilemapData {
             .copied()
     }
 
-    pub fn set_chunk(&mut self, chunk_pos: IVec2, chunk_data: Vec<TilePlaced>) {
+    pub fn set_chunk(&mut self, chunk_pos: IVec3, chunk_data: Vec<TilePlaced>) {
         self.tiles_to_update.extend(
             chunk_data
                 .iter()
@@ -71,15 +225,27 @@ impl TilemapData {
         );
 
         self.chunks.insert(chunk_pos, chunk_data);
+        self.paths_version = self.paths_version.wrapping_add(1);
+        self.fluid_chunks.insert(chunk_pos);
+    }
+
+    // Map of a single chunk filled with the given tile
+    #[cfg(test)]
+    pub fn filled_chunk(chunk_pos: IVec3, tile: TilePlaced) -> Self {
+        let mut tilemap_data = Self::default();
+        tilemap_data.set_chunk(chunk_pos, vec![tile; (CHUNK_SIZE * CHUNK_SIZE) as usize]);
+        tilemap_data
     }
 
-    pub fn remove_chunk(&mut self, pos: IVec2) -> Option<Vec<TilePlaced>> {
+    pub fn remove_chunk(&mut self, pos: IVec3) -> Option<Vec<TilePlaced>> {
         self.chunks_to_remove.push(pos);
+        self.paths_version = self.paths_version.wrapping_add(1);
+        self.biomes.remove(&pos);
         self.chunks.remove(&pos)
     }
 
-    pub fn neighbours(&self, pos: IVec2) -> Vec<(IVec2, TilePlaced)> {
-        [IVec2::X, IVec2::Y, IVec2::NEG_X, IVec2::NEG_Y]
+    pub fn neighbours(&self, pos: IVec3) -> Vec<(IVec3, TilePlaced)> {
+        [IVec3::X, IVec3::Y, IVec3::NEG_X, IVec3::NEG_Y]
             .into_iter()
             .filter_map(|p| {
                 let neigh_pos = pos + p;
@@ -93,8 +259,8 @@ impl TilemapData {
             .collect()
     }
 
-    pub fn non_blocking_neighbours_pos(&self, pos: IVec2, diagonal: bool) -> Vec<IVec2> {
-        let mut result: Vec<IVec2> = self
+    pub fn non_blocking_neighbours_pos(&self, pos: IVec3, diagonal: bool) -> Vec<IVec3> {
+        let mut result: Vec<IVec3> = self
             .neighbours(pos)
             .into_iter()
             .filter_map(
@@ -104,12 +270,20 @@ impl TilemapData {
             )
             .collect();
 
+        if let Some(destination) = self.stairs_destination(pos)
+            && self
+                .get(destination)
+                .is_some_and(|tile| !tile.is_blocking())
+        {
+            result.push(destination);
+        }
+
         if diagonal {
-            const DIAGONAL_DIRECTIONS: [IVec2; 4] = [
-                IVec2::new(1, 1),
-                IVec2::new(-1, 1),
-                IVec2::new(1, -1),
-                IVec2::new(-1, -1),
+            const DIAGONAL_DIRECTIONS: [IVec3; 4] = [
+                IVec3::new(1, 1, 0),
+                IVec3::new(-1, 1, 0),
+                IVec3::new(1, -1, 0),
+                IVec3::new(-1, -1, 0),
             ];
 
             for diag in DIAGONAL_DIRECTIONS {
@@ -118,7 +292,7 @@ impl TilemapData {
                 if let Some(diag_tile) = self.get(diag_pos)
                     && !diag_tile.is_blocking()
                 {
-                    let adj_blocking = [IVec2::new(diag.x, 0), IVec2::new(0, diag.y)]
+                    let adj_blocking = [IVec3::new(diag.x, 0, 0), IVec3::new(0, diag.y, 0)]
                         .into_iter()
                         .any(|adj| {
                             self.get(pos + adj).is_none_or(|t| t.id.data().is_wall())
@@ -136,17 +310,17 @@ impl TilemapData {
     }
 
     pub fn find_from_center_chunk_size(
-        center: IVec2,
-        is_valid: impl Fn(IVec2) -> bool,
-    ) -> Option<IVec2> {
+        center: IVec3,
+        is_valid: impl Fn(IVec3) -> bool,
+    ) -> Option<IVec3> {
         Self::find_from_center(center, CHUNK_SIZE / 2, is_valid)
     }
 
     pub fn find_from_center(
-        center: IVec2,
+        center: IVec3,
         radius: u32,
-        is_valid: impl Fn(IVec2) -> bool,
-    ) -> Option<IVec2> {
+        is_valid: impl Fn(IVec3) -> bool,
+    ) -> Option<IVec3> {
         let radius = radius as i32;
 
         if is_valid(center) {
@@ -155,7 +329,7 @@ impl TilemapData {
 
         // Explore in a spiral pattern
         for layer in 1..=radius {
-            let mut position = center + IVec2::new(-layer, -layer);
+            let mut position = center + IVec3::new(-layer, -layer, 0);
 
             // Top edge: Left to right
             for _ in 0..2 * layer {
@@ -193,10 +367,25 @@ impl TilemapData {
         None
     }
 
-    pub fn astar_successors(&self) -> impl FnMut(&IVec2) -> Vec<(IVec2, i32)> {
+    // Highest scoring position around the center, positions without a score are skipped
+    pub fn find_best_from_center(
+        center: IVec3,
+        radius: u32,
+        score: impl Fn(IVec3) -> Option<i32>,
+    ) -> Option<IVec3> {
+        let radius = radius as i32;
+
+        (-radius..=radius)
+            .flat_map(|dy| (-radius..=radius).map(move |dx| center + IVec3::new(dx, dy, 0)))
+            .filter_map(|pos| score(pos).map(|score| (pos, score)))
+            .max_by_key(|(_, score)| *score)
+            .map(|(pos, _)| pos)
+    }
+
+    pub fn astar_successors(&self) -> impl FnMut(&IVec3) -> Vec<(IVec3, i32)> {
         const ASTAR_MAX_NODES: usize = 1000;
         let mut nodes_explored = 0;
-        move |p: &IVec2| {
+        move |p: &IVec3| {
             nodes_explored += 1;
             if nodes_explored > ASTAR_MAX_NODES {
                 return vec![];
@@ -209,3 +398,126 @@ impl TilemapData {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunk_pos_and_local_index_round_trip() {
+        let size = CHUNK_SIZE as i32;
+        for pos in [
+            IVec3::ZERO,
+            IVec3::new(size - 1, size - 1, 0),
+            IVec3::new(-1, -1, -1),
+            IVec3::new(-size, size, MIN_LAYER),
+            IVec3::new(123_456, -98_765, -2),
+        ] {
+            let (chunk_pos, index) = TilemapData::pos_to_chunk_pos_and_local_index(pos);
+            assert_eq!(chunk_pos.z, pos.z);
+            assert!(index < (CHUNK_SIZE * CHUNK_SIZE) as usize);
+            assert_eq!(
+                TilemapData::chunk_pos_and_local_index_to_pos(chunk_pos, index),
+                pos
+            );
+        }
+
+        let (chunk_pos, _) = TilemapData::pos_to_chunk_pos_and_local_index(IVec3::new(-1, 0, -3));
+        assert_eq!(chunk_pos, IVec3::new(-1, 0, -3));
+    }
+
+    #[test]
+    fn layers_do_not_share_chunks() {
+        let mut tilemap_data = TilemapData::filled_chunk(IVec3::ZERO, TileId::StoneFloor.place());
+        tilemap_data.set_chunk(
+            IVec3::NEG_Z,
+            vec![TileId::StoneWall.place(); (CHUNK_SIZE * CHUNK_SIZE) as usize],
+        );
+
+        let pos = IVec3::new(3, 4, 0);
+        assert_eq!(
+            tilemap_data.get(pos).map(|tile| tile.id),
+            Some(TileId::StoneFloor)
+        );
+        assert_eq!(
+            tilemap_data.get(pos - IVec3::Z).map(|tile| tile.id),
+            Some(TileId::StoneWall)
+        );
+        assert!(tilemap_data.get(pos - IVec3::Z * 2).is_none());
+    }
+
+    #[test]
+    fn manhattan_distance_counts_layers() {
+        assert_eq!(
+            TilemapData::manhattan_distance(IVec3::new(1, 2, 0), IVec3::new(-2, 4, -1)),
+            6
+        );
+        assert_eq!(TilemapData::manhattan_distance(IVec3::ONE, IVec3::ONE), 0);
+    }
+
+    #[test]
+    fn stairs_link_stays_within_layers() {
+        let pos = IVec3::new(5, -7, 0);
+        assert_eq!(
+            TilemapData::stairs_link(pos, ObjectId::StairsDown),
+            Some(pos - IVec3::Z)
+        );
+        assert_eq!(TilemapData::stairs_link(pos, ObjectId::StairsUp), None);
+        assert_eq!(
+            TilemapData::stairs_link(pos.with_z(MIN_LAYER), ObjectId::StairsDown),
+            None
+        );
+        assert_eq!(
+            TilemapData::stairs_link(pos.with_z(MIN_LAYER), ObjectId::StairsUp),
+            Some(pos.with_z(MIN_LAYER + 1))
+        );
+        assert_eq!(TilemapData::stairs_link(pos, ObjectId::Bed), None);
+    }
+
+    #[test]
+    fn connect_stairs_digs_but_never_overwrites() {
+        let mut tilemap_data = TilemapData::filled_chunk(IVec3::ZERO, TileId::StoneFloor.place());
+        tilemap_data.set_chunk(
+            IVec3::NEG_Z,
+            vec![TileId::StoneWall.place(); (CHUNK_SIZE * CHUNK_SIZE) as usize],
+        );
+
+        let pos = IVec3::new(2, 2, 0);
+        tilemap_data.set(pos, TileId::StoneFloor.with(ObjectId::StairsDown));
+        assert!(tilemap_data.connect_stairs(pos));
+        let link = tilemap_data.get(pos - IVec3::Z).unwrap();
+        assert_eq!(link.id, TileId::StoneFloor);
+        assert_eq!(link.object, Some(ObjectId::StairsUp));
+        assert!(link.discovered);
+        assert_eq!(tilemap_data.stairs_destination(pos), Some(pos - IVec3::Z));
+
+        let other = IVec3::new(6, 2, 0);
+        tilemap_data.set(other - IVec3::Z, TileId::StoneFloor.with(ObjectId::Bed));
+        tilemap_data.set(other, TileId::StoneFloor.with(ObjectId::StairsDown));
+        assert!(!tilemap_data.connect_stairs(other));
+        assert_eq!(
+            tilemap_data
+                .get(other - IVec3::Z)
+                .and_then(|tile| tile.object),
+            Some(ObjectId::Bed)
+        );
+    }
+
+    #[test]
+    fn objects_keep_their_spoilage() {
+        let mut tilemap_data = TilemapData::filled_chunk(IVec3::ZERO, TileId::StoneFloor.place());
+        let pos = IVec3::new(3, 3, 0);
+
+        tilemap_data.set(
+            pos,
+            TilePlaced {
+                spoilage: 40,
+                ..TileId::StoneFloor.with(ObjectId::Bread)
+            },
+        );
+        assert_eq!(tilemap_data.get(pos).unwrap().spoilage, 40);
+
+        tilemap_data.set(pos, TileId::StoneFloor.place());
+        assert_eq!(tilemap_data.get(pos).unwrap().spoilage, 0);
+    }
+}
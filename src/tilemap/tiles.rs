@@ -1,12 +1,23 @@
 use bevy::prelude::*;
 use bitcode::{Decode, Encode};
 
-use crate::data::{ObjectId, TileId};
+use crate::{
+    FLUID_SOURCE,
+    data::{ObjectId, TileId},
+};
 
 #[derive(Clone, Copy, Encode, Decode, Reflect, Default, Debug)]
 pub struct TilePlaced {
     pub id: TileId,
     pub object: Option<ObjectId>,
+    pub fluid: u8,        // Fluid level, see fluids.rs
+    pub fire: u8,         // Remaining fuel while burning, see temperature.rs
+    pub discovered: bool, // Seen by a dweller, see fog_of_war.rs
+    pub spoilage: u8,     // Rot of food objects, see spoilage.rs
+    // Only what dwellers built can be deconstructed, see BuildResult::recipe_on_tile
+    pub built_object: bool,
+    pub built_over: Option<TileId>, // Tile restored when the built tile is deconstructed
+    pub roofed: bool,               // Under rock, kept when dug out, see lighting.rs
 }
 
 impl TilePlaced {
@@ -58,6 +69,13 @@ impl TileId {
         TilePlaced {
             id: self,
             object: Some(object_id),
+            fluid: self.default_fluid(),
+            fire: 0,
+            discovered: false,
+            spoilage: 0,
+            built_object: false,
+            built_over: None,
+            roofed: false,
         }
     }
 
@@ -65,9 +83,25 @@ impl TileId {
         TilePlaced {
             id: self,
             object: None,
+            fluid: self.default_fluid(),
+            fire: 0,
+            discovered: false,
+            spoilage: 0,
+            built_object: false,
+            built_over: None,
+            roofed: false,
         }
     }
 
+    // Fluids placed by terrain generation or by tasks are sources
+    fn default_fluid(self) -> u8 {
+        if self.is_fluid() { FLUID_SOURCE } else { 0 }
+    }
+
+    pub fn is_fluid(self) -> bool {
+        matches!(self, Self::Water | Self::ShallowWater | Self::Lava)
+    }
+
     pub fn is_transparent(self) -> bool {
         !self.data().is_wall() || matches!(self, Self::Water | Self::Lava)
     }
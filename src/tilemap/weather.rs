@@ -6,17 +6,29 @@ use bevy::{
 };
 use rand::prelude::*;
 
-use crate::save_load::SaveName;
+use crate::{
+    CHUNK_SIZE, Climate, FLUID_MAX, Layer, TilePlaced, TilemapData, data::TileId, ignite,
+    save_load::SaveName, task_index::TaskIndex, utils::transform_to_pos,
+};
 
 const DAY_LENGTH_SECS: f32 = 300.0;
+const DAYS_PER_SEASON: u32 = 5;
 const WIND_CHANGE_CHANCE: f64 = 0.0001;
 
+// The weather state of each climate may change once per step
+const WEATHER_STEP_SECS: f32 = 60.0;
+const LIGHTNING_CHANCE: f64 = 0.05;
+
 #[derive(Resource, Reflect)]
 #[reflect(Resource)]
 pub struct Weather {
     pub elapsed_secs: f32,
     pub wind: Vec2,
     pub target_wind: Vec2,
+    #[reflect(default)]
+    pub states: [WeatherState; Climate::ALL.len()],
+    #[reflect(default)]
+    pub step: u32,
 }
 
 impl Weather {
@@ -25,6 +37,175 @@ impl Weather {
             elapsed_secs: 0.0,
             wind: random_wind(seed),
             target_wind: random_wind(seed),
+            states: default(),
+            step: 0,
+        }
+    }
+
+    pub fn state(&self, climate: Climate) -> WeatherState {
+        self.states[climate as usize]
+    }
+
+    // Weather of the climate zone of the chunk containing pos, always clear underground
+    pub fn state_at(&self, tilemap_data: &TilemapData, pos: IVec3) -> WeatherState {
+        tilemap_data
+            .biome(pos)
+            .map_or(WeatherState::Clear, |biome| {
+                self.state(biome.data().climate)
+            })
+    }
+
+    // 0.0 (midnight) 0.5 (noon), start in the morning
+    pub fn time_of_day(&self) -> f32 {
+        (self.elapsed_secs + DAY_LENGTH_SECS / 4.0) / DAY_LENGTH_SECS % 1.0
+    }
+
+    // 0.0 at midnight, 1.0 at noon
+    pub fn daylight(&self) -> f32 {
+        (((self.time_of_day() - 0.5) * std::f32::consts::TAU).cos() * 0.5) + 0.5
+    }
+
+    // Calendar: days since the world was created, counted from midnight
+    fn days(&self) -> u32 {
+        ((self.elapsed_secs + DAY_LENGTH_SECS / 4.0) / DAY_LENGTH_SECS) as u32
+    }
+
+    pub fn season(&self) -> Season {
+        let seasons = self.days() / DAYS_PER_SEASON;
+        Season::ALL[seasons as usize % Season::ALL.len()]
+    }
+
+    // Starts at 1
+    pub fn day_of_season(&self) -> u32 {
+        self.days() % DAYS_PER_SEASON + 1
+    }
+
+    // Starts at 1
+    pub fn year(&self) -> u32 {
+        self.days() / (DAYS_PER_SEASON * Season::ALL.len() as u32) + 1
+    }
+
+    pub fn clock(&self) -> String {
+        let minutes = (self.time_of_day() * 24.0 * 60.0) as u32;
+        format!(
+            "{:?} {}, Year {} - {:02}:{:02}",
+            self.season(),
+            self.day_of_season(),
+            self.year(),
+            minutes / 60,
+            minutes % 60
+        )
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Season {
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+impl Season {
+    const ALL: [Season; 4] = [
+        Season::Spring,
+        Season::Summer,
+        Season::Autumn,
+        Season::Winter,
+    ];
+
+    // Multiplies the chance of Farm growing into WheatPlant
+    pub fn crop_growth(self) -> f64 {
+        match self {
+            Season::Spring => 1.0,
+            Season::Summer => 1.5,
+            Season::Autumn => 0.5,
+            Season::Winter => 0.0,
+        }
+    }
+
+    // Multiplies the chance of Bush growing berries
+    pub fn berry_growth(self) -> f64 {
+        match self {
+            Season::Spring => 0.3,
+            Season::Summer => 1.0,
+            Season::Autumn => 1.5,
+            Season::Winter => 0.0,
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug, Default, Reflect)]
+pub enum WeatherState {
+    #[default]
+    Clear,
+    Rain,
+    Thunderstorm,
+    Snow,
+    Heatwave,
+}
+
+impl WeatherState {
+    // Markov chain: weights of the next states
+    fn transitions(self, climate: Climate) -> &'static [(WeatherState, u32)] {
+        use WeatherState::{Clear, Heatwave, Rain, Snow, Thunderstorm};
+
+        match (climate, self) {
+            (Climate::Temperate, Clear) => {
+                &[(Clear, 70), (Rain, 20), (Thunderstorm, 5), (Heatwave, 5)]
+            }
+            (Climate::Temperate, Rain | Snow) => &[(Clear, 40), (Rain, 45), (Thunderstorm, 15)],
+            (Climate::Temperate, Thunderstorm) => &[(Clear, 30), (Rain, 50), (Thunderstorm, 20)],
+            (Climate::Temperate, Heatwave) => &[(Clear, 60), (Heatwave, 40)],
+            (Climate::Desert, Clear) => &[(Clear, 70), (Heatwave, 25), (Thunderstorm, 5)],
+            (Climate::Desert, Heatwave) => &[(Clear, 40), (Heatwave, 60)],
+            (Climate::Desert, Rain | Thunderstorm | Snow) => {
+                &[(Clear, 80), (Rain, 10), (Thunderstorm, 10)]
+            }
+        }
+    }
+
+    // It only snows in temperate winters, and heatwaves don't happen in winter
+    fn in_season(self, climate: Climate, season: Season) -> Self {
+        match (self, climate, season) {
+            (WeatherState::Rain | WeatherState::Snow, Climate::Temperate, Season::Winter) => {
+                WeatherState::Snow
+            }
+            (WeatherState::Snow, _, _) => WeatherState::Rain,
+            (WeatherState::Heatwave, _, Season::Winter) => WeatherState::Clear,
+            _ => self,
+        }
+    }
+
+    fn next(self, climate: Climate, season: Season, rng: &mut StdRng) -> Self {
+        self.transitions(climate)
+            .choose_weighted(rng, |(_, weight)| *weight)
+            .map_or(self, |(state, _)| state.in_season(climate, season))
+    }
+
+    // Multiplies the chance of Farm growing into WheatPlant
+    pub fn crop_growth(self) -> f64 {
+        match self {
+            WeatherState::Clear => 1.0,
+            WeatherState::Rain | WeatherState::Thunderstorm => 1.5,
+            WeatherState::Snow => 0.25,
+            WeatherState::Heatwave => 0.5,
+        }
+    }
+
+    // Multiplies the chance of fire spreading to a neighbour
+    pub fn fire_spread(self) -> f32 {
+        match self {
+            WeatherState::Clear => 1.0,
+            WeatherState::Rain | WeatherState::Thunderstorm | WeatherState::Snow => 0.3,
+            WeatherState::Heatwave => 2.0,
+        }
+    }
+
+    pub fn speed_ratio(self) -> f32 {
+        match self {
+            WeatherState::Snow => 0.6,
+            _ => 1.0,
         }
     }
 }
@@ -39,6 +220,14 @@ pub struct ChunkWeatherMaterial {
     pub wind: Vec2,
     #[uniform(0)]
     pub cloud_opacity: f32,
+    #[uniform(0)]
+    pub rain: f32,
+    #[uniform(0)]
+    pub snow: f32,
+    #[uniform(0)]
+    pub storm: f32,
+    #[uniform(0)]
+    pub heat: f32,
 }
 
 impl ChunkWeatherMaterial {
@@ -48,6 +237,28 @@ impl ChunkWeatherMaterial {
             time_of_day: 0.0,
             wind: Vec2::ZERO,
             cloud_opacity: 1.0,
+            rain: 0.0,
+            snow: 0.0,
+            storm: 0.0,
+            heat: 0.0,
+        }
+    }
+
+    fn set_state(&mut self, state: WeatherState) {
+        self.rain = 0.0;
+        self.snow = 0.0;
+        self.storm = 0.0;
+        self.heat = 0.0;
+
+        match state {
+            WeatherState::Clear => {}
+            WeatherState::Rain => self.rain = 1.0,
+            WeatherState::Thunderstorm => {
+                self.rain = 1.0;
+                self.storm = 1.0;
+            }
+            WeatherState::Snow => self.snow = 1.0,
+            WeatherState::Heatwave => self.heat = 1.0,
         }
     }
 }
@@ -73,28 +284,49 @@ pub fn random_wind(seed: u32) -> Vec2 {
 pub fn update_weather(
     time: Res<Time>,
     save_name: Res<SaveName>,
+    tilemap_data: Res<TilemapData>,
     mut weather: If<ResMut<Weather>>,
-    query: Query<&MeshMaterial2d<ChunkWeatherMaterial>>,
+    query: Query<(&MeshMaterial2d<ChunkWeatherMaterial>, &Transform, &Layer)>,
     mut materials: ResMut<Assets<ChunkWeatherMaterial>>,
 ) {
     let mut rng = rand::rng();
+    let seed = save_name.seed();
 
     if rng.random_bool(WIND_CHANGE_CHANCE) {
-        weather.target_wind = random_wind(save_name.seed());
+        weather.target_wind = random_wind(seed);
     }
 
     weather.elapsed_secs += time.delta_secs();
 
+    // Seeded so a save replays the same weather
+    let step = (weather.elapsed_secs / WEATHER_STEP_SECS) as u32;
+    if step != weather.step {
+        weather.step = step;
+        let season = weather.season();
+
+        for climate in Climate::ALL {
+            let mut rng: StdRng = SeedableRng::seed_from_u64(
+                (seed as u64) << 32 | (step as u64) << 1 | climate as u64,
+            );
+            let state = weather.state(climate).next(climate, season, &mut rng);
+
+            if state != weather.state(climate) {
+                debug!("{climate:?} weather: {state:?}");
+            }
+            weather.states[climate as usize] = state;
+        }
+    }
+
     let lerp_speed = 0.01 * time.delta_secs();
     weather.wind = weather.wind.lerp(weather.target_wind, lerp_speed);
 
-    // start in the morning
-    let time_of_day = (weather.elapsed_secs + DAY_LENGTH_SECS / 4.0) / DAY_LENGTH_SECS % 1.0;
+    let time_of_day = weather.time_of_day();
 
-    for material in query.iter() {
+    for (material, transform, layer) in query.iter() {
         let mut material = materials.get_mut(material).unwrap();
         material.wind = weather.wind * weather.elapsed_secs;
         material.time_of_day = time_of_day;
+        material.set_state(weather.state_at(&tilemap_data, transform_to_pos(transform, layer)));
     }
 }
 
@@ -114,3 +346,136 @@ pub fn update_cloud_opacity(
         material.cloud_opacity = (projection.scale.powf(2.0) - 0.3).clamp(0.0, 1.0);
     }
 }
+
+// Rain refills drained or flowing pools, flooded floors still drain
+fn rain_refill(tile: TilePlaced) -> Option<TilePlaced> {
+    (tile.id == TileId::ShallowWater && tile.fluid < FLUID_MAX).then_some(TilePlaced {
+        fluid: FLUID_MAX,
+        ..tile
+    })
+}
+
+// Rain refills pools and thunderstorms strike lightning
+pub fn update_weather_events(
+    mut commands: Commands,
+    mut tilemap_data: ResMut<TilemapData>,
+    weather: If<Res<Weather>>,
+    task_index: Res<TaskIndex>,
+) {
+    let mut rng = rand::rng();
+
+    let mut refilled = vec![];
+    let mut strikes = vec![];
+
+    for (chunk_pos, tiles) in &tilemap_data.chunks {
+        let state = weather.state_at(
+            &tilemap_data,
+            TilemapData::local_pos_to_global(*chunk_pos, IVec2::ZERO),
+        );
+
+        if matches!(state, WeatherState::Rain | WeatherState::Thunderstorm) {
+            for (pos, tile) in TilemapData::iter_chunk_positions(*chunk_pos).zip(tiles) {
+                if let Some(tile) = rain_refill(*tile) {
+                    refilled.push((pos, tile));
+                }
+            }
+        }
+
+        if state == WeatherState::Thunderstorm && rng.random_bool(LIGHTNING_CHANCE) {
+            let local_pos = IVec2::new(
+                rng.random_range(0..CHUNK_SIZE as i32),
+                rng.random_range(0..CHUNK_SIZE as i32),
+            );
+            strikes.push(TilemapData::local_pos_to_global(*chunk_pos, local_pos));
+        }
+    }
+
+    for (pos, tile) in refilled {
+        tilemap_data.set_fluid(pos, tile);
+    }
+
+    for pos in strikes {
+        if tilemap_data.get(pos).is_some_and(TilePlaced::is_outdoors)
+            && ignite(&mut commands, &mut tilemap_data, &task_index, pos)
+        {
+            debug!("Lightning struck at {pos:?}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn weather_at(elapsed_secs: f32) -> Weather {
+        Weather {
+            elapsed_secs,
+            ..Weather::new(0)
+        }
+    }
+
+    // Elapsed seconds at midnight starting the given day
+    fn midnight(day: u32) -> f32 {
+        day as f32 * DAY_LENGTH_SECS - DAY_LENGTH_SECS / 4.0
+    }
+
+    #[test]
+    fn time_of_day() {
+        let weather = weather_at(0.0);
+        assert!((weather.time_of_day() - 0.25).abs() < 1e-6);
+        assert_eq!(weather.clock(), "Spring 1, Year 1 - 06:00");
+
+        let noon = weather_at(DAY_LENGTH_SECS / 4.0);
+        assert!((noon.time_of_day() - 0.5).abs() < 1e-6);
+        assert!((noon.daylight() - 1.0).abs() < 1e-6);
+
+        let night = weather_at(midnight(1));
+        assert!(night.time_of_day().abs() < 1e-6);
+        assert!(night.daylight().abs() < 1e-6);
+        assert_eq!(night.day_of_season(), 2);
+    }
+
+    #[test]
+    fn seasons() {
+        let last_day = weather_at(midnight(DAYS_PER_SEASON) - 1.0);
+        assert_eq!(last_day.season(), Season::Spring);
+        assert_eq!(last_day.day_of_season(), DAYS_PER_SEASON);
+
+        let first_day = weather_at(midnight(DAYS_PER_SEASON));
+        assert_eq!(first_day.season(), Season::Summer);
+        assert_eq!(first_day.day_of_season(), 1);
+
+        let winter = weather_at(midnight(DAYS_PER_SEASON * 3));
+        assert_eq!(winter.season(), Season::Winter);
+        assert_eq!(winter.year(), 1);
+    }
+
+    #[test]
+    fn years() {
+        let new_year = weather_at(midnight(DAYS_PER_SEASON * Season::ALL.len() as u32));
+        assert_eq!(new_year.season(), Season::Spring);
+        assert_eq!(new_year.day_of_season(), 1);
+        assert_eq!(new_year.year(), 2);
+        assert_eq!(new_year.clock(), "Spring 1, Year 2 - 00:00");
+    }
+
+    #[test]
+    fn rain_only_refills_pools() {
+        let pool = TileId::ShallowWater.place();
+        assert!(rain_refill(pool).is_none());
+        assert_eq!(
+            rain_refill(TilePlaced { fluid: 0, ..pool }).map(|tile| tile.fluid),
+            Some(FLUID_MAX)
+        );
+        assert_eq!(
+            rain_refill(TilePlaced { fluid: 3, ..pool }).map(|tile| tile.fluid),
+            Some(FLUID_MAX)
+        );
+
+        let flooded = TilePlaced {
+            fluid: 3,
+            ..TileId::StoneFloor.place()
+        };
+        assert!(rain_refill(flooded).is_none());
+    }
+}
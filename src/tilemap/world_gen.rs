@@ -0,0 +1,255 @@
+use std::time::{Instant, SystemTime};
+
+use bevy::{
+    asset::RenderAssetUsages,
+    prelude::*,
+    reflect::Struct,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+
+use crate::{
+    CHUNK_SIZE, SaveName, TilePlaced, TilemapData,
+    data::{ObjectId, TileId},
+    generate_chunk, random_world_name,
+};
+
+const SETTINGS_PATH: &str = "assets/world_gen.txt";
+const PREVIEW_CHUNKS: u32 = 4;
+
+// Noise scales and thresholds used by generate_chunk, saved with the world so that
+// tweaking the file doesn't create seams in existing saves
+#[derive(Resource, Reflect, Clone, Debug)]
+#[reflect(Resource, Default)]
+pub struct WorldGenSettings {
+    pub mobs_scale: f64,
+    pub mobs_threshold: f64,
+
+    pub mountains_scale: f64,
+    pub mountains_dirt_threshold: f64,
+    pub mountains_stone_threshold: f64,
+
+    pub mountains_caves_threshold: f64,
+    pub caves_tunnels_scale: f64,
+    pub caves_tunnels_threshold: f64,
+    pub caves_rooms_scale: f64,
+    pub caves_rooms_threshold: f64,
+
+    pub mountains_lava_threshold: f64,
+    pub lava_scale: f64,
+    pub lava_threshold: f64,
+
+    pub ores_scale: f64,
+    pub ores_threshold: f64,
+    pub ores_kind_scale: f64,
+
+    pub river_deep_threshold: f64,
+    pub river_threshold: f64,
+    pub river_shore_threshold: f64,
+
+    pub vegetation_zones_scale: f64,
+    pub pools_threshold: f64,
+
+    pub vegetation_scale: f64,
+    pub tree_threshold: f64,
+    pub plant_threshold: f64,
+
+    // Underground layers, the bonuses are per layer of depth
+    pub layer_ores_bonus: f64,
+    pub layer_lava_threshold: f64,
+    pub layer_lava_bonus: f64,
+}
+
+impl Default for WorldGenSettings {
+    fn default() -> Self {
+        Self {
+            mobs_scale: 0.1,
+            mobs_threshold: 0.0,
+
+            mountains_scale: 0.004,
+            mountains_dirt_threshold: -0.3,
+            mountains_stone_threshold: -0.2,
+
+            mountains_caves_threshold: 0.0,
+            caves_tunnels_scale: 0.05,
+            caves_tunnels_threshold: 0.05,
+            caves_rooms_scale: 0.1,
+            caves_rooms_threshold: 0.9,
+
+            mountains_lava_threshold: 0.5,
+            lava_scale: 0.1,
+            lava_threshold: 0.0,
+
+            ores_scale: 0.2,
+            ores_threshold: 0.7,
+            ores_kind_scale: 0.05,
+
+            river_deep_threshold: 0.75,
+            river_threshold: 0.7,
+            river_shore_threshold: 0.65,
+
+            vegetation_zones_scale: 0.05,
+            pools_threshold: 0.6,
+
+            vegetation_scale: 0.5,
+            tree_threshold: 0.4,
+            plant_threshold: 0.6,
+
+            layer_ores_bonus: 0.05,
+            layer_lava_threshold: 0.6,
+            layer_lava_bonus: 0.15,
+        }
+    }
+}
+
+impl WorldGenSettings {
+    // One `field_name = value` per line, `#` starts a comment, missing fields keep their default
+    fn parse(content: &str) -> Self {
+        let mut settings = Self::default();
+
+        for (line_index, line) in content.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (name, value) = line.split_once('=').unwrap_or((line, ""));
+            let field = settings
+                .field_mut(name.trim())
+                .and_then(|field| field.try_downcast_mut::<f64>());
+
+            match (field, value.trim().parse::<f64>()) {
+                (Some(field), Ok(value)) => *field = value,
+                _ => error!(
+                    "Invalid world generation setting line {}: {line}",
+                    line_index + 1
+                ),
+            }
+        }
+
+        settings
+    }
+
+    // Last edit of the settings file, to refresh the preview while tuning
+    pub fn modified() -> Option<SystemTime> {
+        std::fs::metadata(SETTINGS_PATH)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    pub fn load() -> Self {
+        match std::fs::read_to_string(SETTINGS_PATH) {
+            Ok(content) => Self::parse(&content),
+            Err(_) => {
+                debug!("No world generation settings found, using defaults");
+                Self::default()
+            }
+        }
+    }
+}
+
+fn map_color(tile: TilePlaced) -> [u8; 3] {
+    match tile.object {
+        Some(ObjectId::Tree | ObjectId::PalmTree) => return [34, 85, 34],
+        Some(ObjectId::Bush | ObjectId::BerryBush | ObjectId::TallGrass | ObjectId::Cactus) => {
+            return [70, 120, 50];
+        }
+        Some(ObjectId::CopperOre) => return [190, 110, 60],
+        Some(ObjectId::TinOre) => return [170, 170, 180],
+        Some(ObjectId::IronOre) => return [140, 95, 85],
+        Some(ObjectId::SilverOre) => return [215, 215, 230],
+        Some(ObjectId::GoldOre) => return [240, 200, 50],
+        Some(ObjectId::Coal) => return [30, 30, 35],
+        Some(ObjectId::MobLair) => return [140, 20, 20],
+        _ => {}
+    }
+
+    match tile.displayed_id() {
+        TileId::GrassFloor => [90, 150, 60],
+        TileId::StoneFloor => [130, 130, 130],
+        TileId::DungeonFloor => [100, 90, 80],
+        TileId::Bridge | TileId::WoodFloor => [150, 110, 70],
+        TileId::SandFloor => [220, 200, 140],
+        TileId::ShallowWater => [90, 150, 220],
+        TileId::Water => [40, 90, 180],
+        TileId::Lava => [230, 90, 20],
+        TileId::DirtWall => [110, 80, 50],
+        TileId::StoneWall => [70, 70, 75],
+        TileId::DungeonWall => [50, 45, 40],
+        TileId::WoodWall => [120, 80, 40],
+        TileId::SnowFloor => [235, 240, 250],
+        TileId::AshFloor => [60, 55, 55],
+    }
+}
+
+// One pixel per tile of the square of chunks centered on the origin, north up
+pub fn render_map(settings: &WorldGenSettings, seed: u32, chunks: u32) -> Image {
+    let size = (chunks * CHUNK_SIZE) as i32;
+    let chunk_min = IVec2::splat(-(chunks as i32) / 2);
+    let pos_min = chunk_min * CHUNK_SIZE as i32;
+    let mut pixels = vec![0; (size * size * 4) as usize];
+
+    for chunk_y in 0..chunks as i32 {
+        for chunk_x in 0..chunks as i32 {
+            let chunk_pos = (chunk_min + IVec2::new(chunk_x, chunk_y)).extend(0);
+            let chunk = generate_chunk(settings, seed, chunk_pos);
+
+            for (i, tile) in chunk.tiles.into_iter().enumerate() {
+                let pos =
+                    TilemapData::chunk_pos_and_local_index_to_pos(chunk_pos, i).xy() - pos_min;
+                let index = (((size - 1 - pos.y) * size + pos.x) * 4) as usize;
+                let [r, g, b] = map_color(tile);
+                pixels[index..index + 4].copy_from_slice(&[r, g, b, 255]);
+            }
+        }
+    }
+
+    Image::new(
+        Extent3d {
+            width: size as u32,
+            height: size as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        pixels,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    )
+}
+
+pub fn render_preview(settings: &WorldGenSettings, seed: u32) -> Image {
+    render_map(settings, seed, PREVIEW_CHUNKS)
+}
+
+// cargo run --release -- render-world [output.png] [chunks] [world name]
+pub fn run_world_render(mut ev_exit: MessageWriter<AppExit>) {
+    let mut args = std::env::args().skip(2);
+    let path = args.next().unwrap_or_else(|| "world.png".to_string());
+    let chunks = match args.next().map(|arg| arg.parse::<u32>()) {
+        None => 32,
+        Some(Ok(chunks)) if chunks > 0 => chunks,
+        Some(_) => {
+            error!("The number of chunks must be a positive integer");
+            ev_exit.write(AppExit::error());
+            return;
+        }
+    };
+    let save_name = SaveName(args.next().unwrap_or_else(random_world_name));
+
+    let settings = WorldGenSettings::load();
+
+    let start = Instant::now();
+    let image = render_map(&settings, save_name.seed(), chunks);
+    info!(
+        "Rendered {} ({chunks}x{chunks} chunks) in {:?}",
+        save_name.0,
+        start.elapsed()
+    );
+
+    match image.try_into_dynamic().map(|image| image.save(&path)) {
+        Ok(Ok(())) => info!("Saved to {path}"),
+        Ok(Err(err)) => error!("Failed to save {path}: {err}"),
+        Err(err) => error!("Failed to convert the map: {err}"),
+    }
+
+    ev_exit.write(AppExit::Success);
+}
@@ -1,8 +1,16 @@
 use bevy::prelude::*;
 
 use crate::{
-    ActionKind, Dweller, DwellersSelected, TaskKind, TaskNeeds, UiButton, actions::CurrentAction,
-    data::BUILD_RECIPES, extract_ok, utils::pascal_case_to_title_case,
+    ActionKind, Dweller, DwellersSelected, TaskKind, TaskNeeds, UiButton,
+    actions::CurrentAction,
+    combat::CombatLog,
+    data::{BUILD_RECIPES, ObjectId},
+    extract_ok,
+    room_designer::RoomLibraryUi,
+    social::{EventLog, RelationKind},
+    tilemap::Weather,
+    utils::pascal_case_to_title_case,
+    world_stats::WorldStats,
 };
 
 #[derive(Component)]
@@ -11,6 +19,21 @@ pub struct DwellersSelectedUi;
 #[derive(Component)]
 pub struct CoordinatesUi;
 
+#[derive(Component)]
+pub struct WorldStatsUi;
+
+#[derive(Component)]
+pub struct ClockUi;
+
+#[derive(Component)]
+pub struct CombatLogUi;
+
+#[derive(Component)]
+pub struct EventLogUi;
+
+#[derive(Component)]
+pub struct FamilyTreeUi;
+
 pub fn spawn_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
     commands
         .spawn((
@@ -26,6 +49,54 @@ pub fn spawn_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
             Pickable::IGNORE,
         ))
         .with_children(|c| {
+            c.spawn(Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(10.0),
+                right: Val::Px(10.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::FlexEnd,
+                row_gap: Val::Px(10.0),
+                ..default()
+            })
+            .with_children(|c| {
+                c.spawn((
+                    ClockUi,
+                    Text::new(""),
+                    BackgroundColor(Color::BLACK.with_alpha(0.5)),
+                ));
+
+                c.spawn((
+                    FamilyTreeUi,
+                    Text::new(""),
+                    TextFont::from_font_size(14.0),
+                    BackgroundColor(Color::BLACK.with_alpha(0.5)),
+                ));
+            });
+
+            c.spawn(Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(10.0),
+                left: Val::Px(10.0),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(10.0),
+                ..default()
+            })
+            .with_children(|c| {
+                c.spawn((
+                    CombatLogUi,
+                    Text::new(""),
+                    TextFont::from_font_size(14.0),
+                    BackgroundColor(Color::BLACK.with_alpha(0.5)),
+                ));
+
+                c.spawn((
+                    EventLogUi,
+                    Text::new(""),
+                    TextFont::from_font_size(14.0),
+                    BackgroundColor(Color::BLACK.with_alpha(0.5)),
+                ));
+            });
+
             c.spawn((
                 DwellersSelectedUi,
                 Text::new(""),
@@ -38,6 +109,23 @@ pub fn spawn_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
                 BackgroundColor(Color::BLACK.with_alpha(0.5)),
             ));
 
+            c.spawn((
+                WorldStatsUi,
+                Text::new(""),
+                BackgroundColor(Color::BLACK.with_alpha(0.5)),
+            ));
+
+            // Filled by update_room_library_ui
+            c.spawn((
+                RoomLibraryUi,
+                Node {
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    column_gap: Val::Px(10.0),
+                    ..default()
+                },
+            ));
+
             c.spawn(Node {
                 align_items: AlignItems::Center,
                 justify_content: JustifyContent::Center,
@@ -69,10 +157,12 @@ pub fn spawn_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
                     TaskKind::Flood,
                     TaskKind::Scoop,
                     TaskKind::Attack,
+                    TaskKind::Recruit,
                     TaskKind::Fish,
                     TaskKind::Pickup,
                     TaskKind::Stockpile,
                     TaskKind::Smoothen,
+                    TaskKind::Deconstruct,
                     TaskKind::Walk,
                 ] {
                     c.spawn(UiButton)
@@ -83,6 +173,21 @@ pub fn spawn_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
                         .observe(get_observer_action_button(ActionKind::Task(task_kind)));
                 }
 
+                c.spawn(UiButton)
+                    .with_child(Text::new("Extinguish"))
+                    .with_child(ImageNode::new(
+                        asset_server.load(TaskKind::Extinguish.sprite_path()),
+                    ))
+                    .observe(get_observer_action_button(ActionKind::TaskWithNeeds(
+                        TaskKind::Extinguish,
+                        TaskNeeds::Objects(vec![ObjectId::WaterBucket]),
+                    )));
+
+                c.spawn(UiButton)
+                    .with_child(Text::new("Room"))
+                    .with_child(ImageNode::new(asset_server.load("tasks/build.png")))
+                    .observe(get_observer_action_button(ActionKind::WalledRoom));
+
                 c.spawn(UiButton)
                     .with_child(Text::new("Cancel"))
                     .observe(get_observer_action_button(ActionKind::Cancel));
@@ -129,3 +234,117 @@ pub fn update_dwellers_selected(
             .join(", ");
     }
 }
+
+pub fn update_world_stats_ui(
+    world_stats: If<Res<WorldStats>>,
+    mut q_world_stats_ui: Query<&mut Text, With<WorldStatsUi>>,
+) {
+    if world_stats.is_changed() {
+        let mut world_stats_ui = extract_ok!(q_world_stats_ui.single_mut());
+        world_stats_ui.0 = format!("Tiles discovered: {}", world_stats.tiles_discovered);
+    }
+}
+
+pub fn update_clock_ui(weather: If<Res<Weather>>, mut q_clock_ui: Query<&mut Text, With<ClockUi>>) {
+    let mut clock_ui = extract_ok!(q_clock_ui.single_mut());
+    let clock = weather.clock();
+
+    if clock_ui.0 != clock {
+        clock_ui.0 = clock;
+    }
+}
+
+pub fn update_combat_log_ui(
+    combat_log: Res<CombatLog>,
+    mut q_combat_log_ui: Query<&mut Text, With<CombatLogUi>>,
+) {
+    if combat_log.is_changed() {
+        let mut combat_log_ui = extract_ok!(q_combat_log_ui.single_mut());
+        combat_log_ui.0 = combat_log.0.iter().cloned().collect::<Vec<_>>().join("\n");
+    }
+}
+
+pub fn update_event_log_ui(
+    event_log: Res<EventLog>,
+    mut q_event_log_ui: Query<&mut Text, With<EventLogUi>>,
+) {
+    if event_log.is_changed() {
+        let mut event_log_ui = extract_ok!(q_event_log_ui.single_mut());
+        event_log_ui.0 = event_log.0.iter().cloned().collect::<Vec<_>>().join("\n");
+    }
+}
+
+// Family and relationships of the first selected dweller
+pub fn update_family_tree_ui(
+    dwellers_selected: Res<DwellersSelected>,
+    q_dwellers: Query<&Dweller>,
+    mut q_family_tree_ui: Query<&mut Text, With<FamilyTreeUi>>,
+) {
+    let mut family_tree_ui = extract_ok!(q_family_tree_ui.single_mut());
+
+    let text = dwellers_selected
+        .list()
+        .first()
+        .and_then(|entity| q_dwellers.get(*entity).ok())
+        .map(|dweller| family_tree(dweller, &q_dwellers))
+        .unwrap_or_default();
+
+    if family_tree_ui.0 != text {
+        family_tree_ui.0 = text;
+    }
+}
+
+fn family_tree(dweller: &Dweller, q_dwellers: &Query<&Dweller>) -> String {
+    let mut text = dweller.name.clone();
+
+    if dweller.parents.is_empty() {
+        text += "\nFounder";
+    } else {
+        let parents = dweller
+            .parents
+            .iter()
+            .map(|(_, name)| name.as_str())
+            .collect::<Vec<_>>();
+        text += &format!("\nParents: {}", parents.join(", "));
+    }
+
+    if let Some(partner) = dweller.partner {
+        let name = dweller
+            .relations
+            .iter()
+            .find(|relation| relation.dweller == partner)
+            .map_or("?", |relation| relation.name.as_str());
+        text += &format!("\nPartner: {name}");
+    }
+
+    let mut children = String::new();
+    descendants(dweller, q_dwellers, 1, &mut children);
+    if !children.is_empty() {
+        text += &format!("\nChildren:{children}");
+    }
+
+    for kind in [RelationKind::Friend, RelationKind::Rival] {
+        let names = dweller
+            .relations
+            .iter()
+            .filter(|relation| dweller.relation_kind(relation.dweller) == Some(kind))
+            .map(|relation| format!("{} ({:+})", relation.name, relation.opinion))
+            .collect::<Vec<_>>();
+        if !names.is_empty() {
+            text += &format!("\n{kind:?}s: {}", names.join(", "));
+        }
+    }
+
+    text
+}
+
+// Children of living dwellers, indented by generation
+fn descendants(dweller: &Dweller, q_dwellers: &Query<&Dweller>, depth: usize, text: &mut String) {
+    for child in q_dwellers
+        .iter()
+        .filter(|child| child.parents.iter().any(|(uuid, _)| *uuid == dweller.uuid))
+    {
+        *text += &format!("\n{}{}", "  ".repeat(depth), child.name);
+        descendants(child, q_dwellers, depth + 1, text);
+    }
+}
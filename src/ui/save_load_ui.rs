@@ -1,23 +1,81 @@
+use std::time::SystemTime;
+
 use bevy::prelude::*;
 
 use crate::{
-    GameState, LoadGame, SAVE_DIR, SaveChunk, SaveName, TilemapData, UiButton, UiWindow,
-    save_load::SaveResources,
+    GameState, LoadGame, NewGame, SAVE_DIR, SaveChunk, SaveName, TilemapData, UiButton, UiWindow,
+    WorldGenSettings, extract_ok, random_world_name, render_preview, save_load::SaveResources,
 };
 
+// Candidate world shown in the load/save window
+#[derive(Component)]
+pub struct WorldPreview {
+    name: String,
+    settings: WorldGenSettings,
+    modified: Option<SystemTime>,
+}
+
 pub fn spawn_load_save_ui(
     mut commands: Commands,
     mut next_state: ResMut<NextState<GameState>>,
     keyboard_input: Res<ButtonInput<KeyCode>>,
     q_windows: Query<Entity, With<UiWindow>>,
     save_name: Res<SaveName>,
+    mut images: ResMut<Assets<Image>>,
 ) {
     if keyboard_input.just_pressed(KeyCode::KeyM) {
         if let Some(window) = q_windows.iter().next() {
             commands.entity(window).despawn();
             next_state.set(GameState::Running);
         } else {
+            let name = random_world_name();
+            let modified = WorldGenSettings::modified();
+            let settings = WorldGenSettings::load();
+            let preview = images.add(render_preview(&settings, SaveName(name.clone()).seed()));
+
             commands.spawn(UiWindow).with_children(|c| {
+                // New world, previewed with the settings from the file as it is edited
+                c.spawn(Node {
+                    column_gap: Val::Px(10.),
+                    margin: UiRect::bottom(Val::Px(10.)),
+                    ..default()
+                })
+                .with_children(|c| {
+                    c.spawn((
+                        ImageNode::new(preview),
+                        Node {
+                            width: Val::Px(256.),
+                            height: Val::Px(256.),
+                            ..default()
+                        },
+                        WorldPreview {
+                            name: name.clone(),
+                            settings,
+                            modified,
+                        },
+                    ));
+
+                    c.spawn(UiButton)
+                        .with_child(Text::new(format!("New world {name}")))
+                        .observe(
+                            |_: On<Pointer<Click>>,
+                             mut commands: Commands,
+                             q_preview: Query<&WorldPreview>,
+                             q_windows: Query<Entity, With<UiWindow>>| {
+                                let preview = extract_ok!(q_preview.single());
+                                commands.trigger(NewGame {
+                                    name: preview.name.clone(),
+                                    settings: preview.settings.clone(),
+                                });
+
+                                // Close the load/save window
+                                if let Some(window) = q_windows.iter().next() {
+                                    commands.entity(window).despawn();
+                                }
+                            },
+                        );
+                });
+
                 // Save button
                 c.spawn(UiButton)
                     .with_child(Text::new(format!("Save {}", save_name.0)))
@@ -80,3 +138,23 @@ pub fn spawn_load_save_ui(
         }
     }
 }
+
+// Edit assets/world_gen.txt while the window is open to tune the generation
+pub fn update_world_preview(
+    mut images: ResMut<Assets<Image>>,
+    mut q_preview: Query<(&ImageNode, &mut WorldPreview)>,
+) {
+    let (image_node, mut preview) = extract_ok!(q_preview.single_mut());
+
+    let modified = WorldGenSettings::modified();
+    if modified == preview.modified {
+        return;
+    }
+    preview.modified = modified;
+    preview.settings = WorldGenSettings::load();
+
+    let seed = SaveName(preview.name.clone()).seed();
+    if let Some(mut image) = images.get_mut(&image_node.image) {
+        *image = render_preview(&preview.settings, seed);
+    }
+}
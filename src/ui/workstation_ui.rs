@@ -3,7 +3,9 @@ use std::time::{SystemTime, UNIX_EPOCH};
 use bevy::{platform::collections::HashMap, prelude::*};
 
 use crate::{
-    BG_PRIMARY, Task, TaskKind, TilePlaced, TilemapData, data::WORKSTATIONS, extract_ok,
+    BG_PRIMARY, Task, TaskKind, TaskNeeds, TilePlaced, TilemapData,
+    data::{WORKSTATIONS, workstation_recipe},
+    extract_ok, extract_some,
     tasks::WorkstationAmount,
 };
 
@@ -80,7 +82,7 @@ pub fn update_workstation_ui(
     tilemap_data: Res<TilemapData>,
     q_workstation_ui: Query<(Entity, &WorkstationUi)>,
     q_tasks: Query<&Task>,
-    mut changes: Local<HashMap<u128, WorkstationAmount>>,
+    mut changes: Local<HashMap<u128, (WorkstationAmount, usize)>>,
 ) {
     for (ui_entity, workstation_ui) in &q_workstation_ui {
         let entity = workstation_ui.0;
@@ -89,7 +91,7 @@ pub fn update_workstation_ui(
             continue;
         };
 
-        let TaskKind::Workstation { amount } = task.kind else {
+        let TaskKind::Workstation { amount, recipe } = task.kind else {
             continue;
         };
 
@@ -101,7 +103,7 @@ pub fn update_workstation_ui(
             continue;
         };
 
-        let Some(recipe) = WORKSTATIONS.get(&workstation) else {
+        let Some(output) = workstation_recipe(workstation, recipe).map(|recipe| recipe.0) else {
             continue;
         };
 
@@ -109,13 +111,13 @@ pub fn update_workstation_ui(
             continue;
         };
 
-        if let Some(old_amount) = changes.get(&workstation_ui.1)
-            && *old_amount == amount
+        if let Some(old) = changes.get(&workstation_ui.1)
+            && *old == (amount, recipe)
         {
             continue;
         }
 
-        changes.insert(workstation_ui.1, amount);
+        changes.insert(workstation_ui.1, (amount, recipe));
 
         ec.despawn_related::<Children>().with_children(|c| {
             // Minus button
@@ -140,9 +142,30 @@ pub fn update_workstation_ui(
                 },
             );
 
-            c.spawn(ImageNode::new(
-                asset_server.load(recipe.0.data().sprite_path()),
-            ));
+            // Recipe button, cycles through the workstation recipes while nobody works on it
+            c.spawn((
+                Button,
+                ImageNode::new(asset_server.load(output.data().sprite_path())),
+            ))
+            .observe(
+                move |mut pointer_click: On<Pointer<Click>>,
+                      tilemap_data: Res<TilemapData>,
+                      mut q_tasks: Query<(&mut Task, &mut TaskNeeds)>| {
+                    pointer_click.propagate(false);
+                    let (mut task, mut task_needs) = extract_ok!(q_tasks.get_mut(entity));
+                    if task.dweller_id.is_some() {
+                        return;
+                    }
+
+                    let workstation =
+                        extract_some!(tilemap_data.get(task.pos).and_then(|tile| tile.object));
+                    let recipes = extract_some!(WORKSTATIONS.get(&workstation));
+                    if let TaskKind::Workstation { ref mut recipe, .. } = task.kind {
+                        *recipe = (*recipe + 1) % recipes.len();
+                        *task_needs = TaskNeeds::Objects(recipes[*recipe].1.clone());
+                    }
+                },
+            );
             c.spawn(Text::new(format!("{amount}")));
 
             // Plus button
@@ -1,6 +1,8 @@
 use std::path::Path;
 
-use bevy::{math::IVec2, prelude::*};
+use bevy::prelude::*;
+
+use crate::Layer;
 
 #[macro_export]
 macro_rules! extract_ok {
@@ -44,9 +46,10 @@ pub fn pascal_case_to_title_case(input: &str) -> String {
     result
 }
 
-pub fn transform_to_pos(transform: &Transform) -> IVec2 {
-    IVec2::new(
+pub fn transform_to_pos(transform: &Transform, layer: &Layer) -> IVec3 {
+    IVec3::new(
         (transform.translation.x / crate::TILE_SIZE) as i32,
         (transform.translation.y / crate::TILE_SIZE) as i32,
+        layer.0,
     )
 }
@@ -0,0 +1,8 @@
+use bevy::prelude::*;
+
+// Counters about the whole world, saved with the other resources
+#[derive(Resource, Default, Reflect)]
+#[reflect(Resource, Default)]
+pub struct WorldStats {
+    pub tiles_discovered: u32,
+}